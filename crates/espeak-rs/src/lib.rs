@@ -1,6 +1,6 @@
 use espeak_rs_sys;
 use ffi_support::{rust_string_to_c, FfiStr};
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use regex::Regex;
 use std::env;
 use std::error::Error;
@@ -34,8 +34,35 @@ impl fmt::Display for ESpeakError {
 
 static LANG_SWITCH_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\([^)]*\)").unwrap());
 static STRESS_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"[ˈˌ]").unwrap());
-static ESPEAKNG_INIT: Lazy<ESpeakResult<()>> = Lazy::new(|| {
-    let espeak_data_location = match env::var(PIPER_ESPEAKNG_DATA_DIRECTORY) {
+static ESPEAKNG_INIT: OnceCell<ESpeakResult<PathBuf>> = OnceCell::new();
+
+/// Initialize eSpeak-ng, loading its data from `data_dir` (the directory that
+/// contains the `espeak-ng-data` directory), or from the default locations if `None`.
+///
+/// eSpeak-ng can only be initialized once per process: later calls return the
+/// result of the first one, and fail if they ask for a different `data_dir`.
+/// Phonemization initializes eSpeak-ng from the default locations if needed.
+pub fn initialize(data_dir: Option<&Path>) -> ESpeakResult<()> {
+    let result = ESPEAKNG_INIT.get_or_init(|| match data_dir {
+        Some(dir) if !dir.join(ESPEAKNG_DATA_DIR_NAME).is_dir() => Err(ESpeakError(format!(
+            "No `{ESPEAKNG_DATA_DIR_NAME}` directory found in `{}`",
+            dir.display()
+        ))),
+        Some(dir) => initialize_from(dir.to_path_buf()),
+        None => initialize_from(default_data_location()),
+    });
+    match (result, data_dir) {
+        (Err(e), _) => Err(e.clone()),
+        (Ok(location), Some(dir)) if location != dir => Err(ESpeakError(format!(
+            "eSpeak-ng is already initialized with data from `{}`",
+            location.display()
+        ))),
+        (Ok(_), _) => Ok(()),
+    }
+}
+
+fn default_data_location() -> PathBuf {
+    match env::var(PIPER_ESPEAKNG_DATA_DIRECTORY) {
         Ok(env_dir) => PathBuf::from(env_dir), // 1. From PIPER_ESPEAKNG_DATA_DIRECTORY environment variable
         Err(_) => {
             // 2. From the current working directory (CWD)
@@ -51,7 +78,10 @@ static ESPEAKNG_INIT: Lazy<ESpeakResult<()>> = Lazy::new(|| {
                     .to_path_buf()
             }
         }
-    };
+    }
+}
+
+fn initialize_from(espeak_data_location: PathBuf) -> ESpeakResult<PathBuf> {
     let es_data_path_ptr = if espeak_data_location.join(ESPEAKNG_DATA_DIR_NAME).exists() {
         rust_string_to_c(espeak_data_location.display().to_string())
     } else {
@@ -70,10 +100,10 @@ static ESPEAKNG_INIT: Lazy<ESpeakResult<()>> = Lazy::new(|| {
                 Error code: `{es_sample_rate}`."
             )))
         } else {
-            Ok(())
+            Ok(espeak_data_location)
         }
     }
-});
+}
 
 pub fn text_to_phonemes(
     text: &str,
@@ -102,9 +132,7 @@ pub fn _text_to_phonemes(
     remove_lang_switch_flags: bool,
     remove_stress: bool,
 ) -> ESpeakResult<Vec<String>> {
-    if let Err(ref e) = Lazy::force(&ESPEAKNG_INIT) {
-        return Err(e.clone());
-    }
+    initialize(None)?;
     let set_voice_res = unsafe { espeak_rs_sys::espeak_SetVoiceByName(rust_string_to_c(language)) };
     if set_voice_res != espeak_rs_sys::espeak_ERROR_EE_OK {
         return Err(ESpeakError(format!(
@@ -0,0 +1,192 @@
+use std::f32::consts::PI;
+
+use super::AudioSamples;
+
+/// The response of an `EqBand`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BandKind {
+    /// Boost or cut around `freq_hz`
+    Peaking,
+    /// Boost or cut below `freq_hz`
+    LowShelf,
+    /// Boost or cut above `freq_hz`
+    HighShelf,
+    /// Cut above `freq_hz`; `gain_db` is ignored
+    LowPass,
+    /// Cut below `freq_hz`; `gain_db` is ignored
+    HighPass,
+}
+
+/// One band of a parametric EQ, applied as a biquad filter.
+///
+/// `freq_hz` is clamped below the Nyquist frequency, and `q` to a small positive
+/// value. A `q` of about 0.707 gives shelves and passes without a resonant bump.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EqBand {
+    pub kind: BandKind,
+    pub freq_hz: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+/// A biquad in transposed direct form II, with its own state for every channel
+pub(crate) struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    state: Vec<[f32; 2]>,
+}
+
+impl Biquad {
+    /// The coefficients of the RBJ audio EQ cookbook
+    pub fn new(band: &EqBand, sample_rate: usize, num_channels: usize) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let freq_hz = band.freq_hz.clamp(1.0, sample_rate * 0.49);
+        let w0 = 2.0 * PI * freq_hz / sample_rate;
+        let (sin_w0, cos_w0) = w0.sin_cos();
+        let alpha = sin_w0 / (2.0 * band.q.max(0.01));
+        let a = 10f32.powf(band.gain_db / 40.0);
+        let shelf = 2.0 * a.sqrt() * alpha;
+        let (b0, b1, b2, a0, a1, a2) = match band.kind {
+            BandKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos_w0,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos_w0,
+                1.0 - alpha / a,
+            ),
+            BandKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 + shelf),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos_w0),
+                a * ((a + 1.0) - (a - 1.0) * cos_w0 - shelf),
+                (a + 1.0) + (a - 1.0) * cos_w0 + shelf,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos_w0),
+                (a + 1.0) + (a - 1.0) * cos_w0 - shelf,
+            ),
+            BandKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 + shelf),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_w0),
+                a * ((a + 1.0) + (a - 1.0) * cos_w0 - shelf),
+                (a + 1.0) - (a - 1.0) * cos_w0 + shelf,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos_w0),
+                (a + 1.0) - (a - 1.0) * cos_w0 - shelf,
+            ),
+            BandKind::LowPass => (
+                (1.0 - cos_w0) / 2.0,
+                1.0 - cos_w0,
+                (1.0 - cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+            BandKind::HighPass => (
+                (1.0 + cos_w0) / 2.0,
+                -(1.0 + cos_w0),
+                (1.0 + cos_w0) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos_w0,
+                1.0 - alpha,
+            ),
+        };
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            state: vec![[0.0; 2]; num_channels.max(1)],
+        }
+    }
+    /// Filter interleaved `samples`, carrying the state over to the next call
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let num_channels = self.state.len();
+        for frame in samples.chunks_mut(num_channels) {
+            for (sample, [z1, z2]) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x = *sample;
+                let y = self.b0 * x + *z1;
+                *z1 = self.b1 * x - self.a1 * y + *z2;
+                *z2 = self.b2 * x - self.a2 * y;
+                *sample = y;
+            }
+        }
+    }
+}
+
+/// Cascaded biquads, one per band
+pub(crate) struct FilterChain(Vec<Biquad>);
+
+impl FilterChain {
+    pub fn new(bands: &[EqBand], sample_rate: usize, num_channels: usize) -> Self {
+        Self(Vec::from_iter(
+            bands
+                .iter()
+                .map(|band| Biquad::new(band, sample_rate, num_channels)),
+        ))
+    }
+    pub fn process(&mut self, samples: &mut AudioSamples) {
+        for filter in self.0.iter_mut() {
+            filter.process(samples.as_mut_vec());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq_hz: f32, num_samples: usize) -> Vec<f32> {
+        Vec::from_iter((0..num_samples).map(|i| (2.0 * PI * freq_hz * i as f32 / 16000.0).sin()))
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0f32, |peak, s| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn test_eq_bands() {
+        let band = |kind, freq_hz, gain_db| EqBand {
+            kind,
+            freq_hz,
+            gain_db,
+            q: 0.707,
+        };
+        let cases = [
+            (band(BandKind::Peaking, 1000.0, 6.0), 1000.0, 2.0),
+            (band(BandKind::LowShelf, 200.0, -6.0), 50.0, 0.5),
+            (band(BandKind::HighShelf, 2000.0, 6.0), 6000.0, 2.0),
+            (band(BandKind::LowPass, 500.0, 0.0), 1000.0, 0.24),
+            (band(BandKind::HighPass, 500.0, 0.0), 5000.0, 1.0),
+        ];
+        for (band, freq_hz, expected_gain) in cases {
+            let mut samples = AudioSamples::from(sine(freq_hz, 16000));
+            FilterChain::new(&[band], 16000, 1).process(&mut samples);
+            // skip the transient
+            let gain = peak(&samples.as_slice()[8000..]);
+            assert!((gain - expected_gain).abs() < 0.05, "{:?}: {}", band, gain);
+        }
+    }
+
+    #[test]
+    fn test_eq_state_carries_over_chunks() {
+        let bands = [EqBand {
+            kind: BandKind::Peaking,
+            freq_hz: 300.0,
+            gain_db: 9.0,
+            q: 2.0,
+        }];
+        let input = sine(300.0, 1000);
+        let mut whole = AudioSamples::from(input.clone());
+        FilterChain::new(&bands, 16000, 2).process(&mut whole);
+        let mut chain = FilterChain::new(&bands, 16000, 2);
+        let mut chunked = Vec::new();
+        for chunk in input.chunks(250) {
+            let mut chunk = AudioSamples::from(chunk.to_vec());
+            chain.process(&mut chunk);
+            chunked.extend(chunk.into_vec());
+        }
+        assert_eq!(chunked, whole.into_vec());
+    }
+}
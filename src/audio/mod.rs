@@ -1,7 +1,16 @@
+pub(crate) mod eq;
 pub(crate) mod hanning_window;
 mod samples;
+mod sonic;
 pub mod synth;
+mod textgrid;
 mod wave_writer;
 
-pub use samples::{Audio, AudioInfo, AudioSamples};
-pub use wave_writer::{write_wave_samples_to_file, WaveWriterError};
+pub use eq::{BandKind, EqBand};
+pub use samples::{Audio, AudioInfo, AudioSamples, AudioStats, ConversionMode, ResampleQuality};
+pub use wave_writer::{
+    streaming_wave_header, write_wave_f32_samples_to_buffer, write_wave_f32_samples_to_file,
+    write_wave_g711_to_buffer, write_wave_g711_to_file, write_wave_samples_to_file,
+    write_wave_samples_with_metadata_to_buffer, write_wave_samples_with_metadata_to_file, WavCue,
+    WavEncoding, WavMetadata, WaveWriterError,
+};
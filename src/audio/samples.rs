@@ -1,4 +1,5 @@
 use super::hanning_window;
+use crate::core::{PiperAudioResult, PiperError, PiperResult};
 use std::path::Path;
 
 const PI: f32 = std::f32::consts::PI;
@@ -6,11 +7,160 @@ const I16MIN_F32: f32 = i16::MIN as f32;
 const I16MAX_F32: f32 = i16::MAX as f32;
 const MAX_WAV_VALUE_I16: f32 = 32767.0;
 
+const ULAW_BIAS: i32 = 0x84;
+const ULAW_CLIP: i32 = 32635;
+/// The largest 13-bit magnitude of every A-law segment
+const ALAW_SEGMENT_ENDS: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+fn linear_to_ulaw(sample: i16) -> u8 {
+    let sign = if sample < 0 { 0x80 } else { 0 };
+    let magnitude = (sample as i32).abs().min(ULAW_CLIP) + ULAW_BIAS;
+    // the biased magnitude's top bit is bit 7 to 14
+    let exponent = 24 - (magnitude as u32).leading_zeros() as i32;
+    let mantissa = (magnitude >> (exponent + 3)) & 0x0F;
+    !(sign | (exponent << 4) | mantissa) as u8
+}
+
+fn linear_to_alaw(sample: i16) -> u8 {
+    let sample = sample as i32 >> 3;
+    let (mask, magnitude) = if sample >= 0 {
+        (0xD5, sample)
+    } else {
+        (0x55, -sample - 1)
+    };
+    let value = match ALAW_SEGMENT_ENDS.iter().position(|&end| magnitude <= end) {
+        Some(segment) => {
+            let shift = segment.max(1);
+            ((segment as i32) << 4) | ((magnitude >> shift) & 0x0F)
+        }
+        None => 0x7F,
+    };
+    (value ^ mask) as u8
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioInfo {
+    /// The rate of the samples, and of any file they're written to
     pub sample_rate: usize,
     pub num_channels: usize,
     pub sample_width: usize,
+    /// The rate the model produced the audio at, which stays the same when the
+    /// audio is resampled
+    pub native_sample_rate: usize,
+}
+
+/// Level statistics of a block of samples.
+///
+/// All values are computed over the interleaved samples of every channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AudioStats {
+    /// The largest absolute sample value
+    pub peak: f32,
+    pub rms: f32,
+    /// The mean sample value
+    pub dc_offset: f32,
+    /// The number of samples at or beyond full scale (`1.0`)
+    pub clipped_sample_count: usize,
+}
+
+/// How scaled samples are rounded to integers when converting to `i16`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// Round to the nearest integer, and ties to the even one
+    #[default]
+    RoundHalfToEven,
+    /// Round to the nearest integer, and ties away from zero
+    RoundHalfAwayFromZero,
+    /// Round toward zero
+    Truncate,
+}
+
+impl ConversionMode {
+    #[inline(always)]
+    fn round(self, value: f32) -> f32 {
+        match self {
+            Self::RoundHalfToEven => value.round_ties_even(),
+            Self::RoundHalfAwayFromZero => value.round(),
+            Self::Truncate => value.trunc(),
+        }
+    }
+}
+
+/// The interpolation `Audio::resample` uses between input frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Linear interpolation between neighbouring frames; the fastest, but it dulls
+    /// high frequencies and aliases when downsampling
+    #[default]
+    Linear,
+    /// Catmull-Rom interpolation over four frames
+    Cubic,
+    /// Hann-windowed sinc interpolation, low-pass filtered when downsampling; the
+    /// slowest and most accurate
+    Sinc,
+}
+
+/// Zero crossings on either side of the center of the sinc kernel
+const SINC_HALF_WIDTH: f64 = 16.0;
+
+/// The value of `channel` of interleaved `samples` at the fractional frame
+/// `position`. `cutoff` is the sinc kernel's cutoff, relative to the input Nyquist.
+fn interpolate(
+    samples: &[f32],
+    num_channels: usize,
+    channel: usize,
+    position: f64,
+    quality: ResampleQuality,
+    cutoff: f64,
+) -> f32 {
+    let num_frames = (samples.len() / num_channels) as isize;
+    let at =
+        |frame: isize| samples[frame.clamp(0, num_frames - 1) as usize * num_channels + channel];
+    let frame = position as isize;
+    let fraction = (position - frame as f64) as f32;
+    match quality {
+        ResampleQuality::Linear => {
+            let (current, next) = (at(frame), at(frame + 1));
+            current + (next - current) * fraction
+        }
+        ResampleQuality::Cubic => {
+            let (p0, p1, p2, p3) = (at(frame - 1), at(frame), at(frame + 1), at(frame + 2));
+            let t = fraction;
+            p1 + 0.5
+                * t
+                * (p2 - p0
+                    + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
+        }
+        ResampleQuality::Sinc => {
+            let half_width = SINC_HALF_WIDTH / cutoff;
+            let first = ((position - half_width).ceil() as isize).max(0);
+            let last = ((position + half_width).floor() as isize).min(num_frames - 1);
+            let (mut sum, mut weight_sum) = (0.0, 0.0);
+            for frame in first..=last {
+                let x = frame as f64 - position;
+                let window = 0.5 + 0.5 * (std::f64::consts::PI * x / half_width).cos();
+                let weight = sinc(cutoff * x) * window;
+                sum += weight * samples[frame as usize * num_channels + channel] as f64;
+                weight_sum += weight;
+            }
+            // normalized, so the gain stays right where the kernel runs off the edges
+            if weight_sum.abs() < f64::EPSILON {
+                0.0
+            } else {
+                (sum / weight_sum) as f32
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -21,6 +171,11 @@ impl AudioSamples {
     pub fn new(samples: Vec<f32>) -> Self {
         Self(samples)
     }
+    /// Empty samples with room for `capacity` samples, e.g. from the sample count
+    /// of an estimated duration, so joining sentences doesn't reallocate
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
     pub fn as_slice(&self) -> &[f32] {
         self.0.as_slice()
     }
@@ -46,7 +201,16 @@ impl AudioSamples {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+    /// Peak-normalize the samples to the `i16` range, rounding half to even.
+    ///
+    /// Older versions truncated toward zero instead; use
+    /// `to_i16_vec_with(ConversionMode::Truncate)` to reproduce their output.
     pub fn to_i16_vec(&self) -> Vec<i16> {
+        self.to_i16_vec_with(ConversionMode::default())
+    }
+    /// Like `to_i16_vec`, but rounds with `mode`. Values that still fall outside
+    /// the `i16` range saturate to `i16::MIN` / `i16::MAX`, and NaNs become 0.
+    pub fn to_i16_vec_with(&self, mode: ConversionMode) -> Vec<i16> {
         if self.is_empty() {
             return Default::default();
         }
@@ -68,15 +232,77 @@ impl AudioSamples {
         Vec::from_iter(
             self.0
                 .iter()
-                .map(|f| (f * audio_scale).clamp(I16MIN_F32, I16MAX_F32) as i16),
+                .map(|f| mode.round(f * audio_scale).clamp(I16MIN_F32, I16MAX_F32) as i16),
         )
     }
+    /// Convert to `i16` at a fixed scale, `1.0` being `i16::MAX`, instead of
+    /// peak-normalizing, so the chunks of a stream convert consistently
+    pub fn to_i16_vec_fixed_scale(&self) -> Vec<i16> {
+        Vec::from_iter(self.0.iter().map(|f| {
+            ConversionMode::default()
+                .round(f * MAX_WAV_VALUE_I16)
+                .clamp(I16MIN_F32, I16MAX_F32) as i16
+        }))
+    }
+    /// G.711 μ-law bytes of the samples, peak-normalized like `to_i16_vec`
+    pub fn to_ulaw(&self) -> Vec<u8> {
+        Vec::from_iter(self.to_i16_vec().into_iter().map(linear_to_ulaw))
+    }
+    /// G.711 A-law bytes of the samples, peak-normalized like `to_i16_vec`
+    pub fn to_alaw(&self) -> Vec<u8> {
+        Vec::from_iter(self.to_i16_vec().into_iter().map(linear_to_alaw))
+    }
     pub fn as_wave_bytes(&self) -> Vec<u8> {
         Vec::from_iter(self.to_i16_vec().into_iter().flat_map(|i| i.to_le_bytes()))
     }
+    /// The samples widened to `f64`, leaving these samples as they are
+    #[allow(clippy::wrong_self_convention)]
+    pub fn into_f64_vec(&self) -> Vec<f64> {
+        Vec::from_iter(self.0.iter().copied().map(f64::from))
+    }
     pub fn merge(&mut self, mut other: Self) {
         self.0.append(other.0.as_mut());
     }
+    /// Add `other`, scaled by `gain`, to these samples, zero-extending whichever
+    /// is shorter. The result isn't clamped.
+    pub fn mix(&mut self, other: &Self, gain: f32) {
+        self.mix_at(other, 0, 1, gain);
+    }
+    /// Like `mix`, but starts mixing `other` `offset_frames` frames into these
+    /// interleaved samples. Both must have `num_channels` channels.
+    pub fn mix_at(&mut self, other: &Self, offset_frames: usize, num_channels: usize, gain: f32) {
+        let offset = offset_frames * num_channels.max(1);
+        let end = offset + other.len();
+        if end > self.len() {
+            self.0.resize(end, 0.0);
+        }
+        for (sample, other_sample) in self.0[offset..end].iter_mut().zip(other.0.iter()) {
+            *sample += other_sample * gain;
+        }
+    }
+    /// Average every frame of these interleaved `num_channels` channel samples. A
+    /// trailing partial frame is averaged over the samples it has.
+    pub fn to_mono(&self, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        if num_channels == 1 {
+            return self.clone();
+        }
+        Vec::from_iter(
+            self.0
+                .chunks(num_channels)
+                .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32),
+        )
+        .into()
+    }
+    pub fn apply_gain(&mut self, gain: f32) {
+        self.0.iter_mut().for_each(|f| *f *= gain);
+    }
+    /// Scale each channel of these interleaved samples by its entry of `gains`
+    pub fn apply_channel_gains(&mut self, gains: &[f32]) {
+        for frame in self.0.chunks_mut(gains.len().max(1)) {
+            frame.iter_mut().zip(gains).for_each(|(s, gain)| *s *= gain);
+        }
+    }
     pub fn normalize(&mut self, max_value: f32) {
         if self.is_empty() {
             return;
@@ -177,6 +403,45 @@ impl AudioSamples {
         );
         self.0.splice(sample_range, nonsilence).count();
     }
+    /// Replace non-finite samples with zero and clamp the rest to `-1.0..=1.0`.
+    /// Returns the number of non-finite samples that were replaced.
+    pub fn sanitize(&mut self) -> usize {
+        let mut num_non_finite = 0;
+        for sample in self.0.iter_mut() {
+            if sample.is_finite() {
+                *sample = sample.clamp(-1.0, 1.0);
+            } else {
+                *sample = 0.0;
+                num_non_finite += 1;
+            }
+        }
+        num_non_finite
+    }
+    pub fn stats(&self) -> AudioStats {
+        if self.is_empty() {
+            return AudioStats::default();
+        }
+        let mut peak = 0f32;
+        let mut sum = 0f64;
+        let mut sum_of_squares = 0f64;
+        let mut clipped_sample_count = 0;
+        for sample in self.0.iter().copied() {
+            let magnitude = sample.abs();
+            peak = peak.max(magnitude);
+            if magnitude >= 1.0 {
+                clipped_sample_count += 1;
+            }
+            sum += sample as f64;
+            sum_of_squares += (sample as f64).powi(2);
+        }
+        let len = self.len() as f64;
+        AudioStats {
+            peak,
+            rms: (sum_of_squares / len).sqrt() as f32,
+            dc_offset: (sum / len) as f32,
+            clipped_sample_count,
+        }
+    }
     pub fn to_decibel(&self) -> Vec<f32> {
         Vec::from_iter(self.0.iter().map(|x| 20.0 * x.abs().log10()))
     }
@@ -220,10 +485,175 @@ impl Audio {
                 sample_rate,
                 num_channels: 1,
                 sample_width: 2,
+                native_sample_rate: sample_rate,
             },
         }
     }
 
+    /// Join `parts` into one `Audio`. All parts must share the same sample rate and
+    /// channel count, and there must be at least one part.
+    pub fn concat(parts: Vec<Audio>) -> PiperAudioResult {
+        let num_samples: usize = parts.iter().map(Audio::len).sum();
+        let mut parts = parts.into_iter();
+        let Some(first) = parts.next() else {
+            return Err(PiperError::OperationError(
+                "Can not concatenate an empty list of audio".to_string(),
+            ));
+        };
+        let mut samples = AudioSamples::with_capacity(num_samples);
+        samples.merge(first.samples);
+        let mut joined = Audio { samples, ..first };
+        for (index, part) in parts.enumerate() {
+            joined.append(part).map_err(|e| {
+                PiperError::OperationError(format!("Audio part {}: {}", index + 1, e))
+            })?;
+        }
+        Ok(joined)
+    }
+
+    /// Append the samples of `other`, which must have the same sample rate, channel
+    /// count and sample width. Inference times add up.
+    pub fn append(&mut self, other: Audio) -> PiperResult<()> {
+        if other.info.sample_rate != self.info.sample_rate
+            || other.info.num_channels != self.info.num_channels
+            || other.info.sample_width != self.info.sample_width
+        {
+            return Err(PiperError::OperationError(format!(
+                "Can not append {} Hz audio with {} channel(s) of {} byte samples to {} Hz audio with {} channel(s) of {} byte samples",
+                other.info.sample_rate,
+                other.info.num_channels,
+                other.info.sample_width,
+                self.info.sample_rate,
+                self.info.num_channels,
+                self.info.sample_width
+            )));
+        }
+        if let Some(ms) = other.inference_ms {
+            *self.inference_ms.get_or_insert(0.0) += ms;
+        }
+        self.samples.merge(other.samples);
+        Ok(())
+    }
+
+    /// Downmix to a single channel by averaging the channels of every frame
+    pub fn to_mono(&self) -> Audio {
+        Audio {
+            samples: self.samples.to_mono(self.info.num_channels),
+            info: AudioInfo {
+                num_channels: 1,
+                ..self.info.clone()
+            },
+            inference_ms: self.inference_ms,
+        }
+    }
+
+    /// Copy out the frames in `start_frame..end_frame`
+    pub fn slice(&self, start_frame: usize, end_frame: usize) -> PiperAudioResult {
+        let num_channels = self.info.num_channels.max(1);
+        let num_frames = self.len() / num_channels;
+        if start_frame > end_frame || end_frame > num_frames {
+            return Err(PiperError::OperationError(format!(
+                "Frame range {}..{} is out of bounds for audio with {} frames",
+                start_frame, end_frame, num_frames
+            )));
+        }
+        let samples =
+            &self.samples.as_slice()[start_frame * num_channels..end_frame * num_channels];
+        Ok(Audio {
+            samples: samples.to_vec().into(),
+            info: self.info.clone(),
+            inference_ms: None,
+        })
+    }
+
+    /// Split the interleaved samples into one `Vec` per channel
+    pub fn to_planar(&self) -> Vec<Vec<f32>> {
+        let num_channels = self.info.num_channels.max(1);
+        let mut channels = vec![Vec::with_capacity(self.len() / num_channels); num_channels];
+        for frame in self.samples.as_slice().chunks_exact(num_channels) {
+            for (channel, sample) in channels.iter_mut().zip(frame) {
+                channel.push(*sample);
+            }
+        }
+        channels
+    }
+
+    /// Interleave per-channel samples into one `Audio`. All channels must have the
+    /// same length.
+    pub fn from_planar(channels: Vec<Vec<f32>>, sample_rate: usize) -> PiperAudioResult {
+        let Some(num_frames) = channels.first().map(Vec::len) else {
+            return Err(PiperError::with_message("Audio needs at least one channel"));
+        };
+        if channels.iter().any(|channel| channel.len() != num_frames) {
+            return Err(PiperError::with_message(
+                "All channels must have the same number of samples",
+            ));
+        }
+        let mut samples = Vec::with_capacity(num_frames * channels.len());
+        for frame in 0..num_frames {
+            samples.extend(channels.iter().map(|channel| channel[frame]));
+        }
+        let mut audio = Audio::new(samples.into(), sample_rate, None);
+        audio.info.num_channels = channels.len();
+        Ok(audio)
+    }
+
+    /// Resample every channel to `sample_rate`, interpolating with `quality`
+    pub fn resample(&self, sample_rate: usize, quality: ResampleQuality) -> PiperAudioResult {
+        if sample_rate == 0 || self.info.sample_rate == 0 {
+            return Err(PiperError::OperationError(format!(
+                "Can't resample {} Hz audio to {} Hz",
+                self.info.sample_rate, sample_rate
+            )));
+        }
+        Ok(self.clone().resample_with(sample_rate, quality))
+    }
+
+    /// Resample to `sample_rate` by linear interpolation between frames
+    pub(crate) fn resample_linear(self, sample_rate: usize) -> Self {
+        self.resample_with(sample_rate, ResampleQuality::Linear)
+    }
+
+    fn resample_with(self, sample_rate: usize, quality: ResampleQuality) -> Self {
+        let num_channels = self.info.num_channels.max(1);
+        let num_frames = self.len() / num_channels;
+        if sample_rate == self.info.sample_rate || num_frames == 0 {
+            return Self {
+                info: AudioInfo {
+                    sample_rate,
+                    ..self.info
+                },
+                ..self
+            };
+        }
+        let samples = self.samples.as_slice();
+        let num_out_frames = num_frames * sample_rate / self.info.sample_rate;
+        let step = self.info.sample_rate as f64 / sample_rate as f64;
+        let cutoff = (1.0 / step).min(1.0);
+        let mut resampled = Vec::with_capacity(num_out_frames * num_channels);
+        for out_frame in 0..num_out_frames {
+            let position = out_frame as f64 * step;
+            for channel in 0..num_channels {
+                resampled.push(interpolate(
+                    samples,
+                    num_channels,
+                    channel,
+                    position,
+                    quality,
+                    cutoff,
+                ));
+            }
+        }
+        Self {
+            samples: resampled.into(),
+            info: AudioInfo {
+                sample_rate,
+                ..self.info
+            },
+            inference_ms: self.inference_ms,
+        }
+    }
+
     pub fn into_vec(self) -> Vec<f32> {
         self.samples.into_vec()
     }
@@ -244,6 +674,17 @@ impl Audio {
         (self.len() as f32 / self.info.sample_rate as f32) * 1000.0f32
     }
 
+    pub fn stats(&self) -> AudioStats {
+        self.samples.stats()
+    }
+
+    /// Mix `other` into this audio starting `offset_frames` frames in.
+    /// `other` is assumed to have the same sample rate and channel layout.
+    pub fn mix_at(&mut self, other: &Audio, offset_frames: usize, gain: f32) {
+        self.samples
+            .mix_at(&other.samples, offset_frames, self.info.num_channels, gain);
+    }
+
     pub fn inference_ms(&self) -> Option<f32> {
         self.inference_ms
     }
@@ -338,6 +779,188 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_into_f64_vec() {
+        let data = vec![0.0, 0.1, -2.2, 0.5];
+        let s1 = AudioSamples::from(data.clone());
+        let widened = s1.into_f64_vec();
+        assert_eq!(widened.len(), data.len());
+        assert_eq!(widened[2], -2.2f32 as f64);
+        assert_eq!(s1.as_slice(), data.as_slice());
+    }
+
+    #[test]
+    fn test_mix() {
+        let mut s1 = AudioSamples::from(vec![1.0, 1.0]);
+        s1.mix(&AudioSamples::from(vec![0.5, 0.5, 0.5]), 2.0);
+        assert_eq!(s1.as_slice(), &[2.0, 2.0, 1.0]);
+
+        let mut stereo = AudioSamples::from(vec![0.0; 4]);
+        stereo.mix_at(&AudioSamples::from(vec![1.0, -1.0, 1.0, -1.0]), 1, 2, 0.5);
+        assert_eq!(stereo.as_slice(), &[0.0, 0.0, 0.5, -0.5, 0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_concat() {
+        let parts = vec![
+            Audio::new(vec![0.1, 0.2].into(), 22050, Some(1.0)),
+            Audio::new(vec![0.3].into(), 22050, None),
+        ];
+        let joined = Audio::concat(parts).unwrap();
+        assert_eq!(joined.samples.as_slice(), &[0.1, 0.2, 0.3]);
+        assert_eq!(joined.inference_ms(), Some(1.0));
+
+        let mismatched = vec![
+            Audio::new(vec![0.1].into(), 22050, None),
+            Audio::new(vec![0.1].into(), 16000, None),
+        ];
+        assert!(Audio::concat(mismatched).is_err());
+        assert!(Audio::concat(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_append() {
+        let mut audio = Audio::new(vec![0.1].into(), 22050, None);
+        audio
+            .append(Audio::new(vec![0.2].into(), 22050, Some(2.0)))
+            .unwrap();
+        assert_eq!(audio.samples.as_slice(), &[0.1, 0.2]);
+        assert_eq!(audio.inference_ms(), Some(2.0));
+        let mut wide = Audio::new(vec![0.3].into(), 22050, None);
+        wide.info.sample_width = 4;
+        assert!(audio.append(wide).is_err());
+        assert!(audio
+            .append(Audio::new(vec![0.3].into(), 16000, None))
+            .is_err());
+        assert_eq!(audio.samples.as_slice(), &[0.1, 0.2]);
+    }
+
+    #[test]
+    fn test_concat_presizes() {
+        let parts = vec![
+            Audio::new(vec![0.1; 3].into(), 22050, None),
+            Audio::new(vec![0.2; 5].into(), 22050, None),
+        ];
+        let joined = Audio::concat(parts).unwrap();
+        assert_eq!(joined.len(), 8);
+        assert_eq!(joined.samples.as_vec().capacity(), 8);
+        assert!(AudioSamples::with_capacity(16).as_vec().capacity() >= 16);
+    }
+
+    #[test]
+    fn test_i16_fixed_scale() {
+        let samples = AudioSamples::from(vec![0.0, 0.25, -0.5, 2.0, f32::NAN]);
+        assert_eq!(
+            samples.to_i16_vec_fixed_scale(),
+            vec![0, 8192, -16384, 32767, 0]
+        );
+    }
+
+    #[test]
+    fn test_g711() {
+        let linear = [0, -1, 1000, -1000, i16::MAX, i16::MIN];
+        let ulaw = Vec::from_iter(linear.into_iter().map(linear_to_ulaw));
+        assert_eq!(ulaw, vec![0xFF, 0x7F, 0xCE, 0x4E, 0x80, 0x00]);
+        let alaw = Vec::from_iter(linear.into_iter().map(linear_to_alaw));
+        assert_eq!(alaw, vec![0xD5, 0x55, 0xFA, 0x7A, 0xAA, 0x2A]);
+        let samples = AudioSamples::from(vec![0.0, 1.0, -1.0]);
+        assert_eq!(samples.to_ulaw(), vec![0xFF, 0x80, 0x00]);
+        assert_eq!(samples.to_alaw(), vec![0xD5, 0xAA, 0x2A]);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut s1 = AudioSamples::from(vec![f32::NAN, 0.5, f32::INFINITY, -3.0]);
+        assert_eq!(s1.sanitize(), 2);
+        assert_eq!(s1.as_slice(), &[0.0, 0.5, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn test_planar_round_trip() {
+        let channels = vec![vec![0.1, 0.2, 0.3], vec![-0.1, -0.2, -0.3]];
+        let audio = Audio::from_planar(channels.clone(), 22050).unwrap();
+        assert_eq!(audio.info.num_channels, 2);
+        assert_eq!(audio.samples.as_slice(), &[0.1, -0.1, 0.2, -0.2, 0.3, -0.3]);
+        assert_eq!(audio.to_planar(), channels);
+        assert!(Audio::from_planar(vec![vec![0.1], vec![]], 22050).is_err());
+        assert!(Audio::from_planar(Vec::new(), 22050).is_err());
+    }
+
+    #[test]
+    fn test_i16_conversion_modes() {
+        // the peak is already full scale, so the samples aren't rescaled
+        let samples = AudioSamples::from(vec![32767.0, 2.5, -2.5, 3.5]);
+        let convert = |mode| samples.to_i16_vec_with(mode)[1..].to_vec();
+        assert_eq!(convert(ConversionMode::RoundHalfToEven), vec![2, -2, 4]);
+        assert_eq!(
+            convert(ConversionMode::RoundHalfAwayFromZero),
+            vec![3, -3, 4]
+        );
+        assert_eq!(convert(ConversionMode::Truncate), vec![2, -2, 3]);
+    }
+
+    #[test]
+    fn test_resample() {
+        // a 441 Hz tone left, and its inverse right
+        let samples = Vec::from_iter((0..4410).flat_map(|i| {
+            let value = (i as f32 * 2.0 * PI * 441.0 / 22050.0).sin() * 0.5;
+            [value, -value]
+        }));
+        let mut audio = Audio::new(samples.into(), 22050, None);
+        audio.info.num_channels = 2;
+        for quality in [
+            ResampleQuality::Linear,
+            ResampleQuality::Cubic,
+            ResampleQuality::Sinc,
+        ] {
+            let resampled = audio.resample(16000, quality).unwrap();
+            assert_eq!(resampled.info.sample_rate, 16000);
+            assert_eq!(resampled.info.native_sample_rate, 22050);
+            assert_eq!(resampled.info.num_channels, 2);
+            assert_eq!(resampled.len(), 3200 * 2);
+            for (frame, pair) in resampled.samples.as_slice().chunks(2).enumerate() {
+                let expected = (frame as f32 * 2.0 * PI * 441.0 / 16000.0).sin() * 0.5;
+                assert!((pair[0] - expected).abs() < 0.01, "{:?}", quality);
+                assert_eq!(pair[1], -pair[0]);
+            }
+        }
+        assert!(audio.resample(0, ResampleQuality::Sinc).is_err());
+    }
+
+    #[test]
+    fn test_to_mono() {
+        let mut audio = Audio::new(vec![1.0, 0.0, 0.5, 0.5, 0.25].into(), 22050, None);
+        audio.info.num_channels = 2;
+        let mono = audio.to_mono();
+        assert_eq!(mono.info.num_channels, 1);
+        assert_eq!(mono.samples.as_slice(), &[0.5, 0.5, 0.25]);
+        assert_eq!(mono.to_mono().samples.as_slice(), mono.samples.as_slice());
+    }
+
+    #[test]
+    fn test_slice() {
+        let mut audio = Audio::new(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0].into(), 22050, None);
+        audio.info.num_channels = 2;
+        let slice = audio.slice(1, 3).unwrap();
+        assert_eq!(slice.samples.as_slice(), &[2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(slice.info.num_channels, 2);
+        assert_eq!(slice.info.sample_rate, 22050);
+        assert!(audio.slice(2, 2).unwrap().is_empty());
+        assert!(audio.slice(0, 4).is_err());
+        assert!(audio.slice(2, 1).is_err());
+    }
+
+    #[test]
+    fn test_stats() {
+        let data = vec![0.5, -0.5, 1.0, -1.5, 0.5];
+        let stats = AudioSamples::from(data).stats();
+        assert_eq!(stats.peak, 1.5);
+        assert_eq!(stats.clipped_sample_count, 2);
+        assert_eq!(stats.dc_offset, 0.0);
+        assert!((stats.rms - 0.8944272).abs() < 1e-6);
+        assert_eq!(AudioSamples::default().stats(), AudioStats::default());
+    }
+
     #[test]
     fn test_strip_silence() {
         let data = vec![0.0, 0.1, 2.2, 0.0, 0.5, 0.0, 0.7, 0.0];
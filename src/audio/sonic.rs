@@ -0,0 +1,76 @@
+use super::AudioSamples;
+
+/// An owned sonic stream, destroyed on drop
+pub(crate) struct SonicStream {
+    raw: sonic_rs_sys::sonicStream,
+    num_channels: usize,
+}
+
+// The stream is only ever touched through `&mut self`
+unsafe impl Send for SonicStream {}
+
+impl SonicStream {
+    pub fn new(sample_rate: usize, num_channels: usize) -> Self {
+        let raw =
+            unsafe { sonic_rs_sys::sonicCreateStream(sample_rate as i32, num_channels as i32) };
+        Self { raw, num_channels }
+    }
+    pub fn set_speed(&mut self, speed: f32) {
+        unsafe { sonic_rs_sys::sonicSetSpeed(self.raw, speed) }
+    }
+    pub fn set_volume(&mut self, volume: f32) {
+        unsafe { sonic_rs_sys::sonicSetVolume(self.raw, volume) }
+    }
+    pub fn set_pitch(&mut self, pitch: f32) {
+        unsafe { sonic_rs_sys::sonicSetPitch(self.raw, pitch) }
+    }
+    pub fn set_rate(&mut self, rate: f32) {
+        unsafe { sonic_rs_sys::sonicSetRate(self.raw, rate) }
+    }
+    pub fn set_quality(&mut self, quality: i32) {
+        unsafe { sonic_rs_sys::sonicSetQuality(self.raw, quality) }
+    }
+    /// Queue interleaved `samples` for processing
+    pub fn write(&mut self, samples: &[f32]) {
+        let num_frames = samples.len() / self.num_channels.max(1);
+        if num_frames == 0 {
+            return;
+        }
+        unsafe {
+            sonic_rs_sys::sonicWriteFloatToStream(self.raw, samples.as_ptr(), num_frames as i32);
+        }
+    }
+    /// Process any queued input, even if it's too short for sonic to analyze
+    pub fn flush(&mut self) {
+        unsafe {
+            sonic_rs_sys::sonicFlushStream(self.raw);
+        }
+    }
+    /// Take all processed samples sonic has ready
+    pub fn read_available(&mut self) -> AudioSamples {
+        let mut out_buf: Vec<f32> = Vec::new();
+        unsafe {
+            let num_frames = sonic_rs_sys::sonicSamplesAvailable(self.raw);
+            if num_frames <= 0 {
+                return out_buf.into();
+            }
+            out_buf.reserve_exact(num_frames as usize * self.num_channels);
+            // sonic may hand back fewer samples than it reported as available;
+            // only the samples it actually wrote are initialized
+            let num_read = sonic_rs_sys::sonicReadFloatFromStream(
+                self.raw,
+                out_buf.spare_capacity_mut().as_mut_ptr().cast(),
+                num_frames,
+            );
+            let num_read = (num_read.max(0) as usize).min(num_frames as usize);
+            out_buf.set_len(num_read * self.num_channels);
+        }
+        out_buf.into()
+    }
+}
+
+impl Drop for SonicStream {
+    fn drop(&mut self) {
+        unsafe { sonic_rs_sys::sonicDestroyStream(self.raw) }
+    }
+}
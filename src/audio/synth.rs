@@ -3,15 +3,26 @@ use once_cell::sync::Lazy;
 use rayon::prelude::*;
 use rayon::{ThreadPool, ThreadPoolBuilder};
 use std::any::Any;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::panic::AssertUnwindSafe;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
-use crate::audio;
+use crate::audio::eq::{Biquad, FilterChain};
+use crate::audio::sonic::SonicStream;
+use crate::audio::{self, textgrid};
 use crate::core::{
-    Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes, PiperAudioResult, PiperError,
-    PiperModel, PiperResult,
+    Audio, AudioInfo, AudioSamples, AudioStreamIterator, BandKind, ChunkSchedule, EqBand,
+    InputMode, PhonemeChunkIterator, PhonemeJoinPolicy, PhonemeTiming, Phonemes, PiperAudioResult,
+    PiperError, PiperModel, PiperResult, ResampleQuality, WavCue, WavEncoding, WavMetadata,
 };
+use crate::text;
+use crate::{PiperSynthesisConfig, SAMPLES_PER_FRAME};
 
 pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
     let num_cpus = std::thread::available_parallelism()
@@ -24,18 +35,318 @@ pub static SYNTHESIS_THREAD_POOL: Lazy<ThreadPool> = Lazy::new(|| {
         .unwrap()
 });
 
-#[derive(Clone)]
+/// Roughly how long piper voices take to speak one phoneme (or space) at a
+/// `length_scale` of 1
+const AVERAGE_PHONEME_DURATION: Duration = Duration::from_millis(70);
+
+/// Bytes held per output sample at the peak of `synthesize_to_file`
+const PEAK_BYTES_PER_SAMPLE: usize = 4 + 4 + 2 + 2;
+
+/// How long the phrases of a split sentence are crossfaded into each other
+const PHRASE_CROSSFADE_MS: u32 = 5;
+
+/// Synthesis progress measured in audio time, reported by
+/// `PiperSpeechSynthesizer::synthesize_with_duration_progress`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DurationProgress {
+    /// Duration of the audio synthesized so far
+    pub produced: Duration,
+    /// Estimated duration of all the audio, see `PiperSpeechSynthesizer::estimate_duration`
+    pub estimated_total: Duration,
+}
+
+impl DurationProgress {
+    /// `produced / estimated_total`, capped at 1
+    pub fn fraction(&self) -> f32 {
+        if self.estimated_total.is_zero() {
+            return 1.0;
+        }
+        (self.produced.as_secs_f32() / self.estimated_total.as_secs_f32()).min(1.0)
+    }
+}
+
+/// Where the time of `PiperSpeechSynthesizer::synthesize_profiled` went
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SynthesisProfile {
+    /// Splitting the text into sentences and phonemizing them
+    pub phonemize: Duration,
+    /// Model inference, summed over the sentences
+    pub inference: Duration,
+    /// Applying the output config and pauses, summed over the sentences
+    pub postprocess: Duration,
+    /// Wall-clock time of the whole call. Sentences are synthesized in parallel, so
+    /// `inference` and `postprocess` can add up to more than this.
+    pub total: Duration,
+}
+
+/// How to build the threads a synthesizer runs on
+#[derive(Clone, Debug)]
+pub struct SynthesisThreadConfig {
+    pub name_prefix: String,
+    /// Defaults to four threads per CPU
+    pub num_threads: Option<usize>,
+    /// Cross-platform scheduling priority, from 0 (lowest) to 99 (highest).
+    /// Needs the `thread-priority` feature; otherwise a warning is logged and the
+    /// threads keep the default priority.
+    pub priority: Option<u8>,
+}
+
+impl Default for SynthesisThreadConfig {
+    fn default() -> Self {
+        Self {
+            name_prefix: "piper_synth".to_string(),
+            num_threads: None,
+            priority: None,
+        }
+    }
+}
+
+/// The threads of a dedicated pool that haven't exited yet
+struct LiveThreads {
+    count: Mutex<usize>,
+    exited: Condvar,
+}
+
+impl LiveThreads {
+    fn exit(&self) {
+        *self.count.lock().unwrap() -= 1;
+        self.exited.notify_all();
+    }
+    fn wait(&self) {
+        let count = self.count.lock().unwrap();
+        let _count = self.exited.wait_while(count, |count| *count > 0).unwrap();
+    }
+}
+
+impl SynthesisThreadConfig {
+    fn build_thread_pool(&self) -> PiperResult<(ThreadPool, Arc<LiveThreads>)> {
+        let num_threads = self.num_threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(usize::from)
+                .unwrap_or(4)
+                * 4
+        });
+        let name_prefix = self.name_prefix.clone();
+        let priority = self.priority;
+        let live_threads = Arc::new(LiveThreads {
+            count: Mutex::new(0),
+            exited: Condvar::new(),
+        });
+        let exiting_threads = Arc::clone(&live_threads);
+        let pool = ThreadPoolBuilder::new()
+            .thread_name(move |i| format!("{}_{}", name_prefix, i))
+            .num_threads(num_threads)
+            .start_handler(move |_| {
+                if let Some(priority) = priority {
+                    set_current_thread_priority(priority);
+                }
+            })
+            .exit_handler(move |_| exiting_threads.exit())
+            .build()
+            .map_err(|e| {
+                PiperError::OperationError(format!(
+                    "Failed to build synthesis thread pool. Error: {}",
+                    e
+                ))
+            })?;
+        // threads only exit once the pool is dropped, so none has yet
+        *live_threads.count.lock().unwrap() = pool.current_num_threads();
+        Ok((pool, live_threads))
+    }
+}
+
+#[cfg(feature = "thread-priority")]
+fn set_current_thread_priority(priority: u8) {
+    use thread_priority::{ThreadPriority, ThreadPriorityValue};
+    let result = ThreadPriorityValue::try_from(priority)
+        .map_err(|e| e.to_string())
+        .and_then(|value| {
+            thread_priority::set_current_thread_priority(ThreadPriority::Crossplatform(value))
+                .map_err(|e| format!("{:?}", e))
+        });
+    if let Err(e) = result {
+        tracing::warn!("Failed to set synthesis thread priority: {}", e);
+    }
+}
+
+#[cfg(not(feature = "thread-priority"))]
+fn set_current_thread_priority(#[allow(unused_variables)] priority: u8) {
+    tracing::warn!("Setting the synthesis thread priority needs the `thread-priority` feature");
+}
+
+/// Audio playing on an output device, see `PiperSpeechSynthesizer::synthesize_and_play`.
+///
+/// Derefs to the `rodio::Sink` controlling playback. It also keeps the device's
+/// output stream open, which would silence the sink if dropped.
+#[cfg(feature = "rodio")]
+pub struct Playback {
+    sink: rodio::Sink,
+    _stream: rodio::OutputStream,
+}
+
+#[cfg(feature = "rodio")]
+impl std::ops::Deref for Playback {
+    type Target = rodio::Sink;
+
+    fn deref(&self) -> &rodio::Sink {
+        &self.sink
+    }
+}
+
+/// The waveform used for appended silence
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SilenceKind {
+    /// Digital silence
+    #[default]
+    Zeros,
+    /// Very quiet white noise at `level_db` dBFS, which keeps telephony VADs from
+    /// treating pauses as a dropped line. A `seed` makes the noise reproducible.
+    ComfortNoise { level_db: f32, seed: Option<u64> },
+}
+
+impl SilenceKind {
+    /// `time_ms` of silence, in frames of `num_channels` samples
+    fn generate_ms(&self, time_ms: u32, sample_rate: usize, num_channels: usize) -> Vec<f32> {
+        let num_frames = time_ms as usize * sample_rate / 1000;
+        self.generate(num_frames * num_channels)
+    }
+    fn generate(&self, num_samples: usize) -> Vec<f32> {
+        match *self {
+            Self::Zeros => vec![0f32; num_samples],
+            Self::ComfortNoise { level_db, seed } => {
+                let amplitude = 10f32.powf(level_db / 20.0);
+                let mut state = seed.unwrap_or_else(random_seed).max(1);
+                Vec::from_iter((0..num_samples).map(|_| {
+                    // xorshift64
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    let unit = (state >> 40) as f32 / (1u64 << 24) as f32;
+                    (unit * 2.0 - 1.0) * amplitude
+                }))
+            }
+        }
+    }
+}
+
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish()
+}
+
+#[derive(Clone, Default)]
 pub struct AudioOutputConfig {
     pub rate: Option<f32>,
     pub volume: Option<f32>,
     pub pitch: Option<f32>,
     pub appended_silence_ms: Option<u32>,
+    /// Append `appended_silence_ms` as plain zeros after processing instead of
+    /// running the silence through sonic, so its length doesn't depend on `rate`
+    pub raw_appended_silence: bool,
+    pub silence: SilenceKind,
+    /// Zero out NaN/infinite model output and clamp it to `-1.0..=1.0` before any
+    /// other processing. Off by default, but recommended for quantized models,
+    /// whose occasional NaNs would otherwise corrupt the whole output.
+    pub sanitize_output: bool,
+    /// Sonic's playback rate: resamples, changing speed and pitch together
+    pub playback_rate: Option<f32>,
+    /// Use sonic's slower, full-resolution pitch detection for rate/pitch changes,
+    /// which can sound better on aggressive rate changes
+    pub high_quality: bool,
+    /// Silence model output quieter than `gate_threshold_db` dBFS once it has stayed
+    /// below it for `gate_hold_ms`, e.g. to mute a model's trailing hum. Unlike
+    /// trimming, the gated samples are zeroed, so the duration doesn't change.
+    pub gate_threshold_db: Option<f32>,
+    pub gate_hold_ms: u32,
+    /// Downmix multichannel model output to mono, before any other processing
+    pub force_mono: bool,
+    /// Crossfade the end of each sentence of a realtime stream into the start of
+    /// the next. Holds back one chunk of output, so it adds a chunk of latency.
+    pub crossfade_ms: u32,
+    /// Remove DC offset and rumble below this frequency (40-60 Hz suits speech)
+    /// with a Butterworth high-pass, right after `sanitize_output`
+    pub highpass_hz: Option<f32>,
+    /// Parametric EQ bands, applied one after the other once sonic has run
+    pub eq: Option<Vec<EqBand>>,
+    /// Limit the output so its true peak, including the peaks between samples
+    /// that appear once it's converted to analog, stays below this many dBFS.
+    /// Runs last, after any volume change.
+    pub true_peak_ceiling_db: Option<f32>,
+    /// A gain for every channel of the output, e.g. to correct a speaker
+    /// imbalance. Applied after EQ, and must have one entry per output channel.
+    pub channel_gains: Option<Vec<f32>>,
 }
 
 impl AudioOutputConfig {
+    pub fn builder() -> AudioOutputConfigBuilder {
+        AudioOutputConfigBuilder::default()
+    }
+    /// Apply this config to `audio`, also reporting whether any stage changed it.
+    ///
+    /// A config whose stages are all unset (or set to their neutral value)
+    /// returns `audio` untouched, without a sonic round-trip, and reports `false`.
+    pub fn apply_reporting(&self, audio: Audio) -> PiperResult<(Audio, bool)> {
+        if self.modifies_audio() {
+            Ok((self.apply(audio)?, true))
+        } else {
+            Ok((audio, false))
+        }
+    }
+    fn modifies_audio(&self) -> bool {
+        let is_active = |value: Option<f32>| value.is_some_and(|v| v != 1.0);
+        self.sanitize_output
+            || is_active(self.rate)
+            || is_active(self.volume)
+            || is_active(self.pitch)
+            || is_active(self.playback_rate)
+            || self.appended_silence_ms.is_some_and(|ms| ms > 0)
+            || self.gate_threshold_db.is_some()
+            || self.force_mono
+            || self.highpass_hz.is_some()
+            || self.eq.as_ref().is_some_and(|bands| !bands.is_empty())
+            || self.true_peak_ceiling_db.is_some()
+            || self.channel_gains.is_some()
+    }
+    /// The format of the output for model output in `info`. Only the channel count
+    /// can change: rate changes keep the sample rate, and samples stay `f32` until
+    /// they're written.
+    pub fn output_info(&self, info: AudioInfo) -> AudioInfo {
+        AudioInfo {
+            num_channels: self.output_channels(info.num_channels),
+            ..info
+        }
+    }
+    /// The channel count of the output for `num_channels` channel model output
+    fn output_channels(&self, num_channels: usize) -> usize {
+        if self.force_mono {
+            1
+        } else {
+            num_channels
+        }
+    }
     fn apply(&self, mut audio: Audio) -> PiperAudioResult {
-        let mut samples = audio.samples.take();
-        if let Some(time_ms) = self.appended_silence_ms {
+        if self.force_mono && audio.info.num_channels > 1 {
+            audio = audio.to_mono();
+        }
+        let mut speech = AudioSamples::from(audio.samples.take());
+        self.sanitize(&mut speech);
+        if let Some(mut highpass) = self.highpass(audio.info.sample_rate, audio.info.num_channels) {
+            highpass.process(speech.as_mut_vec());
+        }
+        if let Some(mut gate) = self.noise_gate(audio.info.sample_rate, audio.info.num_channels) {
+            gate.process(&mut speech);
+        }
+        self.check_channel_gains(audio.info.num_channels)?;
+        let mut samples = speech.into_vec();
+        let raw_silence_ms = self
+            .appended_silence_ms
+            .filter(|_| self.raw_appended_silence);
+        if let Some(time_ms) = self
+            .appended_silence_ms
+            .filter(|_| !self.raw_appended_silence)
+        {
             let mut silence_samples = self.generate_silence(
                 time_ms as usize,
                 audio.info.sample_rate,
@@ -48,50 +359,124 @@ impl AudioOutputConfig {
             audio.info.sample_rate,
             audio.info.num_channels,
         )?;
+        if let Some(time_ms) = raw_silence_ms {
+            samples.merge(self.generate_silence(
+                time_ms as usize,
+                audio.info.sample_rate,
+                audio.info.num_channels,
+            )?);
+        }
+        if let Some(ref gains) = self.channel_gains {
+            samples.apply_channel_gains(gains);
+        }
+        if let Some(mut limiter) =
+            self.true_peak_limiter(audio.info.sample_rate, audio.info.num_channels)
+        {
+            samples = limiter.process(samples);
+            samples.merge(limiter.flush());
+        }
         audio.samples.as_mut_vec().append(samples.as_mut_vec());
         Ok(audio)
     }
+    /// Fail unless `channel_gains`, if set, has a gain for each of `num_channels`
+    fn check_channel_gains(&self, num_channels: usize) -> PiperResult<()> {
+        match self.channel_gains {
+            Some(ref gains) if gains.len() != num_channels => {
+                Err(PiperError::OperationError(format!(
+                    "{} channel gains were given for {} channel output",
+                    gains.len(),
+                    num_channels
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+    fn true_peak_limiter(
+        &self,
+        sample_rate: usize,
+        num_channels: usize,
+    ) -> Option<TruePeakLimiter> {
+        let ceiling_db = self.true_peak_ceiling_db?;
+        Some(TruePeakLimiter::new(
+            10f32.powf(ceiling_db / 20.0),
+            sample_rate,
+            num_channels,
+        ))
+    }
+    fn sanitize(&self, samples: &mut AudioSamples) {
+        if !self.sanitize_output {
+            return;
+        }
+        let num_non_finite = samples.sanitize();
+        if num_non_finite > 0 {
+            tracing::warn!(
+                "Replaced {} non-finite samples in model output",
+                num_non_finite
+            );
+        }
+    }
+    fn noise_gate(&self, sample_rate: usize, num_channels: usize) -> Option<NoiseGate> {
+        let threshold_db = self.gate_threshold_db?;
+        Some(NoiseGate {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            hold_frames: self.gate_hold_ms as usize * sample_rate / 1000,
+            num_channels: num_channels.max(1),
+            quiet_frames: 0,
+        })
+    }
+    fn highpass(&self, sample_rate: usize, num_channels: usize) -> Option<Biquad> {
+        let band = EqBand {
+            kind: BandKind::HighPass,
+            freq_hz: self.highpass_hz?,
+            gain_db: 0.0,
+            q: std::f32::consts::FRAC_1_SQRT_2,
+        };
+        Some(Biquad::new(&band, sample_rate, num_channels))
+    }
+    fn filter_chain(&self, sample_rate: usize, num_channels: usize) -> Option<FilterChain> {
+        let bands = self.eq.as_ref().filter(|bands| !bands.is_empty())?;
+        Some(FilterChain::new(bands, sample_rate, num_channels))
+    }
+    fn sonic_stream(&self, sample_rate: usize, num_channels: usize) -> SonicStream {
+        let mut stream = SonicStream::new(sample_rate, num_channels);
+        if let Some(rate) = self.rate {
+            stream.set_speed(rate);
+        }
+        if let Some(volume) = self.volume {
+            stream.set_volume(volume);
+        }
+        if let Some(pitch) = self.pitch {
+            stream.set_pitch(pitch);
+        }
+        if let Some(playback_rate) = self.playback_rate {
+            stream.set_rate(playback_rate);
+        }
+        if self.high_quality {
+            stream.set_quality(1);
+        }
+        stream
+    }
     fn apply_to_raw_samples(
         &self,
-        samples: AudioSamples,
+        mut samples: AudioSamples,
         sample_rate: usize,
         num_channels: usize,
     ) -> PiperResult<AudioSamples> {
-        let samples = samples.into_vec();
-        let input_len = samples.len();
-        if input_len == 0 {
-            return Ok(samples.into());
-        }
-        let mut out_buf: Vec<f32> = Vec::new();
-        unsafe {
-            let stream = sonic_rs_sys::sonicCreateStream(sample_rate as i32, num_channels as i32);
-            if let Some(rate) = self.rate {
-                sonic_rs_sys::sonicSetSpeed(stream, rate);
-            }
-            if let Some(volume) = self.volume {
-                sonic_rs_sys::sonicSetVolume(stream, volume);
-            }
-            if let Some(pitch) = self.pitch {
-                sonic_rs_sys::sonicSetPitch(stream, pitch);
-            }
-            sonic_rs_sys::sonicWriteFloatToStream(stream, samples.as_ptr(), input_len as i32);
-            sonic_rs_sys::sonicFlushStream(stream);
-            let num_samples = sonic_rs_sys::sonicSamplesAvailable(stream);
-            if num_samples <= 0 {
-                return Err(
-                    PiperError::OperationError("Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string())
-                );
-            }
-            out_buf.reserve_exact(num_samples as usize);
-            sonic_rs_sys::sonicReadFloatFromStream(
-                stream,
-                out_buf.spare_capacity_mut().as_mut_ptr().cast(),
-                num_samples,
-            );
-            sonic_rs_sys::sonicDestroyStream(stream);
-            out_buf.set_len(num_samples as usize);
+        self.sanitize(&mut samples);
+        if samples.is_empty() {
+            return Ok(samples);
         }
-        Ok(out_buf.into())
+        let mut stream = self.sonic_stream(sample_rate, num_channels);
+        stream.write(samples.as_slice());
+        stream.flush();
+        let mut output = stream.read_available();
+        if output.is_empty() {
+            return Err(invalid_sonic_parameter_error());
+        }
+        if let Some(mut eq) = self.filter_chain(sample_rate, num_channels) {
+            eq.process(&mut output);
+        }
+        Ok(output)
     }
     #[inline(always)]
     fn generate_silence(
@@ -101,323 +486,5249 @@ impl AudioOutputConfig {
         num_channels: usize,
     ) -> PiperResult<AudioSamples> {
         let num_samples = (time_ms * sample_rate) / 1000;
-        let silence_samples = vec![0f32; num_samples];
+        let silence_samples = self.silence.generate(num_samples * num_channels);
+        if self.raw_appended_silence {
+            return Ok(silence_samples.into());
+        }
         self.apply_to_raw_samples(silence_samples.into(), sample_rate, num_channels)
     }
 }
 
-pub struct PiperSpeechSynthesizer(Arc<dyn PiperModel + Sync + Send>);
-
-impl PiperSpeechSynthesizer {
-    pub fn new(model: Arc<dyn PiperModel + Sync + Send>) -> PiperResult<Self> {
-        Ok(Self(model))
-    }
-
-    fn create_synthesis_task_provider(
-        &self,
-        text: String,
-        output_config: Option<AudioOutputConfig>,
-    ) -> SpeechSynthesisTaskProvider {
-        SpeechSynthesisTaskProvider {
-            model: self.clone_model(),
-            text,
-            output_config,
-        }
-    }
+/// Chainable constructor for `AudioOutputConfig`, e.g.
+/// `AudioOutputConfig::builder().rate(1.2).volume(0.9).build()`
+#[derive(Clone, Default)]
+#[must_use]
+pub struct AudioOutputConfigBuilder(AudioOutputConfig);
 
-    pub fn synthesize_lazy(
-        &self,
-        text: String,
-        output_config: Option<AudioOutputConfig>,
-    ) -> PiperResult<PiperSpeechStreamLazy> {
-        PiperSpeechStreamLazy::new(self.create_synthesis_task_provider(text, output_config))
+impl AudioOutputConfigBuilder {
+    pub fn rate(mut self, rate: f32) -> Self {
+        self.0.rate = Some(rate);
+        self
     }
-    pub fn synthesize_parallel(
-        &self,
-        text: String,
-        output_config: Option<AudioOutputConfig>,
-    ) -> PiperResult<PiperSpeechStreamParallel> {
-        PiperSpeechStreamParallel::new(self.create_synthesis_task_provider(text, output_config))
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.0.volume = Some(volume);
+        self
     }
-    pub fn synthesize_streamed(
-        &self,
-        text: String,
-        output_config: Option<AudioOutputConfig>,
-        chunk_size: usize,
-        chunk_padding: usize,
-    ) -> PiperResult<RealtimeSpeechStream> {
-        let provider = self.create_synthesis_task_provider(text, output_config);
-        let wavinfo = self.0.audio_output_info();
-        RealtimeSpeechStream::new(
-            provider,
-            chunk_size,
-            chunk_padding,
-            wavinfo.sample_rate,
-            wavinfo.num_channels,
-        )
+    pub fn pitch(mut self, pitch: f32) -> Self {
+        self.0.pitch = Some(pitch);
+        self
     }
-
-    pub fn synthesize_to_file(
-        &self,
-        filename: &Path,
-        text: String,
-        output_config: Option<AudioOutputConfig>,
-    ) -> PiperResult<()> {
-        let mut samples: Vec<f32> = Vec::new();
-        for result in self.synthesize_parallel(text, output_config)? {
-            match result {
-                Ok(ws) => {
-                    samples.append(&mut ws.into_vec());
-                }
-                Err(e) => return Err(e),
-            };
-        }
-        if samples.is_empty() {
-            return Err(PiperError::OperationError(
-                "No speech data to write".to_string(),
-            ));
-        }
-        let audio = AudioSamples::from(samples);
-        Ok(audio::write_wave_samples_to_file(
-            filename,
-            audio.to_i16_vec().iter(),
-            self.0.audio_output_info().sample_rate as u32,
-            self.0.audio_output_info().num_channels.try_into().unwrap(),
-            self.0.audio_output_info().sample_width.try_into().unwrap(),
-        )?)
+    pub fn appended_silence_ms(mut self, appended_silence_ms: u32) -> Self {
+        self.0.appended_silence_ms = Some(appended_silence_ms);
+        self
     }
-    #[inline(always)]
-    pub fn clone_model(&self) -> Arc<dyn PiperModel + Send + Sync> {
-        Arc::clone(&self.0)
+    pub fn raw_appended_silence(mut self, raw_appended_silence: bool) -> Self {
+        self.0.raw_appended_silence = raw_appended_silence;
+        self
     }
-}
-
-impl PiperModel for PiperSpeechSynthesizer {
-    fn audio_output_info(&self) -> AudioInfo {
-        self.0.audio_output_info()
+    pub fn silence(mut self, silence: SilenceKind) -> Self {
+        self.0.silence = silence;
+        self
     }
-    fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
-        self.0.phonemize_text(text)
+    pub fn sanitize_output(mut self, sanitize_output: bool) -> Self {
+        self.0.sanitize_output = sanitize_output;
+        self
     }
-    fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
-        self.0.speak_batch(phoneme_batches)
+    pub fn playback_rate(mut self, playback_rate: f32) -> Self {
+        self.0.playback_rate = Some(playback_rate);
+        self
     }
-    fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        self.0.speak_one_sentence(phonemes)
+    pub fn high_quality(mut self, high_quality: bool) -> Self {
+        self.0.high_quality = high_quality;
+        self
     }
-    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        self.0.get_default_synthesis_config()
+    pub fn crossfade_ms(mut self, crossfade_ms: u32) -> Self {
+        self.0.crossfade_ms = crossfade_ms;
+        self
     }
-    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
-        self.0.get_fallback_synthesis_config()
+    pub fn force_mono(mut self, force_mono: bool) -> Self {
+        self.0.force_mono = force_mono;
+        self
     }
-    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
-        self.0.set_fallback_synthesis_config(synthesis_config)
+    pub fn true_peak_ceiling_db(mut self, ceiling_db: f32) -> Self {
+        self.0.true_peak_ceiling_db = Some(ceiling_db);
+        self
     }
-    fn get_language(&self) -> PiperResult<Option<String>> {
-        self.0.get_language()
+    pub fn channel_gains(mut self, gains: Vec<f32>) -> Self {
+        self.0.channel_gains = Some(gains);
+        self
     }
-    fn get_speakers(&self) -> PiperResult<Option<&HashMap<i64, String>>> {
-        self.0.get_speakers()
+    pub fn highpass_hz(mut self, highpass_hz: f32) -> Self {
+        self.0.highpass_hz = Some(highpass_hz);
+        self
     }
-    fn set_speaker(&self, sid: i64) -> Option<PiperError> {
-        self.0.set_speaker(sid)
+    pub fn eq(mut self, bands: Vec<EqBand>) -> Self {
+        self.0.eq = Some(bands);
+        self
     }
-    fn properties(&self) -> PiperResult<HashMap<String, String>> {
-        self.0.properties()
+    pub fn noise_gate(mut self, threshold_db: f32, hold_ms: u32) -> Self {
+        self.0.gate_threshold_db = Some(threshold_db);
+        self.0.gate_hold_ms = hold_ms;
+        self
     }
-    fn supports_streaming_output(&self) -> bool {
-        self.0.supports_streaming_output()
+    pub fn build(self) -> AudioOutputConfig {
+        self.0
     }
-    fn stream_synthesis<'a>(
-        &'a self,
-        #[allow(unused_variables)] phonemes: String,
-        #[allow(unused_variables)] chunk_size: usize,
-        #[allow(unused_variables)] chunk_padding: usize,
-    ) -> PiperResult<Box<dyn Iterator<Item = PiperResult<AudioSamples>> + Send + Sync + 'a>> {
-        self.0.stream_synthesis(phonemes, chunk_size, chunk_padding)
+}
+
+/// A noise gate, whose hold state carries over from one chunk to the next
+struct NoiseGate {
+    threshold: f32,
+    hold_frames: usize,
+    num_channels: usize,
+    quiet_frames: usize,
+}
+
+impl NoiseGate {
+    fn process(&mut self, samples: &mut AudioSamples) {
+        for frame in samples.as_mut_vec().chunks_mut(self.num_channels) {
+            if frame.iter().any(|s| s.abs() >= self.threshold) {
+                self.quiet_frames = 0;
+            } else if self.quiet_frames >= self.hold_frames {
+                frame.fill(0.0);
+            } else {
+                self.quiet_frames += 1;
+            }
+        }
     }
 }
 
-struct SpeechSynthesisTaskProvider {
-    model: Arc<dyn PiperModel + Sync + Send>,
-    text: String,
-    output_config: Option<AudioOutputConfig>,
+/// Frames the limiter looks ahead, for interpolating between samples
+const LIMITER_LOOKAHEAD_FRAMES: usize = 2;
+const LIMITER_RELEASE_MS: f32 = 50.0;
+/// The points checked between every two samples, i.e. 4x oversampling
+const LIMITER_OVERSAMPLED_POINTS: [f32; 3] = [0.25, 0.5, 0.75];
+
+/// A brickwall limiter on the true peak, estimated by oversampling with cubic
+/// interpolation. Gain drops at once to keep a peak below the ceiling, then
+/// recovers over `LIMITER_RELEASE_MS`, and is shared by all channels of a
+/// frame. Delays the output by `LIMITER_LOOKAHEAD_FRAMES`, so `flush` must be
+/// called at the end.
+struct TruePeakLimiter {
+    ceiling: f32,
+    num_channels: usize,
+    release: f32,
+    gain: f32,
+    /// The last `LIMITER_LOOKAHEAD_FRAMES` frames emitted, then the frames held back
+    frames: Vec<f32>,
 }
 
-impl SpeechSynthesisTaskProvider {
-    fn get_phonemes(&self) -> PiperResult<Vec<String>> {
-        Ok(self.model.phonemize_text(&self.text)?.to_vec())
+impl TruePeakLimiter {
+    fn new(ceiling: f32, sample_rate: usize, num_channels: usize) -> Self {
+        let num_channels = num_channels.max(1);
+        let release_frames = (LIMITER_RELEASE_MS * sample_rate as f32 / 1000.0).max(1.0);
+        Self {
+            ceiling,
+            num_channels,
+            release: 1.0 - (-1.0 / release_frames).exp(),
+            gain: 1.0,
+            frames: vec![0.0; LIMITER_LOOKAHEAD_FRAMES * num_channels],
+        }
     }
-    fn process_one_sentence(&self, phonemes: String) -> PiperAudioResult {
-        let wave_samples = self.model.speak_one_sentence(phonemes)?;
-        match self.output_config {
-            Some(ref config) => config.apply(wave_samples),
-            None => Ok(wave_samples),
+    /// Limit a chunk, returning the frames that are ready so far
+    fn process(&mut self, samples: AudioSamples) -> AudioSamples {
+        self.frames.extend_from_slice(samples.as_slice());
+        let num_frames = self.frames.len() / self.num_channels;
+        let ready = LIMITER_LOOKAHEAD_FRAMES..num_frames.saturating_sub(LIMITER_LOOKAHEAD_FRAMES);
+        let mut out = Vec::with_capacity(ready.len() * self.num_channels);
+        for frame in ready.clone() {
+            let peak = (0..self.num_channels)
+                .map(|channel| self.true_peak(frame, channel))
+                .fold(0f32, f32::max);
+            let target = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+            self.gain = if target < self.gain {
+                target
+            } else {
+                (self.gain + (1.0 - self.gain) * self.release).min(target)
+            };
+            let frame = &self.frames[frame * self.num_channels..(frame + 1) * self.num_channels];
+            out.extend(
+                frame
+                    .iter()
+                    .map(|sample| (sample * self.gain).clamp(-self.ceiling, self.ceiling)),
+            );
         }
+        if !ready.is_empty() {
+            let keep_from = ready.end - LIMITER_LOOKAHEAD_FRAMES;
+            self.frames.drain(..keep_from * self.num_channels);
+        }
+        out.into()
     }
-    #[allow(dead_code)]
-    fn process_batches(&self, phonemes: Vec<String>) -> PiperResult<Vec<Audio>> {
-        let wave_samples = self.model.speak_batch(phonemes)?;
-        match self.output_config {
-            Some(ref config) => {
-                let mut processed: Vec<Audio> = Vec::with_capacity(wave_samples.len());
-                for samples in wave_samples.into_iter() {
-                    processed.push(config.apply(samples)?);
-                }
-                Ok(processed)
+    /// Emit the frames held back for the lookahead
+    fn flush(&mut self) -> AudioSamples {
+        let padding = vec![0.0; LIMITER_LOOKAHEAD_FRAMES * self.num_channels];
+        self.process(padding.into())
+    }
+    /// The highest magnitude of `channel` around `frame`, including the points
+    /// between it and its neighbours
+    fn true_peak(&self, frame: usize, channel: usize) -> f32 {
+        let sample = |frame: usize| self.frames[frame * self.num_channels + channel];
+        let mut peak = sample(frame).abs();
+        for start in [frame - 1, frame] {
+            let points = [
+                sample(start - 1),
+                sample(start),
+                sample(start + 1),
+                sample(start + 2),
+            ];
+            for t in LIMITER_OVERSAMPLED_POINTS {
+                peak = peak.max(catmull_rom(points, t).abs());
             }
-            None => Ok(wave_samples),
         }
+        peak
     }
 }
 
-pub struct PiperSpeechStreamLazy {
-    provider: SpeechSynthesisTaskProvider,
-    sentence_phonemes: std::vec::IntoIter<String>,
+/// Cubic interpolation between `points[1]` and `points[2]`
+#[inline(always)]
+fn catmull_rom([p0, p1, p2, p3]: [f32; 4], t: f32) -> f32 {
+    p1 + 0.5
+        * t
+        * (p2 - p0 + t * (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3 + t * (3.0 * (p1 - p2) + p3 - p0)))
 }
 
-impl PiperSpeechStreamLazy {
-    fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
-        let sentence_phonemes = provider.get_phonemes()?.into_iter();
-        Ok(Self {
-            provider,
-            sentence_phonemes,
-        })
-    }
+/// The output processing of one sentence's chunks in the realtime path.
+///
+/// The stages keep their state from one chunk to the next, and may hold samples
+/// back, so `flush` must be called once the sentence's last chunk is processed.
+struct ChunkPipeline<'a> {
+    config: Cow<'a, AudioOutputConfig>,
+    num_channels: usize,
+    highpass: Option<Biquad>,
+    gate: Option<NoiseGate>,
+    sonic: SonicStream,
+    eq: Option<FilterChain>,
+    has_input: bool,
+    has_output: bool,
 }
 
-impl Iterator for PiperSpeechStreamLazy {
-    type Item = PiperAudioResult;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let phonemes = self.sentence_phonemes.next()?;
-        match self.provider.process_one_sentence(phonemes) {
-            Ok(ws) => Some(Ok(ws)),
-            Err(e) => Some(Err(e)),
+impl<'a> ChunkPipeline<'a> {
+    /// A pipeline for `num_channels` channel model output
+    fn new(config: &'a AudioOutputConfig, sample_rate: usize, num_channels: usize) -> Self {
+        Self::with_config(Cow::Borrowed(config), sample_rate, num_channels)
+    }
+    fn with_config(
+        config: Cow<'a, AudioOutputConfig>,
+        sample_rate: usize,
+        num_channels: usize,
+    ) -> Self {
+        let output_channels = config.output_channels(num_channels);
+        Self {
+            highpass: config.highpass(sample_rate, output_channels),
+            gate: config.noise_gate(sample_rate, output_channels),
+            sonic: config.sonic_stream(sample_rate, output_channels),
+            eq: config.filter_chain(sample_rate, output_channels),
+            config,
+            num_channels,
+            has_input: false,
+            has_output: false,
+        }
+    }
+    /// Process a chunk, returning the samples that are ready so far
+    fn process(&mut self, mut samples: AudioSamples) -> AudioSamples {
+        if self.config.force_mono && self.num_channels > 1 {
+            samples = samples.to_mono(self.num_channels);
+        }
+        self.config.sanitize(&mut samples);
+        if let Some(ref mut highpass) = self.highpass {
+            highpass.process(samples.as_mut_vec());
+        }
+        if let Some(ref mut gate) = self.gate {
+            gate.process(&mut samples);
+        }
+        self.has_input |= !samples.is_empty();
+        self.sonic.write(samples.as_slice());
+        self.read_sonic()
+    }
+    /// Drain the samples the stages still hold at the end of the sentence
+    fn flush(&mut self) -> PiperResult<AudioSamples> {
+        self.sonic.flush();
+        let processed = self.read_sonic();
+        if self.has_input && !self.has_output {
+            return Err(invalid_sonic_parameter_error());
+        }
+        Ok(processed)
+    }
+    /// Take sonic's output, and run it through the stages after sonic
+    fn read_sonic(&mut self) -> AudioSamples {
+        let mut processed = self.sonic.read_available();
+        self.has_output |= !processed.is_empty();
+        if let Some(ref mut eq) = self.eq {
+            eq.process(&mut processed);
+        }
+        if let Some(ref gains) = self.config.channel_gains {
+            processed.apply_channel_gains(gains);
         }
+        processed
     }
 }
 
-#[must_use]
-pub struct PiperSpeechStreamParallel {
-    precalculated_results: std::vec::IntoIter<PiperAudioResult>,
+fn invalid_sonic_parameter_error() -> PiperError {
+    PiperError::OperationError(
+        "Sonic Error: failed to apply audio config. Invalid parameter value for rate, volume, or pitch".to_string(),
+    )
 }
 
-impl PiperSpeechStreamParallel {
-    fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
-        let calculated_result: Vec<PiperAudioResult> = provider
-            .get_phonemes()?
-            .par_iter()
-            .map(|ph| provider.process_one_sentence(ph.to_string()))
-            .collect();
-        Ok(Self {
-            precalculated_results: calculated_result.into_iter(),
-        })
-    }
+/// A phoneme's predicted duration, see `PiperSpeechSynthesizer::phoneme_duration_report`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhonemeDuration {
+    /// The index of the (phonemized) sentence the phoneme is in
+    pub sentence_index: usize,
+    pub phoneme: char,
+    pub duration_ms: f32,
 }
 
-impl Iterator for PiperSpeechStreamParallel {
-    type Item = PiperAudioResult;
+/// A non-fatal issue found while synthesizing, see `PiperSpeechSynthesizer::synthesize_verbose`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynthesisWarning {
+    /// The index of the (phonemized) sentence the issue was found in
+    pub sentence_index: usize,
+    pub kind: SynthesisWarningKind,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.precalculated_results.next()
+#[derive(Clone, Debug, PartialEq)]
+pub enum SynthesisWarningKind {
+    /// The model has no ids for these phonemes, so they were skipped
+    UnknownPhonemes(Vec<char>),
+    /// The sentence is longer than the model's `max_phoneme_length`, but was
+    /// synthesized as is (`LongSentencePolicy::Warn`)
+    SentenceTooLong {
+        num_phonemes: usize,
+        max_length: usize,
+    },
+    /// The sentence was longer than the model's `max_phoneme_length`, and was
+    /// split into `num_parts` sentences (`LongSentencePolicy::Split`)
+    SentenceSplit {
+        num_phonemes: usize,
+        num_parts: usize,
+    },
+    /// The output was cut short here to honor `max_output_duration`
+    Truncated,
+    /// Text in a script the phonemizer can't read was replaced, or dropped if
+    /// there's no `replacement` (see `UnsupportedScriptPolicy`). The sentence index
+    /// is that of the first sentence of the text it was found in.
+    UnsupportedScript {
+        text: String,
+        replacement: Option<String>,
+    },
+    /// The phonemizer failed on `word`, so it was spelled out (`OovPolicy::SpellOut`)
+    SpelledOut { word: String },
+}
+
+impl std::fmt::Display for SynthesisWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Sentence {}: ", self.sentence_index)?;
+        match self.kind {
+            SynthesisWarningKind::UnknownPhonemes(ref phonemes) => write!(
+                f,
+                "skipped unknown phonemes `{}`",
+                String::from_iter(phonemes)
+            ),
+            SynthesisWarningKind::SentenceTooLong {
+                num_phonemes,
+                max_length,
+            } => write!(
+                f,
+                "{} phonemes, more than the model's maximum of {}",
+                num_phonemes, max_length
+            ),
+            SynthesisWarningKind::SentenceSplit {
+                num_phonemes,
+                num_parts,
+            } => write!(
+                f,
+                "split {} phonemes into {} sentences",
+                num_phonemes, num_parts
+            ),
+            SynthesisWarningKind::Truncated => write!(f, "output truncated"),
+            SynthesisWarningKind::UnsupportedScript {
+                ref text,
+                replacement: Some(ref replacement),
+            } => write!(f, "transliterated `{}` as `{}`", text, replacement),
+            SynthesisWarningKind::UnsupportedScript {
+                ref text,
+                replacement: None,
+            } => write!(f, "dropped unsupported text `{}`", text),
+            SynthesisWarningKind::SpelledOut { ref word } => {
+                write!(f, "spelled out `{}`, which couldn't be phonemized", word)
+            }
+        }
     }
 }
 
-pub struct RealtimeSpeechStream(Receiver<PiperResult<AudioSamples>>);
+/// What to do with a sentence whose phonemes exceed the model's `max_phoneme_length`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LongSentencePolicy {
+    /// Split the sentence, preferably at punctuation, then at word boundaries
+    Split,
+    /// Log a warning and synthesize the sentence as is
+    #[default]
+    Warn,
+    /// Fail the synthesis
+    Error,
+}
+
+/// Romanizes a run of text in a script the phonemizer can't read, or returns
+/// `None` to drop it
+pub type Transliterator = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// How numbers in text are read
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DigitMode {
+    /// As numbers, e.g. "eight million six hundred..." for 8675309
+    #[default]
+    Cardinal,
+    /// Digit by digit, e.g. "eight six seven..." for 8675309
+    Digits,
+}
+
+/// The level of an inline `<emphasis level="...">` span
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EmphasisLevel {
+    Strong,
+    Moderate,
+    Reduced,
+}
+
+impl EmphasisLevel {
+    /// The level of a `level` attribute. Unknown (or missing) levels are moderate,
+    /// and `none` is no emphasis at all.
+    fn from_attribute(level: &str) -> Option<Self> {
+        match level {
+            "none" => None,
+            "strong" => Some(Self::Strong),
+            "reduced" => Some(Self::Reduced),
+            _ => Some(Self::Moderate),
+        }
+    }
+    fn default_prosody(self) -> EmphasisProsody {
+        let (volume, pitch, rate) = match self {
+            Self::Strong => (1.3, 1.1, 0.85),
+            Self::Moderate => (1.15, 1.05, 0.92),
+            Self::Reduced => (0.8, 0.95, 1.1),
+        };
+        EmphasisProsody {
+            volume,
+            pitch,
+            rate,
+        }
+    }
+}
+
+/// How an emphasis level changes the speech, as factors of the output config's
+/// `volume`, `pitch` and `rate`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EmphasisProsody {
+    pub volume: f32,
+    pub pitch: f32,
+    pub rate: f32,
+}
+
+/// What to do with `<...>` markers that aren't pauses, when pause markers are parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownMarkerPolicy {
+    /// Pass the marker on as text
+    #[default]
+    Keep,
+    /// Remove the marker
+    Strip,
+}
+
+/// What to do with text the phonemizer fails on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OovPolicy {
+    /// Fail the synthesis
+    #[default]
+    Error,
+    /// Phonemize the sentence word by word, and spell out the words that fail
+    SpellOut,
+}
+
+/// What to do with text that has no sentences to synthesize, e.g. only
+/// punctuation or whitespace
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyInputPolicy {
+    /// Fail the synthesis
+    Error,
+    /// Synthesize `ms` of silence instead
+    SilentClip { ms: u32 },
+    /// Synthesize nothing: streams yield no audio, and writing a file fails as
+    /// there's nothing to write
+    #[default]
+    Empty,
+}
+
+/// What to do with runs of text in a script the phonemizer can't read for the
+/// model's language, like emoji or Chinese characters in English text
+#[derive(Clone)]
+pub enum UnsupportedScriptPolicy {
+    /// Skip the run
+    Drop,
+    /// Replace the run with what the hook returns
+    Transliterate(Transliterator),
+    /// Fail the synthesis
+    Error,
+}
+
+#[derive(Clone, Default)]
+struct SynthesisOptions {
+    long_sentence_policy: LongSentencePolicy,
+    parse_speaker_tags: bool,
+    /// Replaces `SYNTHESIS_THREAD_POOL` and rayon's global pool when set
+    thread_pool: Option<Arc<ThreadPool>>,
+    max_output_duration: Option<Duration>,
+    phrase_split_threshold: Option<usize>,
+    unsupported_script_policy: Option<UnsupportedScriptPolicy>,
+    pause_markers: Option<UnknownMarkerPolicy>,
+    digit_mode: DigitMode,
+    oov_policy: OovPolicy,
+    empty_input_policy: EmptyInputPolicy,
+    reverse_sentences: bool,
+    /// Overrides the default prosody of each emphasis level
+    emphasis_prosody: HashMap<EmphasisLevel, EmphasisProsody>,
+}
+
+/// Enforces `max_output_duration` on a sequence of sample blocks
+struct OutputLimit {
+    remaining_samples: Option<usize>,
+    truncated: bool,
+}
+
+impl OutputLimit {
+    fn new(max_duration: Option<Duration>, info: &AudioInfo) -> Self {
+        let remaining_samples = max_duration.map(|duration| {
+            (duration.as_secs_f64() * info.sample_rate as f64) as usize * info.num_channels
+        });
+        Self {
+            remaining_samples,
+            truncated: false,
+        }
+    }
+    fn is_exhausted(&self) -> bool {
+        self.remaining_samples == Some(0)
+    }
+    /// Cut `samples` down to what's left of the limit
+    fn apply(&mut self, samples: &mut AudioSamples) {
+        let Some(remaining) = self.remaining_samples.as_mut() else {
+            return;
+        };
+        if samples.len() > *remaining {
+            samples.as_mut_vec().truncate(*remaining);
+            self.truncated = true;
+        }
+        *remaining -= samples.len();
+    }
+}
+
+pub struct PiperSpeechSynthesizer {
+    model: Arc<dyn PiperModel + Sync + Send>,
+    options: SynthesisOptions,
+    warmed_up: AtomicBool,
+    speaker_configs: RwLock<HashMap<i64, AudioOutputConfig>>,
+    /// The threads of `options.thread_pool`, if this synthesizer built it
+    pool_threads: Option<Arc<LiveThreads>>,
+    /// Used by calls without an output config
+    default_output_config: Option<AudioOutputConfig>,
+}
+
+impl PiperSpeechSynthesizer {
+    pub fn new(model: Arc<dyn PiperModel + Sync + Send>) -> PiperResult<Self> {
+        Ok(Self {
+            model,
+            options: SynthesisOptions::default(),
+            warmed_up: AtomicBool::new(false),
+            speaker_configs: RwLock::default(),
+            pool_threads: None,
+            default_output_config: None,
+        })
+    }
+
+    /// A synthesizer sharing this one's model, settings and thread pool, which
+    /// applies `config` to calls without an output config of their own, e.g. for
+    /// narrator and dialogue profiles over a single loaded model.
+    ///
+    /// The thread pool can only be shut down through the synthesizer that set it.
+    pub fn with_default_config(&self, config: AudioOutputConfig) -> Self {
+        Self {
+            model: self.clone_model(),
+            options: self.options.clone(),
+            warmed_up: AtomicBool::new(self.warmed_up.load(Ordering::Acquire)),
+            speaker_configs: RwLock::new(self.speaker_configs.read().unwrap().clone()),
+            pool_threads: None,
+            default_output_config: Some(config),
+        }
+    }
+
+    /// Run a tiny throwaway synthesis so the one-time phonemizer and ONNX
+    /// initialization doesn't land on the first real request.
+    ///
+    /// Only the first successful call does any work.
+    pub fn warm_up(&self) -> PiperResult<()> {
+        if self.warmed_up.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        let phonemes = self.model.phonemize_text("a")?;
+        if let Some(sentence) = phonemes.sentences().first() {
+            let _ = self.model.speak_one_sentence(sentence.clone())?;
+        }
+        self.warmed_up.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn set_long_sentence_policy(&mut self, policy: LongSentencePolicy) {
+        self.options.long_sentence_policy = policy;
+    }
+
+    /// Split sentences of more than `threshold` phonemes into phrases, preferably
+    /// at clause punctuation, and synthesize the phrases in parallel. The phrases
+    /// are crossfaded back into one sentence over a few milliseconds.
+    ///
+    /// This recovers parallelism for run-on text the sentence splitter leaves as
+    /// one huge sentence, at the cost of the prosody across the phrase boundaries.
+    pub fn set_phrase_split_threshold(&mut self, threshold: Option<usize>) {
+        self.options.phrase_split_threshold = threshold;
+    }
+
+    /// Drop or transliterate text in scripts the phonemizer can't read, instead of
+    /// passing it on as is. Replaced runs are reported by `synthesize_verbose`.
+    /// Only applies to phoneme-input models.
+    pub fn set_unsupported_script_policy(&mut self, policy: Option<UnsupportedScriptPolicy>) {
+        self.options.unsupported_script_policy = policy;
+    }
+
+    /// Turn inline pause markers into silence: `...` for half a second, and `<pN>`
+    /// for N milliseconds, e.g. `Wait... <p300>what?`. Other `<...>` markers are
+    /// handled by `policy`; with `None`, the default, markers are left as text.
+    ///
+    /// Unlike `appended_silence_ms`, a pause can be anywhere in a sentence; the text
+    /// on either side of it is synthesized separately, streamed or not. Markers in
+    /// the text received by `synthesize_channel` aren't parsed.
+    pub fn set_pause_markers(&mut self, policy: Option<UnknownMarkerPolicy>) {
+        self.options.pause_markers = policy;
+    }
+
+    /// Read the numbers in text digit by digit with `DigitMode::Digits`, e.g. for
+    /// phone numbers and confirmation codes. Whatever the mode, numbers in inline
+    /// `<digits>...</digits>` spans are read digit by digit.
+    pub fn set_digit_mode(&mut self, mode: DigitMode) {
+        self.options.digit_mode = mode;
+    }
+
+    /// Change the prosody of inline `<emphasis level="...">` spans at `level`.
+    ///
+    /// Emphasized spans are synthesized apart from the text around them, with the
+    /// output config's volume, pitch and rate scaled by the level's prosody. By
+    /// default strong emphasis is louder, higher and slower, and reduced emphasis
+    /// quieter, lower and faster.
+    pub fn set_emphasis_prosody(&mut self, level: EmphasisLevel, prosody: EmphasisProsody) {
+        self.options.emphasis_prosody.insert(level, prosody);
+    }
+
+    /// Spell out the words the phonemizer fails on with `OovPolicy::SpellOut`,
+    /// instead of failing the synthesis. Spelled out words are reported by
+    /// `synthesize_verbose`. Only applies to phoneme-input models.
+    pub fn set_oov_policy(&mut self, policy: OovPolicy) {
+        self.options.oov_policy = policy;
+    }
+
+    /// Choose what text without any sentences synthesizes to, see `EmptyInputPolicy`.
+    /// Realtime streams yield nothing for `EmptyInputPolicy::SilentClip`.
+    pub fn set_empty_input_policy(&mut self, policy: EmptyInputPolicy) {
+        self.options.empty_input_policy = policy;
+    }
+
+    /// Synthesize the sentences of the text in reverse order, e.g. to read a list
+    /// from the bottom up. Only the order of the sentences is reversed; each one
+    /// is still read forward.
+    pub fn set_reverse_sentences(&mut self, reverse: bool) {
+        self.options.reverse_sentences = reverse;
+    }
+
+    /// The phonemes of `word` read out letter by letter in the model's language,
+    /// the fallback of `OovPolicy::SpellOut`
+    pub fn spell_out_phonemes(&self, word: &str) -> PiperResult<String> {
+        spell_out_phonemes(self.model.as_ref(), word)
+    }
+
+    /// Switch speakers at inline `[spk:N]` tags, e.g. `[spk:3] Hello. [spk:7] Goodbye.`
+    ///
+    /// Tags are honored by `synthesize_lazy`, `synthesize_parallel`, `synthesize` and
+    /// the file-writing methods; the other methods reject tagged text.
+    pub fn set_parse_speaker_tags(&mut self, parse_speaker_tags: bool) {
+        self.options.parse_speaker_tags = parse_speaker_tags;
+    }
+
+    /// Process the sentences of speaker `sid` with `config`, instead of the output
+    /// config passed to each call, so every voice of a tagged script keeps its own
+    /// volume, rate and so on. Speakers without a config of their own, and text
+    /// before the first tag, use the passed config.
+    ///
+    /// Like speaker tags, this is honored by the methods that synthesize whole
+    /// sentences; streamed methods use the passed config throughout.
+    pub fn set_speaker_config(&self, sid: i64, config: AudioOutputConfig) {
+        self.speaker_configs.write().unwrap().insert(sid, config);
+    }
+
+    pub fn speaker_config(&self, sid: i64) -> Option<AudioOutputConfig> {
+        self.speaker_configs.read().unwrap().get(&sid).cloned()
+    }
+
+    /// Remove the config of speaker `sid`, returning it
+    pub fn clear_speaker_config(&self, sid: i64) -> Option<AudioOutputConfig> {
+        self.speaker_configs.write().unwrap().remove(&sid)
+    }
+
+    pub fn clear_speaker_configs(&self) {
+        self.speaker_configs.write().unwrap().clear();
+    }
+
+    /// Stop producing audio once `max_duration` of it has been synthesized, cutting
+    /// the last sentence or chunk short if needed. Streams report the cut through
+    /// their `was_truncated` method.
+    pub fn set_max_output_duration(&mut self, max_duration: Option<Duration>) {
+        self.options.max_output_duration = max_duration;
+    }
+
+    /// Set how the model assembles phonemes into input ids. The model is shared, so
+    /// this affects every synthesizer using it.
+    pub fn set_phoneme_join(&self, policy: PhonemeJoinPolicy) -> PiperResult<()> {
+        self.model.set_phoneme_join(policy)
+    }
+
+    /// Run this synthesizer's work on a dedicated thread pool built from `config`,
+    /// instead of the shared pools. Dropping the synthesizer, once no stream is
+    /// running on the pool any more, lets the pool's threads exit.
+    pub fn set_thread_config(&mut self, config: &SynthesisThreadConfig) -> PiperResult<()> {
+        let (pool, live_threads) = config.build_thread_pool()?;
+        self.options.thread_pool = Some(Arc::new(pool));
+        self.pool_threads = Some(live_threads);
+        Ok(())
+    }
+
+    /// Shut down the dedicated thread pool of `set_thread_config`, if there is one,
+    /// and wait for its threads to exit; for clean teardown in tests and short-lived
+    /// tools. Later calls use the shared pools again.
+    ///
+    /// Streams still running on the pool keep it alive, so drop them first, or
+    /// this blocks until they're done. The shared `SYNTHESIS_THREAD_POOL` lives
+    /// as long as the process.
+    pub fn shutdown_thread_pool(&mut self) {
+        self.options.thread_pool = None;
+        if let Some(live_threads) = self.pool_threads.take() {
+            live_threads.wait();
+        }
+    }
+
+    fn create_synthesis_task_provider(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> SpeechSynthesisTaskProvider {
+        SpeechSynthesisTaskProvider {
+            model: self.clone_model(),
+            text,
+            output_config: self.output_config_or_default(output_config),
+            options: self.options.clone(),
+            speaker_configs: self.speaker_configs.read().unwrap().clone(),
+            embedding: None,
+        }
+    }
+
+    fn output_config_or_default(
+        &self,
+        output_config: Option<AudioOutputConfig>,
+    ) -> Option<AudioOutputConfig> {
+        output_config.or_else(|| self.default_output_config.clone())
+    }
+
+    /// The format of the audio synthesized with `config`, without synthesizing
+    /// anything, e.g. to write a WAV header before the audio exists
+    pub fn effective_output_info(&self, config: Option<&AudioOutputConfig>) -> AudioInfo {
+        let info = self.model.audio_output_info();
+        match config.or(self.default_output_config.as_ref()) {
+            Some(config) => config.output_info(info),
+            None => info,
+        }
+    }
+
+    /// The sample rate the model synthesizes at, before any resampling
+    pub fn native_sample_rate(&self) -> usize {
+        self.model.audio_output_info().native_sample_rate
+    }
+
+    /// Split `text` into the sentences it's synthesized as, as plain text, so they
+    /// can be synthesized independently (e.g. by different workers) and joined.
+    ///
+    /// The split only depends on `text` and on whether speaker tags are parsed,
+    /// and is kept stable between releases. When they are, every sentence spoken
+    /// by a tagged speaker starts with that speaker's tag.
+    pub fn split_sentences(&self, text: &str) -> PiperResult<Vec<String>> {
+        let provider = self.create_synthesis_task_provider(text.to_string(), None);
+        let mut sentences = Vec::new();
+        for (speaker, segment) in provider.speaker_segments()? {
+            sentences.extend(text::split_sentences(&segment).into_iter().map(|sentence| {
+                match speaker {
+                    Some(sid) => format!("{} {}", text::speaker_tag(sid), sentence),
+                    None => sentence,
+                }
+            }));
+        }
+        Ok(sentences)
+    }
+    pub fn synthesize_lazy(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamLazy> {
+        PiperSpeechStreamLazy::new(self.create_synthesis_task_provider(text, output_config))
+    }
+    /// Synthesize the sentences of `text` in parallel, and yield each as soon as it
+    /// and every sentence before it are done, so playback of the first sentence can
+    /// start while the rest are still being synthesized. Sentences that finish early
+    /// are held back until their turn.
+    pub fn synthesize_parallel(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        PiperSpeechStreamParallel::new(self.create_synthesis_task_provider(text, output_config))
+    }
+    /// Like `synthesize_parallel`, but synthesizes every sentence in order on the
+    /// calling thread before returning.
+    ///
+    /// Prefer this on one or two cores, where the thread pool's overhead
+    /// outweighs synthesizing sentences side by side. With more cores,
+    /// `synthesize_parallel` finishes sooner for text with several sentences.
+    pub fn synthesize_sequential(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let results = Vec::from_iter(
+            provider
+                .get_sentences()?
+                .into_iter()
+                .map(|sentence| timed(|| provider.process_sentence(sentence))),
+        );
+        Ok(PiperSpeechStreamParallel::from_results(&provider, results))
+    }
+    /// Like `synthesize_parallel`, but synthesizes the first sentence before
+    /// returning, so the first result is ready at once, while the rest synthesize
+    /// in the background.
+    pub fn synthesize_hybrid(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        PiperSpeechStreamParallel::new_hybrid(
+            self.create_synthesis_task_provider(text, output_config),
+        )
+    }
+    /// Like `synthesize_parallel`, but bounds the whole call by `deadline`: it
+    /// returns by then, without waiting for sentences still being synthesized.
+    ///
+    /// Sentences not started by the deadline are abandoned, and so is everything
+    /// after the first sentence that didn't finish in time; the stream yields only
+    /// the sentences completed before it, and reports the rest via `was_truncated`.
+    pub fn synthesize_parallel_deadline(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        deadline: Instant,
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let sentences = provider.get_sentences()?;
+        let stream = PiperSpeechStreamParallel::spawn(provider, HashMap::new(), sentences);
+        Ok(stream.completed_by(deadline))
+    }
+    /// Like `synthesize_parallel`, but synthesizes with `synth_config` (e.g. a
+    /// `PiperSynthesisConfig`) instead of the model's shared fallback config,
+    /// which is left untouched. Safe to use concurrently with different configs.
+    pub fn synthesize_with_config(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        synth_config: &(dyn Any + Sync),
+    ) -> PiperResult<PiperSpeechStreamParallel> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let sentences = provider.get_single_speaker_sentences()?;
+        PiperSpeechStreamParallel::with_processor(&provider, sentences, |sentence| {
+            provider.process_sentence_with(sentence, synth_config)
+        })
+    }
+    /// Synthesize all of `text` into a single `Audio`; call `stats()` on it to
+    /// check the output level.
+    pub fn synthesize(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        match self.join_stream(self.synthesize_parallel(text, output_config)?)? {
+            Some(audio) => Ok(audio),
+            None => self.join_parts(Vec::new()),
+        }
+    }
+    /// The speaker embedding that `sid` selects, e.g. to interpolate between speakers
+    /// with `synthesize_with_embedding`. Its length is the model's embedding
+    /// dimension, which for piper's streaming models is the `gin_channels` of the
+    /// training config. Models without embeddings fail.
+    pub fn get_speaker_embedding(&self, sid: i64) -> PiperResult<Vec<f32>> {
+        self.model.speaker_embedding(sid)
+    }
+    /// Like `synthesize`, but conditions the speech on `embedding` instead of the
+    /// embedding of a speaker id. Fails if `embedding` isn't as long as the model's
+    /// embeddings, see `get_speaker_embedding`. Sentences with a speaker tag are
+    /// still spoken by the speaker of the tag.
+    pub fn synthesize_with_embedding(
+        &self,
+        text: String,
+        embedding: &[f32],
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        let mut provider = self.create_synthesis_task_provider(text, output_config);
+        provider.embedding = Some(Arc::from(embedding));
+        match self.join_stream(PiperSpeechStreamParallel::new(provider)?)? {
+            Some(audio) => Ok(audio),
+            None => self.join_parts(Vec::new()),
+        }
+    }
+    /// Like `synthesize`, but returns the model's raw output along with the output of
+    /// `output_config`, from a single inference per sentence.
+    pub fn synthesize_raw_and_processed(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<(Audio, Audio)> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let sentences = provider.get_sentences()?;
+        let results: Vec<PiperResult<(Audio, Audio)>> = provider.run_in_pool(|| {
+            sentences
+                .into_par_iter()
+                .map(|sentence| {
+                    let pause_ms = sentence.pause_ms;
+                    let raw = provider.speak_one_sentence(sentence.phonemes, sentence.speaker)?;
+                    let processed = provider.apply_sentence_config(
+                        raw.clone(),
+                        sentence.speaker,
+                        sentence.emphasis,
+                    )?;
+                    Ok((
+                        provider.append_pause(raw, pause_ms),
+                        provider.append_pause(processed, pause_ms),
+                    ))
+                })
+                .collect()
+        });
+        let (mut raw_limit, mut processed_limit) =
+            (provider.output_limit(), provider.output_limit());
+        let (mut raw_parts, mut processed_parts) = (Vec::new(), Vec::new());
+        for result in results {
+            let (mut raw, mut processed) = result?;
+            raw_limit.apply(&mut raw.samples);
+            processed_limit.apply(&mut processed.samples);
+            raw_parts.push(raw);
+            processed_parts.push(processed);
+        }
+        Ok((
+            self.join_parts(raw_parts)?,
+            self.join_parts(processed_parts)?,
+        ))
+    }
+    /// Like `synthesize`, but also reports how long phonemization, inference and
+    /// post-processing took
+    pub fn synthesize_profiled(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<(Audio, SynthesisProfile)> {
+        let start = Instant::now();
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let (sentences, phonemize) = timed(|| provider.get_sentences());
+        let sentences = sentences?;
+        let results: Vec<PiperResult<(Audio, Duration, Duration)>> = provider.run_in_pool(|| {
+            sentences
+                .into_par_iter()
+                .map(|sentence| {
+                    let (pause_ms, speaker, emphasis) =
+                        (sentence.pause_ms, sentence.speaker, sentence.emphasis);
+                    let (raw, inference) =
+                        timed(|| provider.speak_one_sentence(sentence.phonemes, speaker));
+                    let (processed, postprocess) = timed(|| -> PiperAudioResult {
+                        let processed = provider.apply_sentence_config(raw?, speaker, emphasis)?;
+                        Ok(provider.append_pause(processed, pause_ms))
+                    });
+                    Ok((processed?, inference, postprocess))
+                })
+                .collect()
+        });
+        let mut profile = SynthesisProfile {
+            phonemize,
+            ..Default::default()
+        };
+        let mut limit = provider.output_limit();
+        let mut parts = Vec::with_capacity(results.len());
+        for result in results {
+            let (mut audio, inference, postprocess) = result?;
+            limit.apply(&mut audio.samples);
+            profile.inference += inference;
+            profile.postprocess += postprocess;
+            parts.push(audio);
+        }
+        let audio = self.join_parts(parts)?;
+        profile.total = start.elapsed();
+        Ok((audio, profile))
+    }
+    /// Like `synthesize`, but also returns a 64-bit XXH3 hash (seed 0) of the output,
+    /// computed sentence by sentence as they're produced. Samples are hashed as
+    /// little-endian `f32` bytes, so the hash is the same on every platform.
+    #[cfg(feature = "hash")]
+    pub fn synthesize_with_hash(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<(Audio, u64)> {
+        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+        let mut parts = Vec::new();
+        for result in self.synthesize_parallel(text, output_config)? {
+            let audio = result?;
+            for block in audio.samples.as_slice().chunks(1024) {
+                let bytes = Vec::from_iter(block.iter().flat_map(|f| f.to_le_bytes()));
+                hasher.update(&bytes);
+            }
+            parts.push(audio);
+        }
+        Ok((self.join_parts(parts)?, hasher.digest()))
+    }
+    /// Synthesize `text` and play it on the default output device. Playback has
+    /// started by the time this returns, and stops when the `Playback` is dropped.
+    #[cfg(feature = "rodio")]
+    pub fn synthesize_and_play(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<Playback> {
+        let audio = self.synthesize(text, output_config)?;
+        let (stream, handle) = rodio::OutputStream::try_default().map_err(|e| {
+            PiperError::OperationError(format!(
+                "Failed to open the default audio output device. Error: {}",
+                e
+            ))
+        })?;
+        let sink = rodio::Sink::try_new(&handle).map_err(|e| {
+            PiperError::OperationError(format!("Failed to start audio playback. Error: {}", e))
+        })?;
+        sink.append(rodio::buffer::SamplesBuffer::new(
+            audio.info.num_channels as u16,
+            audio.info.sample_rate as u32,
+            audio.into_vec(),
+        ));
+        Ok(Playback {
+            sink,
+            _stream: stream,
+        })
+    }
+    /// Synthesize pre-computed phoneme id sequences, one per sentence, into a single
+    /// `Audio`. Each sequence is passed to the model as is, so it must include the
+    /// model's bos, pad and eos ids; ids outside the model's vocabulary are an error.
+    pub fn synthesize_from_ids(
+        &self,
+        id_sequences: Vec<Vec<i64>>,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        let provider = self.create_synthesis_task_provider(String::new(), output_config);
+        let parts = PiperSpeechStreamParallel::with_processor(&provider, id_sequences, |ids| {
+            provider.apply_output_config(provider.model.speak_input_ids(ids)?)
+        })?
+        .collect::<PiperResult<Vec<Audio>>>()?;
+        self.join_parts(parts)
+    }
+    /// Synthesize the first `max_sentences` sentences of `text` in parallel, and join
+    /// them, e.g. to preview a voice on a long document. The rest of the text is
+    /// neither phonemized nor synthesized.
+    pub fn synthesize_preview(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        max_sentences: usize,
+    ) -> PiperAudioResult {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let sentences = provider.get_first_sentences(max_sentences)?;
+        let parts = PiperSpeechStreamParallel::with_processor(&provider, sentences, |s| {
+            provider.process_sentence(s)
+        })?
+        .collect::<PiperResult<Vec<Audio>>>()?;
+        self.join_parts(parts)
+    }
+    /// Synthesize `text`, scaling the duration of its words by `word_scales` for
+    /// emphasis: a scale of 2.0 speaks a word twice as long. The scales apply to the
+    /// words of `text` in order, ignoring punctuation. Words without a scale, and
+    /// sentences whose words can't be matched with their phonemes, keep their
+    /// predicted duration.
+    ///
+    /// Sentences whose words are all left at 1.0 are synthesized like `synthesize`
+    /// does. Scaling the others needs a model that implements
+    /// `PiperModel::speak_with_durations`, i.e. a streaming (encoder/decoder)
+    /// model; other models fail on them. Scaled sentences aren't split into phrases.
+    pub fn synthesize_with_word_durations(
+        &self,
+        text: String,
+        word_scales: Vec<f32>,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let mut word_scales = word_scales.into_iter();
+        let mut items = Vec::new();
+        for (sentence_text, sentences) in provider.get_text_sentences()? {
+            if sentences.iter().any(|sentence| sentence.speaker.is_some()) {
+                return Err(PiperError::OperationError(
+                    "Speaker tags are not supported by this synthesis method".to_string(),
+                ));
+            }
+            let num_words = text_words(&sentence_text).len();
+            let mut scales = Vec::from_iter(word_scales.by_ref().take(num_words));
+            let num_phoneme_words: usize = sentences
+                .iter()
+                .map(|sentence| count_phoneme_words(&sentence.phonemes))
+                .sum();
+            if num_phoneme_words != num_words {
+                tracing::warn!(
+                    "Can't match the words of `{}` with its phonemes; ignoring their duration scales",
+                    sentence_text
+                );
+                scales.clear();
+            }
+            let mut scales = scales.into_iter();
+            for sentence in sentences {
+                let phoneme_scales = phoneme_scales(&sentence.phonemes, &mut scales);
+                items.push((sentence, phoneme_scales));
+            }
+        }
+        let parts =
+            PiperSpeechStreamParallel::with_processor(&provider, items, |(sentence, scales)| {
+                provider.process_sentence_with_durations(sentence, scales)
+            })?
+            .collect::<PiperResult<Vec<Audio>>>()?;
+        self.join_parts(parts)
+    }
+    /// Estimate how long the synthesized audio of `text` will be, without running the
+    /// model. The estimate assumes an average speaking rate per phoneme, scaled by the
+    /// model's `length_scale` and the output config's rate and appended silence.
+    pub fn estimate_duration(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<Duration> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        Ok(provider.estimate_duration(&provider.get_sentences()?))
+    }
+    /// Estimate the peak memory, in bytes, of synthesizing `text` to a file in
+    /// parallel, to decide between parallel and streamed synthesis.
+    ///
+    /// The estimate is 12 bytes per sample of the estimated duration (see
+    /// `estimate_duration`): the `f32` sentences, their joined copy, its `i16`
+    /// conversion and the encoded WAV. A quarter is added for the error of the
+    /// duration estimate. It doesn't include the model itself or inference working memory.
+    pub fn estimate_peak_memory(
+        &self,
+        text: &str,
+        output_config: Option<&AudioOutputConfig>,
+    ) -> PiperResult<usize> {
+        let provider =
+            self.create_synthesis_task_provider(text.to_string(), output_config.cloned());
+        let num_samples = provider.estimate_len(&provider.get_sentences()?);
+        let bytes = num_samples * PEAK_BYTES_PER_SAMPLE;
+        Ok(bytes + bytes / 4)
+    }
+    /// Like `synthesize`, but synthesizes the sentences one after the other, calling
+    /// `progress` after each one with the audio produced so far and the estimated
+    /// total, which gives a smoother progress bar than counting sentences.
+    ///
+    /// The total is only an estimate (see `estimate_duration`), so the fraction may
+    /// stall near the end or jump to 1 when the last call reports the actual total.
+    pub fn synthesize_with_duration_progress<F>(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        mut progress: F,
+    ) -> PiperAudioResult
+    where
+        F: FnMut(DurationProgress),
+    {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let sentences = provider.get_sentences()?;
+        let estimated_total = provider.estimate_duration(&sentences);
+        let info = self.model.audio_output_info();
+        let mut limit = provider.output_limit();
+        let mut parts = Vec::with_capacity(sentences.len());
+        let mut num_frames = 0;
+        for sentence in sentences {
+            if limit.is_exhausted() {
+                break;
+            }
+            let mut audio = provider.process_sentence(sentence)?;
+            limit.apply(&mut audio.samples);
+            num_frames += audio.len() / info.num_channels.max(1);
+            let produced = Duration::from_secs_f64(num_frames as f64 / info.sample_rate as f64);
+            progress(DurationProgress {
+                produced,
+                estimated_total: estimated_total.max(produced),
+            });
+            parts.push(audio);
+        }
+        let audio = self.join_parts(parts)?;
+        let produced = Duration::from_secs_f64(num_frames as f64 / info.sample_rate as f64);
+        progress(DurationProgress {
+            produced,
+            estimated_total: produced,
+        });
+        Ok(audio)
+    }
+    /// Like `synthesize`, but also returns the non-fatal issues found while
+    /// phonemizing and synthesizing `text`, in sentence order
+    pub fn synthesize_verbose(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<(Audio, Vec<SynthesisWarning>)> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let mut warnings = Vec::new();
+        let sentences = provider.get_sentences_reporting(&mut warnings)?;
+        let mut stream = PiperSpeechStreamParallel::with_processor(&provider, sentences, |s| {
+            provider.process_sentence(s)
+        })?;
+        let parts = stream.by_ref().collect::<PiperResult<Vec<Audio>>>()?;
+        if stream.was_truncated() {
+            warnings.push(SynthesisWarning {
+                sentence_index: parts.len().saturating_sub(1),
+                kind: SynthesisWarningKind::Truncated,
+            });
+        }
+        Ok((self.join_parts(parts)?, warnings))
+    }
+    /// Synthesize `text` into one `AudioSegment` per sentence, in order, for
+    /// subtitles and captions. Offsets include any appended silence.
+    pub fn synthesize_segments(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<Vec<AudioSegment>> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let (texts, sentences): (Vec<String>, Vec<Vec<Sentence>>) =
+            provider.get_text_sentences()?.into_iter().unzip();
+        let num_parts = Vec::from_iter(sentences.iter().map(Vec::len));
+        let sentences = Vec::from_iter(sentences.into_iter().flatten());
+        let mut parts = PiperSpeechStreamParallel::with_processor(&provider, sentences, |s| {
+            provider.process_sentence(s)
+        })?
+        .collect::<PiperResult<Vec<Audio>>>()?
+        .into_iter();
+        let mut segments = Vec::with_capacity(texts.len());
+        let mut num_frames = 0;
+        for (text, num_parts) in texts.into_iter().zip(num_parts) {
+            let audio_parts = Vec::from_iter(parts.by_ref().take(num_parts));
+            // the rest was dropped to honor `max_output_duration`
+            if audio_parts.is_empty() {
+                break;
+            }
+            let audio = Audio::concat(audio_parts)?;
+            let sample_rate = audio.info.sample_rate as f64;
+            let start_offset = Duration::from_secs_f64(num_frames as f64 / sample_rate);
+            num_frames += audio.len() / audio.info.num_channels.max(1);
+            segments.push(AudioSegment {
+                text,
+                audio,
+                start_offset,
+            });
+        }
+        Ok(segments)
+    }
+    /// Phonemize `text` without synthesizing it, for editing the phonemes before
+    /// `synthesize_prepared`. Speaker tags and the long sentence policy are applied.
+    pub fn prepare(&self, text: String) -> PiperResult<PreparedUtterance> {
+        let provider = self.create_synthesis_task_provider(text, None);
+        Ok(PreparedUtterance {
+            sentences: provider.get_sentences()?,
+        })
+    }
+    /// Synthesize the sentences of `utterance` into a single `Audio`
+    pub fn synthesize_prepared(
+        &self,
+        utterance: &PreparedUtterance,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        let provider = self.create_synthesis_task_provider(String::new(), output_config);
+        let sentences = utterance.sentences.clone();
+        let parts = PiperSpeechStreamParallel::with_processor(&provider, sentences, |sentence| {
+            provider.process_sentence(sentence)
+        })?
+        .collect::<PiperResult<Vec<Audio>>>()?;
+        self.join_parts(parts)
+    }
+    /// `Audio::concat`, but empty input gives empty audio in the model's format
+    fn join_parts(&self, parts: Vec<Audio>) -> PiperAudioResult {
+        if parts.is_empty() {
+            return Ok(Audio {
+                samples: AudioSamples::default(),
+                info: self.model.audio_output_info(),
+                inference_ms: None,
+            });
+        }
+        Audio::concat(parts)
+    }
+    /// Join the audio of `stream`, into samples pre-sized from its estimated length.
+    /// `None` if the stream has no sentences.
+    fn join_stream(&self, stream: PiperSpeechStreamParallel) -> PiperResult<Option<Audio>> {
+        let capacity = stream.estimated_len;
+        let mut joined: Option<Audio> = None;
+        for result in stream {
+            let audio = result?;
+            match joined.as_mut() {
+                Some(joined) => joined.append(audio)?,
+                None => {
+                    let mut samples = AudioSamples::with_capacity(capacity.max(audio.len()));
+                    samples.merge(audio.samples);
+                    joined = Some(Audio { samples, ..audio });
+                }
+            }
+        }
+        Ok(joined)
+    }
+    /// Synthesize `text` in chunks of `chunk_size` mel frames, growing within each
+    /// sentence, as they're produced.
+    ///
+    /// Every sentence starts over at `chunk_size`. Earlier versions kept growing the
+    /// chunks of later sentences by the number of chunks streamed before them; use
+    /// `synthesize_streamed_two_phase` for larger chunks after the first.
+    pub fn synthesize_streamed(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        self.synthesize_streamed_with_context(text, output_config, chunk_size, chunk_padding, 0)
+    }
+    /// Like `synthesize_streamed`, but writes the stream to `writer` as a 16-bit PCM
+    /// WAV while it's produced, e.g. to a socket, so the client can start playing at
+    /// once. The WAV's sizes are `0xFFFFFFFF`, as its length isn't known up front.
+    ///
+    /// Chunks are converted to `i16` at a fixed scale, not peak-normalized like the
+    /// file writers' output. A write error stops the synthesis and is returned.
+    pub fn synthesize_to_stream<W: Write>(
+        &self,
+        mut writer: W,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<()> {
+        let stream = self.synthesize_streamed(text, output_config, chunk_size, chunk_padding)?;
+        let info = stream.info();
+        let write_error = |e: std::io::Error| {
+            PiperError::OperationError(format!("Failed to write wave bytes. Error: {}", e))
+        };
+        let header =
+            audio::streaming_wave_header(info.sample_rate as u32, info.num_channels as u32, 2);
+        writer.write_all(&header).map_err(write_error)?;
+        // returning early drops the stream, which stops its producer
+        for chunk in stream {
+            let bytes = Vec::from_iter(
+                chunk?
+                    .to_i16_vec_fixed_scale()
+                    .into_iter()
+                    .flat_map(i16::to_le_bytes),
+            );
+            writer.write_all(&bytes).map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+    /// Like `synthesize_streamed`, but with the chunk size and padding given in
+    /// milliseconds. `synthesize_streamed` counts them in mel frames of
+    /// `SAMPLES_PER_FRAME` samples; the durations are rounded up to whole frames, so
+    /// chunks are at least `chunk_ms` long, and grow by about `chunk_ms` every chunk.
+    ///
+    /// The durations are of the model's audio, before the output config is applied
+    /// to each chunk: a `rate` other than 1 shortens or lengthens the emitted chunks
+    /// by that factor, and resampling or upmixing keeps their duration, not their
+    /// number of samples.
+    pub fn synthesize_streamed_by_duration(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_ms: u32,
+        padding_ms: u32,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let sample_rate = self.model.audio_output_info().sample_rate;
+        let to_frames = |ms: u32| (ms as usize * sample_rate).div_ceil(1000 * SAMPLES_PER_FRAME);
+        self.synthesize_streamed(
+            text,
+            output_config,
+            to_frames(chunk_ms).max(1),
+            to_frames(padding_ms),
+        )
+    }
+    /// Like `synthesize_streamed`, but pairs every chunk with the phonemes spoken in
+    /// it, for tracking down where streamed audio goes wrong. The chunks are the
+    /// model's raw output, with no output config applied.
+    pub fn synthesize_streamed_with_phonemes(
+        &self,
+        text: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<impl Iterator<Item = PiperResult<(AudioSamples, String)>>> {
+        let provider = self.create_synthesis_task_provider(text, None);
+        let sentences = provider.get_single_speaker_sentences()?;
+        let info = self.model.audio_output_info();
+        let (tx, rx) = flume::bounded(CHANNEL_STREAM_CAPACITY);
+        let pool = provider.options.thread_pool.clone();
+        let pool: &ThreadPool = pool.as_deref().unwrap_or(&SYNTHESIS_THREAD_POOL);
+        pool.spawn(move || {
+            let streamed = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                for sentence in sentences {
+                    if !sentence.phonemes.is_empty() {
+                        let chunks = match provider.model.stream_synthesis_with_phonemes(
+                            sentence.phonemes,
+                            chunk_size,
+                            chunk_padding,
+                        ) {
+                            Ok(chunks) => chunks,
+                            Err(e) => {
+                                tx.send(Err(e)).ok();
+                                return;
+                            }
+                        };
+                        for chunk in chunks {
+                            let is_err = chunk.is_err();
+                            if tx.send(chunk).is_err() || is_err {
+                                return;
+                            }
+                        }
+                    }
+                    // the pause marked after the sentence, with no phonemes spoken in it
+                    if sentence.pause_ms > 0 {
+                        let pause = SilenceKind::default().generate_ms(
+                            sentence.pause_ms,
+                            info.sample_rate,
+                            info.num_channels,
+                        );
+                        if tx.send(Ok((pause.into(), String::new()))).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }));
+            if streamed.is_err() {
+                let error = PiperError::OperationError("Streamed synthesis panicked".to_string());
+                tx.send(Err(error)).ok();
+            }
+        });
+        Ok(rx.into_iter())
+    }
+    /// Like `synthesize_streamed`, but scales every chunk by the gain in `gain`,
+    /// an `f32` stored as its bit pattern (`f32::to_bits`). The gain is read once
+    /// per chunk, so storing a new value from another thread fades the voice live.
+    /// It's applied on top of `AudioOutputConfig::volume`.
+    pub fn synthesize_streamed_with_gain(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        gain: Arc<AtomicU32>,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let source =
+            SentenceSource::Sentences(provider.get_single_speaker_sentences()?.into_iter());
+        let params = StreamParams {
+            chunk_size,
+            steady_chunk_size: None,
+            chunk_padding,
+            context_phonemes: 0,
+            gain: Some(gain),
+        };
+        Ok(RealtimeSpeechStream::new(
+            provider,
+            source,
+            params,
+            self.model.audio_output_info(),
+        ))
+    }
+    /// Like `synthesize_streamed`, but feeds the last `context_phonemes` phonemes of
+    /// the previous sentence into the model together with the next one, to smooth
+    /// prosody across sentence joins. The context audio is trimmed, not emitted.
+    ///
+    /// This costs one extra encoder pass over `context_phonemes` phonemes per sentence,
+    /// which adds a little latency before each sentence's first chunk.
+    /// Models without predicted durations can't trim the context and ignore it.
+    pub fn synthesize_streamed_with_context(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        context_phonemes: usize,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let source =
+            SentenceSource::Sentences(provider.get_single_speaker_sentences()?.into_iter());
+        let params = StreamParams {
+            chunk_size,
+            steady_chunk_size: None,
+            chunk_padding,
+            context_phonemes,
+            gain: None,
+        };
+        Ok(RealtimeSpeechStream::new(
+            provider,
+            source,
+            params,
+            self.model.audio_output_info(),
+        ))
+    }
+    /// Like `synthesize_streamed`, but with a two-phase chunk size policy: the first
+    /// chunk of the stream has `first_chunk_size` mel frames, for the lowest time to
+    /// first audio, and every later chunk has `steady_chunk_size`, for efficiency.
+    ///
+    /// `synthesize_streamed` instead grows the chunks of each sentence by
+    /// `chunk_size` every chunk, starting over at every sentence.
+    pub fn synthesize_streamed_two_phase(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        first_chunk_size: usize,
+        steady_chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<RealtimeSpeechStream> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let source =
+            SentenceSource::Sentences(provider.get_single_speaker_sentences()?.into_iter());
+        let params = StreamParams {
+            chunk_size: first_chunk_size,
+            steady_chunk_size: Some(steady_chunk_size),
+            chunk_padding,
+            context_phonemes: 0,
+            gain: None,
+        };
+        Ok(RealtimeSpeechStream::new(
+            provider,
+            source,
+            params,
+            self.model.audio_output_info(),
+        ))
+    }
+    /// Like `synthesize_streamed`, but takes its text from `text_rx` as it arrives,
+    /// e.g. sentence by sentence from a language model, and ends once `text_rx` is
+    /// closed and drained. Speaker tags aren't supported.
+    ///
+    /// Only a few chunks are buffered ahead of the consumer, so a slow consumer holds
+    /// back synthesis, and with it the reading of `text_rx`.
+    pub fn synthesize_channel(
+        &self,
+        text_rx: Receiver<String>,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> RealtimeSpeechStream {
+        let provider = self.create_synthesis_task_provider(String::new(), output_config);
+        let source = SentenceSource::Text {
+            receiver: text_rx,
+            pending: std::vec::Vec::new().into_iter(),
+        };
+        let params = StreamParams {
+            chunk_size,
+            steady_chunk_size: None,
+            chunk_padding,
+            context_phonemes: 0,
+            gain: None,
+        };
+        RealtimeSpeechStream::new(provider, source, params, self.model.audio_output_info())
+    }
+    /// Push-style counterpart of `synthesize_streamed` for backends that can't drive an
+    /// iterator. Blocks until synthesis finishes, handing every chunk (or error) to
+    /// `sink`; returning `ControlFlow::Break` from `sink` stops the synthesis early.
+    pub fn synthesize_push<F>(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        chunk_size: usize,
+        chunk_padding: usize,
+        mut sink: F,
+    ) -> PiperResult<()>
+    where
+        F: FnMut(PiperResult<AudioSamples>) -> ControlFlow<()> + Send,
+    {
+        let stream = self.synthesize_streamed(text, output_config, chunk_size, chunk_padding)?;
+        for chunk in stream {
+            if sink(chunk).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn synthesize_to_file(
+        &self,
+        filename: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let audio = self.synthesize_file_samples(text, output_config)?;
+        Ok(audio::write_wave_samples_to_file(
+            filename,
+            audio.samples.to_i16_vec().iter(),
+            audio.info.sample_rate as u32,
+            audio.info.num_channels.try_into().unwrap(),
+            audio.info.sample_width.try_into().unwrap(),
+        )?)
+    }
+    /// Like `synthesize_to_file`, but also writes `metadata` as a `LIST`/`INFO` chunk
+    pub fn synthesize_to_file_with_tags(
+        &self,
+        filename: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        metadata: &WavMetadata,
+    ) -> PiperResult<()> {
+        let audio = self.synthesize_file_samples(text, output_config)?;
+        Ok(audio::write_wave_samples_with_metadata_to_file(
+            filename,
+            audio.samples.to_i16_vec().iter(),
+            audio.info.sample_rate as u32,
+            audio.info.num_channels.try_into().unwrap(),
+            audio.info.sample_width.try_into().unwrap(),
+            metadata,
+        )?)
+    }
+    /// Like `synthesize_to_file`, but stores the samples with `encoding`.
+    /// `WavEncoding::Float32` writes the `f32` samples as they are. The G.711
+    /// encodings resample the audio to 8000 Hz first (`Audio::resample` with
+    /// `ResampleQuality::Sinc`), the rate telephony expects.
+    pub fn synthesize_to_file_with_encoding(
+        &self,
+        filename: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        encoding: WavEncoding,
+    ) -> PiperResult<()> {
+        if encoding == WavEncoding::PcmI16 {
+            return self.synthesize_to_file(filename, text, output_config);
+        }
+        let audio = self.synthesize_file_samples(text, output_config)?;
+        let info = &audio.info;
+        if let Some(data) = g711_bytes(&audio, encoding)? {
+            return Ok(audio::write_wave_g711_to_file(
+                filename,
+                encoding,
+                &data,
+                G711_SAMPLE_RATE as u32,
+                info.num_channels.try_into().unwrap(),
+            )?);
+        }
+        Ok(audio::write_wave_f32_samples_to_file(
+            filename,
+            audio.samples.as_slice().iter(),
+            info.sample_rate as u32,
+            info.num_channels.try_into().unwrap(),
+            &WavMetadata::default(),
+        )?)
+    }
+    /// Like `synthesize_to_file_with_encoding`, but returns the WAV file's bytes
+    pub fn synthesize_to_wav_bytes(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+        encoding: WavEncoding,
+    ) -> PiperResult<Vec<u8>> {
+        let audio = self.synthesize_file_samples(text, output_config)?;
+        let info = &audio.info;
+        let (sample_rate, num_channels) = (info.sample_rate as u32, info.num_channels as u32);
+        let mut out = Vec::new();
+        match g711_bytes(&audio, encoding)? {
+            Some(data) => audio::write_wave_g711_to_buffer(
+                &mut out,
+                encoding,
+                &data,
+                G711_SAMPLE_RATE as u32,
+                num_channels,
+            )?,
+            None if encoding == WavEncoding::Float32 => audio::write_wave_f32_samples_to_buffer(
+                &mut out,
+                audio.samples.as_slice().iter(),
+                sample_rate,
+                num_channels,
+                &WavMetadata::default(),
+            )?,
+            None => audio::write_wave_samples_with_metadata_to_buffer(
+                &mut out,
+                audio.samples.to_i16_vec().iter(),
+                sample_rate,
+                num_channels,
+                info.sample_width as u32,
+                &WavMetadata::default(),
+            )?,
+        }
+        Ok(out)
+    }
+    /// Like `synthesize_to_file`, but also writes a cue point at the start of every
+    /// word, labelled with the word, so audio editors show a marker per word.
+    ///
+    /// Words are labelled with their text when a sentence has as many words as the
+    /// phonemizer produced, and with their phonemes otherwise.
+    ///
+    /// The cues come from the same inference as the audio, so this needs a model
+    /// that reports phoneme durations, i.e. a streaming (encoder/decoder) model;
+    /// other models fail. Sentences aren't split into phrases, and speaker tags
+    /// aren't supported.
+    pub fn synthesize_to_file_with_cues(
+        &self,
+        filename: &Path,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let provider = self.create_synthesis_task_provider(text, output_config);
+        let text_sentences = provider.get_text_sentences()?;
+        let items = Vec::from_iter(text_sentences.iter().enumerate().flat_map(
+            |(index, (_, sentences))| {
+                sentences
+                    .iter()
+                    .map(move |sentence| (index, sentence.clone()))
+            },
+        ));
+        let results: Vec<(usize, PiperResult<TimedSentence>)> = provider.run_in_pool(|| {
+            items
+                .into_par_iter()
+                .map(|(index, sentence)| (index, provider.process_sentence_with_timings(sentence)))
+                .collect()
+        });
+        let mut limit = provider.output_limit();
+        let mut text_words = Vec::from_iter(text_sentences.iter().map(|_| Vec::new()));
+        let mut parts = Vec::with_capacity(results.len());
+        let mut num_frames = 0;
+        for result in results {
+            if limit.is_exhausted() {
+                break;
+            }
+            let (index, result) = result;
+            let TimedSentence {
+                mut audio,
+                timings,
+                rate,
+            } = result?;
+            // the sentence starts where the audio of the ones before it ends
+            let offset_s = num_frames as f32 / audio.info.sample_rate as f32;
+            Self::sentence_intervals(
+                timings,
+                offset_s,
+                rate,
+                &mut Vec::new(),
+                &mut text_words[index],
+            );
+            limit.apply(&mut audio.samples);
+            num_frames += audio.len() / audio.info.num_channels.max(1);
+            parts.push(audio);
+        }
+        let audio = self.join_parts(parts)?;
+        if audio.is_empty() {
+            return Err(PiperError::OperationError(
+                "No speech data to write".to_string(),
+            ));
+        }
+        let info = &audio.info;
+        let mut cues = Vec::new();
+        for ((sentence, _), mut words) in text_sentences.iter().zip(text_words) {
+            label_words_with_text(sentence, &mut words);
+            cues.extend(
+                words
+                    .into_iter()
+                    .map(|word| WavCue {
+                        frame_offset: (word.start_s * info.sample_rate as f32).round() as u32,
+                        label: word.text,
+                    })
+                    .filter(|cue| (cue.frame_offset as usize) < num_frames),
+            );
+        }
+        let metadata = WavMetadata {
+            cues,
+            ..Default::default()
+        };
+        Ok(audio::write_wave_samples_with_metadata_to_file(
+            filename,
+            audio.samples.to_i16_vec().iter(),
+            info.sample_rate as u32,
+            info.num_channels.try_into().unwrap(),
+            info.sample_width.try_into().unwrap(),
+            &metadata,
+        )?)
+    }
+    /// The duration the model predicts for every phoneme of `text`, in sentence order,
+    /// to spot abnormally fast or slow phonemes. Only the encoder runs.
+    ///
+    /// Durations reflect the model's `length_scale`. The silences before and after
+    /// each sentence aren't reported.
+    pub fn phoneme_duration_report(&self, text: &str) -> PiperResult<Vec<PhonemeDuration>> {
+        let provider = self.create_synthesis_task_provider(text.to_string(), None);
+        let mut report = Vec::new();
+        for (sentence_index, phonemes) in provider.get_phonemes()?.into_iter().enumerate() {
+            report.extend(
+                self.model
+                    .phoneme_timings(phonemes)?
+                    .into_iter()
+                    .filter(|timing| !['^', '$'].contains(&timing.phoneme))
+                    .map(|timing| PhonemeDuration {
+                        sentence_index,
+                        phoneme: timing.phoneme,
+                        duration_ms: timing.duration_ms,
+                    }),
+            );
+        }
+        Ok(report)
+    }
+    /// Write the predicted timing of `text` as a Praat TextGrid with a phoneme tier
+    /// and a word tier. Only the encoder runs, so no audio is produced.
+    ///
+    /// Timings reflect the model's `length_scale` and `output_config`'s rate and
+    /// appended silence. Words are labelled with their phonemes.
+    pub fn export_textgrid(
+        &self,
+        text: &str,
+        path: &Path,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<()> {
+        let timings = self.timing_tiers(text, output_config)?;
+        let tiers = [
+            textgrid::IntervalTier {
+                name: "phones",
+                intervals: timings.phones,
+            },
+            textgrid::IntervalTier {
+                name: "words",
+                intervals: Vec::from_iter(
+                    timings
+                        .sentence_words
+                        .into_iter()
+                        .flat_map(|(_, words)| words),
+                ),
+            },
+        ];
+        std::fs::write(path, textgrid::to_textgrid(&tiers, timings.duration_s)).map_err(|e| {
+            PiperError::OperationError(format!("Failed to write TextGrid file. Error: {}", e))
+        })
+    }
+    /// The predicted phoneme and word intervals of `text`, with words grouped by
+    /// the text sentence they're in
+    fn timing_tiers(
+        &self,
+        text: &str,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<TimingTiers> {
+        let output_config = self.output_config_or_default(output_config);
+        let rate = output_config
+            .as_ref()
+            .and_then(|c| c.rate)
+            .filter(|r| *r > 0.0)
+            .unwrap_or(1.0);
+        let silence_ms = output_config.as_ref().map_or(0.0, |c| {
+            let ms = c.appended_silence_ms.unwrap_or(0) as f32;
+            if c.raw_appended_silence {
+                ms
+            } else {
+                ms / rate
+            }
+        });
+        let provider = self.create_synthesis_task_provider(text.to_string(), output_config);
+        let mut phones = Vec::new();
+        let mut sentence_words = Vec::new();
+        let mut offset_s = 0f32;
+        for (sentence_text, sentences) in provider.get_text_sentences()? {
+            let mut words = Vec::new();
+            for sentence in sentences {
+                if sentence.speaker.is_some() {
+                    return Err(PiperError::OperationError(
+                        "Speaker tags are not supported by this synthesis method".to_string(),
+                    ));
+                }
+                offset_s = Self::sentence_intervals(
+                    self.model.phoneme_timings(sentence.phonemes)?,
+                    offset_s,
+                    rate,
+                    &mut phones,
+                    &mut words,
+                );
+                offset_s += (silence_ms + sentence.pause_ms as f32) / 1000.0;
+            }
+            sentence_words.push((sentence_text, words));
+        }
+        Ok(TimingTiers {
+            phones,
+            sentence_words,
+            duration_s: offset_s,
+        })
+    }
+    /// Append the intervals of one sentence starting at `offset_s`, and return
+    /// when it ends
+    fn sentence_intervals(
+        timings: Vec<PhonemeTiming>,
+        offset_s: f32,
+        rate: f32,
+        phones: &mut Vec<textgrid::Interval>,
+        words: &mut Vec<textgrid::Interval>,
+    ) -> f32 {
+        let mut word: Option<textgrid::Interval> = None;
+        let mut sentence_end_s = offset_s;
+        for timing in timings {
+            let start_s = offset_s + timing.start_ms / rate / 1000.0;
+            let end_s = start_s + timing.duration_ms / rate / 1000.0;
+            sentence_end_s = end_s;
+            let is_marker = ['^', '$'].contains(&timing.phoneme);
+            if !is_marker {
+                phones.push(textgrid::Interval {
+                    start_s,
+                    end_s,
+                    text: timing.phoneme.to_string(),
+                });
+            }
+            let is_word_break = is_marker || is_phoneme_word_break(timing.phoneme);
+            match (&mut word, is_word_break) {
+                (Some(_), true) => words.extend(word.take()),
+                (Some(w), false) => {
+                    w.end_s = end_s;
+                    w.text.push(timing.phoneme);
+                }
+                (None, false) => {
+                    word = Some(textgrid::Interval {
+                        start_s,
+                        end_s,
+                        text: timing.phoneme.to_string(),
+                    })
+                }
+                (None, true) => {}
+            }
+        }
+        words.extend(word);
+        sentence_end_s
+    }
+    fn synthesize_file_samples(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperAudioResult {
+        match self.join_stream(self.synthesize_parallel(text, output_config)?)? {
+            Some(audio) if !audio.is_empty() => Ok(audio),
+            _ => Err(PiperError::OperationError(
+                "No speech data to write".to_string(),
+            )),
+        }
+    }
+    /// Synthesize `text` and return the samples widened to `f64`.
+    ///
+    /// Sonic works on `f32` internally, so the stages of `output_config` up to and
+    /// including sonic (rate, volume, pitch) and the EQ still run at `f32` precision.
+    /// The samples are then widened once, and `channel_gains` is applied to the
+    /// `f64` samples. With a `true_peak_ceiling_db`, whose limiter runs after the
+    /// gains on `f32`, the gains stay `f32` too.
+    pub fn synthesize_to_f64(
+        &self,
+        text: String,
+        output_config: Option<AudioOutputConfig>,
+    ) -> PiperResult<Vec<f64>> {
+        let mut output_config = self.output_config_or_default(output_config);
+        let channel_gains = match output_config {
+            Some(ref mut config) if config.true_peak_ceiling_db.is_none() => {
+                let num_channels = self.model.audio_output_info().num_channels;
+                config.check_channel_gains(config.output_channels(num_channels))?;
+                config.channel_gains.take()
+            }
+            _ => None,
+        };
+        let audio = match self.join_stream(self.synthesize_parallel(text, output_config)?)? {
+            Some(audio) => audio,
+            None => self.join_parts(Vec::new())?,
+        };
+        let mut samples = audio.samples.into_f64_vec();
+        if let Some(gains) = channel_gains {
+            for frame in samples.chunks_mut(gains.len().max(1)) {
+                frame
+                    .iter_mut()
+                    .zip(&gains)
+                    .for_each(|(sample, gain)| *sample *= f64::from(*gain));
+            }
+        }
+        Ok(samples)
+    }
+    #[inline(always)]
+    pub fn clone_model(&self) -> Arc<dyn PiperModel + Send + Sync> {
+        Arc::clone(&self.model)
+    }
+}
+
+impl PiperModel for PiperSpeechSynthesizer {
+    fn audio_output_info(&self) -> AudioInfo {
+        self.model.audio_output_info()
+    }
+    fn max_phoneme_length(&self) -> Option<usize> {
+        self.model.max_phoneme_length()
+    }
+    fn input_mode(&self) -> InputMode {
+        self.model.input_mode()
+    }
+    fn unknown_phonemes(&self, phonemes: &str) -> Vec<char> {
+        self.model.unknown_phonemes(phonemes)
+    }
+    fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+        self.model.phonemize_text(text)
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
+        self.model.speak_batch(phoneme_batches)
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
+        self.model.speak_one_sentence(phonemes)
+    }
+    fn speak_one_sentence_with(
+        &self,
+        phonemes: String,
+        synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        self.model.speak_one_sentence_with(phonemes, synth_config)
+    }
+    fn speak_one_sentence_into(&self, phonemes: String, out: &mut [f32]) -> PiperResult<usize> {
+        self.model.speak_one_sentence_into(phonemes, out)
+    }
+    fn speak_one_sentence_as(&self, phonemes: String, speaker: i64) -> PiperAudioResult {
+        self.model.speak_one_sentence_as(phonemes, speaker)
+    }
+    fn speak_input_ids(&self, input_ids: Vec<i64>) -> PiperAudioResult {
+        self.model.speak_input_ids(input_ids)
+    }
+    fn speaker_embedding(&self, sid: i64) -> PiperResult<Vec<f32>> {
+        self.model.speaker_embedding(sid)
+    }
+    fn speak_with_embedding(&self, phonemes: String, embedding: &[f32]) -> PiperAudioResult {
+        self.model.speak_with_embedding(phonemes, embedding)
+    }
+    fn speak_with_durations(
+        &self,
+        phonemes: String,
+        duration_scales: Vec<f32>,
+    ) -> PiperAudioResult {
+        self.model.speak_with_durations(phonemes, duration_scales)
+    }
+    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
+        self.model.get_default_synthesis_config()
+    }
+    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
+        self.model.get_fallback_synthesis_config()
+    }
+    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
+        self.model.set_fallback_synthesis_config(synthesis_config)
+    }
+    fn get_language(&self) -> PiperResult<Option<String>> {
+        self.model.get_language()
+    }
+    fn get_speakers(&self) -> PiperResult<Option<&HashMap<i64, String>>> {
+        self.model.get_speakers()
+    }
+    fn set_speaker(&self, sid: i64) -> Option<PiperError> {
+        self.model.set_speaker(sid)
+    }
+    fn set_phoneme_join(&self, policy: PhonemeJoinPolicy) -> PiperResult<()> {
+        self.model.set_phoneme_join(policy)
+    }
+    fn get_speaker_name(&self, sid: i64) -> Option<&str> {
+        self.model.get_speaker_name(sid)
+    }
+    fn set_speaker_by_name(&self, name: &str) -> Option<PiperError> {
+        self.model.set_speaker_by_name(name)
+    }
+    fn properties(&self) -> PiperResult<HashMap<String, String>> {
+        self.model.properties()
+    }
+    fn phoneme_timings(&self, phonemes: String) -> PiperResult<Vec<PhonemeTiming>> {
+        self.model.phoneme_timings(phonemes)
+    }
+    fn speak_with_timings(&self, phonemes: String) -> PiperResult<(Audio, Vec<PhonemeTiming>)> {
+        self.model.speak_with_timings(phonemes)
+    }
+    fn supports_streaming_output(&self) -> bool {
+        self.model.supports_streaming_output()
+    }
+    fn stream_synthesis<'a>(
+        &'a self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] chunk_size: usize,
+        #[allow(unused_variables)] chunk_padding: usize,
+    ) -> PiperResult<Box<dyn Iterator<Item = PiperResult<AudioSamples>> + Send + Sync + 'a>> {
+        self.model
+            .stream_synthesis(phonemes, chunk_size, chunk_padding)
+    }
+    fn stream_synthesis_with_context(
+        &self,
+        context: String,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.model
+            .stream_synthesis_with_context(context, phonemes, chunk_size, chunk_padding)
+    }
+    fn stream_synthesis_with_phonemes(
+        &self,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<PhonemeChunkIterator<'_>> {
+        self.model
+            .stream_synthesis_with_phonemes(phonemes, chunk_size, chunk_padding)
+    }
+    fn stream_synthesis_scheduled(
+        &self,
+        context: String,
+        phonemes: String,
+        schedule: ChunkSchedule,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.model
+            .stream_synthesis_scheduled(context, phonemes, schedule, chunk_padding)
+    }
+}
+
+struct SpeechSynthesisTaskProvider {
+    model: Arc<dyn PiperModel + Sync + Send>,
+    text: String,
+    output_config: Option<AudioOutputConfig>,
+    options: SynthesisOptions,
+    speaker_configs: HashMap<i64, AudioOutputConfig>,
+    /// The speaker embedding to speak sentences without a speaker tag with
+    embedding: Option<Arc<[f32]>>,
+}
+
+/// A sentence's phonemes, the speaker selected for it by a speaker tag, the pause
+/// marked after it and its emphasis
+#[derive(Clone)]
+struct Sentence {
+    phonemes: String,
+    speaker: Option<i64>,
+    pause_ms: u32,
+    emphasis: Option<EmphasisLevel>,
+}
+
+/// A part of the text that is phonemized on its own
+struct TextSegment {
+    speaker: Option<i64>,
+    text: String,
+    /// The pause marked after the part
+    pause_ms: u32,
+    emphasis: Option<EmphasisLevel>,
+}
+
+impl TextSegment {
+    fn sentence(&self, phonemes: String) -> Sentence {
+        Sentence {
+            phonemes,
+            speaker: self.speaker,
+            pause_ms: 0,
+            emphasis: self.emphasis,
+        }
+    }
+}
+
+fn spell_out_phonemes(model: &(dyn PiperModel + Sync + Send), word: &str) -> PiperResult<String> {
+    let letters = text::spell_out(word);
+    match model.input_mode() {
+        InputMode::Phoneme => Ok(model.phonemize_text(&letters)?.to_vec().join(" ")),
+        InputMode::Character => Ok(letters),
+    }
+}
+
+/// Add `pause_ms` to the pause after the last of `sentences`. A pause before any
+/// speech is dropped.
+fn add_pause(sentences: &mut [Sentence], pause_ms: u32) {
+    if let Some(last) = sentences.last_mut() {
+        last.pause_ms += pause_ms;
+    }
+}
+
+/// A sentence's processed audio, and the timing of its phonemes in the model's audio
+struct TimedSentence {
+    audio: Audio,
+    timings: Vec<PhonemeTiming>,
+    /// How much the output config speeds the sentence up, and so its phonemes
+    rate: f32,
+}
+
+struct TimingTiers {
+    phones: Vec<textgrid::Interval>,
+    sentence_words: Vec<(String, Vec<textgrid::Interval>)>,
+    duration_s: f32,
+}
+
+/// The words of `sentence`, without their surrounding punctuation
+fn text_words(sentence: &str) -> Vec<&str> {
+    Vec::from_iter(
+        sentence
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+            .filter(|word| !word.is_empty()),
+    )
+}
+
+fn is_phoneme_word_break(phoneme: char) -> bool {
+    phoneme.is_whitespace() || phoneme.is_ascii_punctuation()
+}
+
+fn count_phoneme_words(phonemes: &str) -> usize {
+    phonemes
+        .split(is_phoneme_word_break)
+        .filter(|word| !word.is_empty())
+        .count()
+}
+
+/// The scale of every phoneme of `phonemes`, taking the next of `word_scales` at the
+/// start of every word. Word breaks keep their duration.
+fn phoneme_scales(phonemes: &str, word_scales: &mut impl Iterator<Item = f32>) -> Vec<f32> {
+    let mut scale = 1.0;
+    let mut in_word = false;
+    Vec::from_iter(phonemes.chars().map(|phoneme| {
+        if is_phoneme_word_break(phoneme) {
+            in_word = false;
+            return 1.0;
+        }
+        if !in_word {
+            in_word = true;
+            scale = word_scales.next().unwrap_or(1.0);
+        }
+        scale
+    }))
+}
+
+/// Relabel `words` with the words of `sentence`, if there are as many of them
+fn label_words_with_text(sentence: &str, words: &mut [textgrid::Interval]) {
+    let text_words = text_words(sentence);
+    if text_words.len() == words.len() {
+        for (word, text) in words.iter_mut().zip(text_words) {
+            word.text = text.to_string();
+        }
+    }
+}
+
+impl SpeechSynthesisTaskProvider {
+    /// Like `get_sentences`, for synthesis paths that can't switch speakers
+    fn get_single_speaker_sentences(&self) -> PiperResult<Vec<Sentence>> {
+        let sentences = self.get_sentences()?;
+        if sentences.iter().any(|sentence| sentence.speaker.is_some()) {
+            return Err(PiperError::OperationError(
+                "Speaker tags are not supported by this synthesis method".to_string(),
+            ));
+        }
+        Ok(sentences)
+    }
+    /// Phonemes of every sentence, for paths that only look at the phonemes
+    fn get_phonemes(&self) -> PiperResult<Vec<String>> {
+        let sentences = self.get_single_speaker_sentences()?;
+        Ok(Vec::from_iter(
+            sentences
+                .into_iter()
+                .map(|sentence| sentence.phonemes)
+                .filter(|phonemes| !phonemes.is_empty()),
+        ))
+    }
+    /// The output config of a sentence with `emphasis`, adjusted for the emphasis
+    fn emphasized_output_config(
+        &self,
+        emphasis: Option<EmphasisLevel>,
+    ) -> Option<Cow<'_, AudioOutputConfig>> {
+        match emphasis {
+            Some(level) => Some(Cow::Owned(
+                self.emphasized(self.output_config.as_ref(), level),
+            )),
+            None => self.output_config.as_ref().map(Cow::Borrowed),
+        }
+    }
+    fn get_sentences(&self) -> PiperResult<Vec<Sentence>> {
+        self.get_sentences_reporting(&mut Vec::new())
+    }
+    /// Like `get_sentences`, but also collects the non-fatal issues found on the way
+    fn get_sentences_reporting(
+        &self,
+        warnings: &mut Vec<SynthesisWarning>,
+    ) -> PiperResult<Vec<Sentence>> {
+        let mut sentences = Vec::new();
+        for segment in self.text_segments()? {
+            if !segment.text.trim().is_empty() {
+                sentences.extend(
+                    self.phonemize_reporting(&segment.text, sentences.len(), warnings)?
+                        .into_iter()
+                        .map(|phonemes| segment.sentence(phonemes)),
+                );
+            }
+            add_pause(&mut sentences, segment.pause_ms);
+        }
+        if self.options.reverse_sentences {
+            sentences.reverse();
+        }
+        if sentences.is_empty() {
+            match self.options.empty_input_policy {
+                EmptyInputPolicy::Error => {
+                    return Err(PiperError::OperationError(
+                        "The text has no sentences to synthesize".to_string(),
+                    ))
+                }
+                // a sentence without phonemes is synthesized to its pause alone
+                EmptyInputPolicy::SilentClip { ms } => sentences.push(Sentence {
+                    phonemes: String::new(),
+                    speaker: None,
+                    pause_ms: ms,
+                    emphasis: None,
+                }),
+                EmptyInputPolicy::Empty => {}
+            }
+        }
+        Ok(sentences)
+    }
+    /// The first `max_sentences` sentences, phonemizing no more of the text than needed
+    fn get_first_sentences(&self, max_sentences: usize) -> PiperResult<Vec<Sentence>> {
+        let mut sentences = Vec::new();
+        'segments: for segment in self.text_segments()? {
+            for sentence_text in text::split_sentences(&segment.text) {
+                if sentences.len() >= max_sentences {
+                    break 'segments;
+                }
+                sentences.extend(
+                    self.phonemize(&sentence_text)?
+                        .into_iter()
+                        .map(|phonemes| segment.sentence(phonemes)),
+                );
+            }
+            add_pause(&mut sentences, segment.pause_ms);
+        }
+        sentences.truncate(max_sentences);
+        Ok(sentences)
+    }
+    /// Like `get_sentences`, but grouped by the text sentence each came from.
+    /// Text sentences without any phonemes are left out.
+    fn get_text_sentences(&self) -> PiperResult<Vec<(String, Vec<Sentence>)>> {
+        let mut text_sentences = Vec::new();
+        for segment in self.text_segments()? {
+            for sentence_text in text::split_sentences(&segment.text) {
+                let sentences = Vec::from_iter(
+                    self.phonemize(&sentence_text)?
+                        .into_iter()
+                        .map(|phonemes| segment.sentence(phonemes)),
+                );
+                if !sentences.is_empty() {
+                    text_sentences.push((sentence_text, sentences));
+                }
+            }
+            if let Some((_, sentences)) = text_sentences.last_mut() {
+                add_pause(sentences, segment.pause_ms);
+            }
+        }
+        if self.options.reverse_sentences {
+            text_sentences.reverse();
+        }
+        Ok(text_sentences)
+    }
+    /// The text split at speaker tags, if they're parsed, with the speaker of each part
+    fn speaker_segments(&self) -> PiperResult<Vec<(Option<i64>, String)>> {
+        if !self.options.parse_speaker_tags {
+            return Ok(vec![(None, self.text.clone())]);
+        }
+        let segments =
+            text::split_speaker_tags(&self.text).map_err(PiperError::PhonemizationError)?;
+        let mut speaker_segments = Vec::with_capacity(segments.len());
+        for segment in segments {
+            if let Some((sid, tag_offset)) = segment.speaker {
+                let is_known = self
+                    .model
+                    .get_speakers()?
+                    .is_some_and(|speakers| speakers.contains_key(&sid));
+                if !is_known {
+                    return Err(PiperError::OperationError(format!(
+                        "Unknown speaker id `{}` in speaker tag at byte {}",
+                        sid, tag_offset
+                    )));
+                }
+            }
+            speaker_segments.push((segment.speaker.map(|(sid, _)| sid), segment.text));
+        }
+        Ok(speaker_segments)
+    }
+    /// Like `speaker_segments`, but with digits normalized, and also split at
+    /// emphasis spans and, if they're parsed, pause markers
+    fn text_segments(&self) -> PiperResult<Vec<TextSegment>> {
+        let all_digits = self.options.digit_mode == DigitMode::Digits;
+        let strip_unknown = self
+            .options
+            .pause_markers
+            .map(|policy| policy == UnknownMarkerPolicy::Strip);
+        let mut segments = Vec::new();
+        for (speaker, text) in self.speaker_segments()? {
+            let text = text::normalize_digits(&text, all_digits);
+            for span in text::split_emphasis(&text) {
+                let emphasis = span
+                    .level
+                    .and_then(|level| EmphasisLevel::from_attribute(&level));
+                let parts = match strip_unknown {
+                    Some(strip_unknown) => text::split_pause_markers(&span.text, strip_unknown),
+                    None => vec![text::PausedText {
+                        text: span.text,
+                        pause_ms: 0,
+                    }],
+                };
+                segments.extend(parts.into_iter().map(|part| TextSegment {
+                    speaker,
+                    text: part.text,
+                    pause_ms: part.pause_ms,
+                    emphasis,
+                }));
+            }
+        }
+        Ok(segments)
+    }
+    fn phonemize(&self, text: &str) -> PiperResult<Vec<String>> {
+        self.phonemize_reporting(text, 0, &mut Vec::new())
+    }
+    /// Like `phonemize`, but also collects the non-fatal issues found on the way.
+    /// The first sentence of `text` is reported as sentence `first_index`.
+    fn phonemize_reporting(
+        &self,
+        text: &str,
+        first_index: usize,
+        warnings: &mut Vec<SynthesisWarning>,
+    ) -> PiperResult<Vec<String>> {
+        let sentences = match self.model.input_mode() {
+            InputMode::Phoneme => {
+                let text = self.replace_unsupported_scripts(text, first_index, warnings)?;
+                match self.model.phonemize_text(&text) {
+                    Ok(phonemes) => phonemes.to_vec(),
+                    Err(_) if self.options.oov_policy == OovPolicy::SpellOut => {
+                        self.phonemize_spelling_out(&text, first_index, warnings)?
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            InputMode::Character => text::split_character_sentences(text),
+        };
+        let max_len = self.model.max_phoneme_length().unwrap_or(usize::MAX);
+        let mut phonemes = Vec::with_capacity(sentences.len());
+        for sentence in sentences {
+            let sentence_index = first_index + phonemes.len();
+            let unknown_phonemes = self.model.unknown_phonemes(&sentence);
+            if !unknown_phonemes.is_empty() {
+                warnings.push(SynthesisWarning {
+                    sentence_index,
+                    kind: SynthesisWarningKind::UnknownPhonemes(unknown_phonemes),
+                });
+            }
+            let sentence_len = sentence.chars().count();
+            if sentence_len <= max_len {
+                phonemes.push(sentence);
+                continue;
+            }
+            match self.options.long_sentence_policy {
+                LongSentencePolicy::Split => {
+                    let mut parts = text::split_phonemes(&sentence, max_len);
+                    warnings.push(SynthesisWarning {
+                        sentence_index,
+                        kind: SynthesisWarningKind::SentenceSplit {
+                            num_phonemes: sentence_len,
+                            num_parts: parts.len(),
+                        },
+                    });
+                    phonemes.append(&mut parts)
+                }
+                LongSentencePolicy::Warn => {
+                    tracing::warn!(
+                        "Sentence {} has {} phonemes, more than the model's maximum of {}",
+                        sentence_index,
+                        sentence_len,
+                        max_len
+                    );
+                    warnings.push(SynthesisWarning {
+                        sentence_index,
+                        kind: SynthesisWarningKind::SentenceTooLong {
+                            num_phonemes: sentence_len,
+                            max_length: max_len,
+                        },
+                    });
+                    phonemes.push(sentence);
+                }
+                LongSentencePolicy::Error => {
+                    return Err(PiperError::OperationError(format!(
+                        "Sentence {} has {} phonemes, more than the model's maximum of {}",
+                        sentence_index, sentence_len, max_len
+                    )))
+                }
+            }
+        }
+        Ok(phonemes)
+    }
+    /// Phonemize `text` sentence by sentence, and the sentences the phonemizer
+    /// fails on word by word, spelling out the words it fails on
+    fn phonemize_spelling_out(
+        &self,
+        text: &str,
+        first_index: usize,
+        warnings: &mut Vec<SynthesisWarning>,
+    ) -> PiperResult<Vec<String>> {
+        let mut sentences = Vec::new();
+        for sentence_text in text::split_sentences(text) {
+            if let Ok(phonemes) = self.model.phonemize_text(&sentence_text) {
+                sentences.extend(phonemes.to_vec());
+                continue;
+            }
+            let mut words = Vec::new();
+            for word in sentence_text.split_whitespace() {
+                match self.model.phonemize_text(word) {
+                    Ok(phonemes) => words.push(phonemes.to_vec().join(" ")),
+                    Err(_) => {
+                        words.push(spell_out_phonemes(self.model.as_ref(), word)?);
+                        warnings.push(SynthesisWarning {
+                            sentence_index: first_index + sentences.len(),
+                            kind: SynthesisWarningKind::SpelledOut {
+                                word: word.to_string(),
+                            },
+                        });
+                    }
+                }
+            }
+            sentences.push(words.join(" "));
+        }
+        Ok(sentences)
+    }
+    /// Apply the unsupported script policy, if there is one, to `text`
+    fn replace_unsupported_scripts(
+        &self,
+        text: &str,
+        sentence_index: usize,
+        warnings: &mut Vec<SynthesisWarning>,
+    ) -> PiperResult<String> {
+        let Some(ref policy) = self.options.unsupported_script_policy else {
+            return Ok(text.to_string());
+        };
+        let language = self.model.get_language()?;
+        let mut replaced = String::with_capacity(text.len());
+        let mut end = 0;
+        for run in text::unsupported_script_runs(text, language.as_deref()) {
+            let run_text = &text[run.clone()];
+            let replacement = match policy {
+                UnsupportedScriptPolicy::Drop => None,
+                UnsupportedScriptPolicy::Transliterate(transliterate) => transliterate(run_text),
+                UnsupportedScriptPolicy::Error => {
+                    return Err(PiperError::PhonemizationError(format!(
+                        "Text `{}` at byte {} is in a script the phonemizer can't read",
+                        run_text, run.start
+                    )))
+                }
+            };
+            replaced.push_str(&text[end..run.start]);
+            // keep the words around a dropped run apart
+            replaced.push_str(replacement.as_deref().unwrap_or(" "));
+            warnings.push(SynthesisWarning {
+                sentence_index,
+                kind: SynthesisWarningKind::UnsupportedScript {
+                    text: run_text.to_string(),
+                    replacement,
+                },
+            });
+            end = run.end;
+        }
+        replaced.push_str(&text[end..]);
+        Ok(replaced)
+    }
+    fn process_sentence(&self, sentence: Sentence) -> PiperAudioResult {
+        let wave_samples = if sentence.phonemes.is_empty() {
+            Audio {
+                samples: AudioSamples::default(),
+                info: self.model.audio_output_info(),
+                inference_ms: None,
+            }
+        } else {
+            self.speak_one_sentence(sentence.phonemes, sentence.speaker)?
+        };
+        let audio =
+            self.apply_sentence_config(wave_samples, sentence.speaker, sentence.emphasis)?;
+        Ok(self.append_pause(audio, sentence.pause_ms))
+    }
+    /// Like `process_sentence`, but without phrase splitting, and also returns the
+    /// timing of every phoneme from the same inference. The sentence mustn't have
+    /// a speaker tag.
+    fn process_sentence_with_timings(&self, sentence: Sentence) -> PiperResult<TimedSentence> {
+        if sentence.speaker.is_some() {
+            return Err(PiperError::OperationError(
+                "Speaker tags are not supported by this synthesis method".to_string(),
+            ));
+        }
+        let (wave_samples, timings) = if sentence.phonemes.is_empty() {
+            let audio = Audio {
+                samples: AudioSamples::default(),
+                info: self.model.audio_output_info(),
+                inference_ms: None,
+            };
+            (audio, Vec::new())
+        } else {
+            self.model.speak_with_timings(sentence.phonemes)?
+        };
+        let positive = |value: Option<f32>| value.filter(|v| *v > 0.0).unwrap_or(1.0);
+        let rate = self
+            .emphasized_output_config(sentence.emphasis)
+            .map_or(1.0, |c| positive(c.rate) * positive(c.playback_rate));
+        let audio = self.apply_sentence_config(wave_samples, None, sentence.emphasis)?;
+        Ok(TimedSentence {
+            audio: self.append_pause(audio, sentence.pause_ms),
+            timings,
+            rate,
+        })
+    }
+    /// Like `process_sentence`, but scales the duration of every phoneme by its
+    /// entry of `duration_scales`. The sentence mustn't have a speaker tag.
+    fn process_sentence_with_durations(
+        &self,
+        sentence: Sentence,
+        duration_scales: Vec<f32>,
+    ) -> PiperAudioResult {
+        if duration_scales.iter().all(|scale| *scale == 1.0) {
+            return self.process_sentence(sentence);
+        }
+        let wave_samples = self
+            .model
+            .speak_with_durations(sentence.phonemes, duration_scales)?;
+        let audio = self.apply_sentence_config(wave_samples, None, sentence.emphasis)?;
+        Ok(self.append_pause(audio, sentence.pause_ms))
+    }
+    /// Append `pause_ms` of the output config's kind of silence to `audio`, which
+    /// isn't processed, so the pause lasts exactly as long as marked
+    fn append_pause(&self, mut audio: Audio, pause_ms: u32) -> Audio {
+        if pause_ms > 0 {
+            let silence = self
+                .output_config
+                .as_ref()
+                .map_or_else(SilenceKind::default, |c| c.silence);
+            audio.samples.as_mut_vec().extend(silence.generate_ms(
+                pause_ms,
+                audio.info.sample_rate,
+                audio.info.num_channels,
+            ));
+        }
+        audio
+    }
+    /// Apply the config of `speaker` if it has one, or the output config, adjusted
+    /// for `emphasis`
+    fn apply_sentence_config(
+        &self,
+        wave_samples: Audio,
+        speaker: Option<i64>,
+        emphasis: Option<EmphasisLevel>,
+    ) -> PiperAudioResult {
+        let config = speaker
+            .and_then(|sid| self.speaker_configs.get(&sid))
+            .or(self.output_config.as_ref());
+        match (emphasis, config) {
+            (Some(level), config) => self.emphasized(config, level).apply(wave_samples),
+            (None, Some(config)) => config.apply(wave_samples),
+            (None, None) => Ok(wave_samples),
+        }
+    }
+    /// `config`, with its volume, pitch and rate scaled by the prosody of `level`
+    fn emphasized(
+        &self,
+        config: Option<&AudioOutputConfig>,
+        level: EmphasisLevel,
+    ) -> AudioOutputConfig {
+        let prosody = self
+            .options
+            .emphasis_prosody
+            .get(&level)
+            .copied()
+            .unwrap_or_else(|| level.default_prosody());
+        let scale = |value: Option<f32>, factor: f32| Some(value.unwrap_or(1.0) * factor);
+        let mut config = config.cloned().unwrap_or_default();
+        config.volume = scale(config.volume, prosody.volume);
+        config.pitch = scale(config.pitch, prosody.pitch);
+        config.rate = scale(config.rate, prosody.rate);
+        config
+    }
+    /// Like `process_sentence`, but without the output config or pause
+    fn speak_one_sentence(&self, phonemes: String, speaker: Option<i64>) -> PiperAudioResult {
+        if let Some(threshold) = self.options.phrase_split_threshold {
+            if phonemes.chars().count() > threshold {
+                let phrases = text::split_phonemes(&phonemes, threshold);
+                if phrases.len() > 1 {
+                    return self.speak_phrases(phrases, speaker);
+                }
+            }
+        }
+        self.speak_unsplit(phonemes, speaker)
+    }
+    fn speak_phrases(&self, phrases: Vec<String>, speaker: Option<i64>) -> PiperAudioResult {
+        let parts = self.run_in_pool(|| {
+            phrases
+                .into_par_iter()
+                .map(|phrase| self.speak_unsplit(phrase, speaker))
+                .collect::<PiperResult<Vec<Audio>>>()
+        })?;
+        join_phrases(parts)
+    }
+    fn speak_unsplit(&self, phonemes: String, speaker: Option<i64>) -> PiperAudioResult {
+        match (speaker, &self.embedding) {
+            (Some(sid), _) => self.model.speak_one_sentence_as(phonemes, sid),
+            (None, Some(embedding)) => self.model.speak_with_embedding(phonemes, embedding),
+            (None, None) => self.model.speak_one_sentence(phonemes),
+        }
+    }
+    /// Like `process_sentence`, but synthesizes with `synth_config`. The sentence
+    /// mustn't have a speaker tag.
+    fn process_sentence_with(
+        &self,
+        sentence: Sentence,
+        synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        let wave_samples = if sentence.phonemes.is_empty() {
+            Audio {
+                samples: AudioSamples::default(),
+                info: self.model.audio_output_info(),
+                inference_ms: None,
+            }
+        } else {
+            self.model
+                .speak_one_sentence_with(sentence.phonemes, synth_config)?
+        };
+        let audio = self.apply_sentence_config(wave_samples, None, sentence.emphasis)?;
+        Ok(self.append_pause(audio, sentence.pause_ms))
+    }
+    /// Run `op` on the synthesizer's dedicated thread pool, if it has one
+    fn run_in_pool<R, F>(&self, op: F) -> R
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        match self.options.thread_pool {
+            Some(ref pool) => pool.install(op),
+            None => op(),
+        }
+    }
+    fn estimate_duration(&self, sentences: &[Sentence]) -> Duration {
+        let length_scale = self
+            .model
+            .get_fallback_synthesis_config()
+            .ok()
+            .and_then(|config| {
+                config
+                    .downcast_ref::<PiperSynthesisConfig>()
+                    .map(|config| config.length_scale)
+            })
+            .unwrap_or(1.0);
+        let num_phonemes: usize = sentences.iter().map(|s| s.phonemes.chars().count()).sum();
+        let mut seconds =
+            AVERAGE_PHONEME_DURATION.as_secs_f32() * length_scale * num_phonemes as f32;
+        let pause_s = sentences.iter().map(|s| s.pause_ms).sum::<u32>() as f32 / 1000.0;
+        if let Some(ref config) = self.output_config {
+            let silence_s =
+                config.appended_silence_ms.unwrap_or(0) as f32 / 1000.0 * sentences.len() as f32;
+            let speed = config.rate.unwrap_or(1.0) * config.playback_rate.unwrap_or(1.0);
+            if config.raw_appended_silence {
+                seconds = seconds / speed + silence_s;
+            } else {
+                seconds = (seconds + silence_s) / speed;
+            }
+        }
+        Duration::try_from_secs_f32(seconds + pause_s).unwrap_or_default()
+    }
+    /// The number of samples of the estimated duration of `sentences`
+    fn estimate_len(&self, sentences: &[Sentence]) -> usize {
+        let info = self.model.audio_output_info();
+        let num_frames = self.estimate_duration(sentences).as_secs_f64() * info.sample_rate as f64;
+        num_frames.ceil() as usize * info.num_channels.max(1)
+    }
+    fn output_limit(&self) -> OutputLimit {
+        OutputLimit::new(
+            self.options.max_output_duration,
+            &self.model.audio_output_info(),
+        )
+    }
+    fn apply_output_config(&self, wave_samples: Audio) -> PiperAudioResult {
+        match self.output_config {
+            Some(ref config) => config.apply(wave_samples),
+            None => Ok(wave_samples),
+        }
+    }
+    #[allow(dead_code)]
+    fn process_batches(&self, phonemes: Vec<String>) -> PiperResult<Vec<Audio>> {
+        let wave_samples = self.model.speak_batch(phonemes)?;
+        match self.output_config {
+            Some(ref config) => {
+                let mut processed: Vec<Audio> = Vec::with_capacity(wave_samples.len());
+                for samples in wave_samples.into_iter() {
+                    processed.push(config.apply(samples)?);
+                }
+                Ok(processed)
+            }
+            None => Ok(wave_samples),
+        }
+    }
+}
+
+/// Phonemized text returned by `PiperSpeechSynthesizer::prepare`, whose sentences
+/// can be edited one by one and synthesized again without phonemizing the rest
+#[derive(Clone)]
+pub struct PreparedUtterance {
+    sentences: Vec<Sentence>,
+}
+
+impl PreparedUtterance {
+    pub fn num_sentences(&self) -> usize {
+        self.sentences.len()
+    }
+    pub fn phonemes(&self, index: usize) -> Option<&str> {
+        self.sentences.get(index).map(|s| s.phonemes.as_str())
+    }
+    /// The speaker selected by a speaker tag for sentence `index`, if any
+    pub fn speaker(&self, index: usize) -> Option<i64> {
+        self.sentences.get(index)?.speaker
+    }
+    /// Replace the phonemes of sentence `index`
+    pub fn set_phonemes(&mut self, index: usize, phonemes: String) -> PiperResult<()> {
+        let num_sentences = self.sentences.len();
+        let Some(sentence) = self.sentences.get_mut(index) else {
+            return Err(PiperError::OperationError(format!(
+                "Sentence index {} is out of range for an utterance of {} sentences",
+                index, num_sentences
+            )));
+        };
+        sentence.phonemes = phonemes;
+        Ok(())
+    }
+}
+
+/// The audio of one sentence of a text, and where it starts in the audio of the
+/// whole text
+#[derive(Clone, Debug)]
+pub struct AudioSegment {
+    pub text: String,
+    pub audio: Audio,
+    pub start_offset: Duration,
+}
+
+/// A sentence's audio and how long it took to synthesize and process
+pub type TimedAudioResult = PiperResult<(Audio, Duration)>;
+
+/// Run `op`, and measure how long it took
+fn timed<R>(op: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let result = op();
+    (result, start.elapsed())
+}
+
+pub struct PiperSpeechStreamLazy {
+    provider: SpeechSynthesisTaskProvider,
+    sentences: std::vec::IntoIter<Sentence>,
+    limit: OutputLimit,
+}
+
+impl PiperSpeechStreamLazy {
+    fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
+        let sentences = provider.get_sentences()?.into_iter();
+        let limit = provider.output_limit();
+        Ok(Self {
+            provider,
+            sentences,
+            limit,
+        })
+    }
+    /// Whether audio was dropped to honor the synthesizer's `max_output_duration`
+    pub fn was_truncated(&self) -> bool {
+        self.limit.truncated
+    }
+    /// Yield each sentence along with how long it took to synthesize and process
+    pub fn timed(mut self) -> impl Iterator<Item = TimedAudioResult> + Send {
+        std::iter::from_fn(move || self.next_timed())
+    }
+    fn next_timed(&mut self) -> Option<TimedAudioResult> {
+        if self.limit.is_exhausted() {
+            if self.sentences.len() > 0 {
+                self.limit.truncated = true;
+                self.sentences = Vec::new().into_iter();
+            }
+            return None;
+        }
+        let sentence = self.sentences.next()?;
+        let (result, elapsed) = timed(|| self.provider.process_sentence(sentence));
+        match result {
+            Ok(mut ws) => {
+                self.limit.apply(&mut ws.samples);
+                Some(Ok((ws, elapsed)))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl Iterator for PiperSpeechStreamLazy {
+    type Item = PiperAudioResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_timed()?.map(|(audio, _)| audio))
+    }
+}
+
+/// Synthesizes sentences in parallel, yielding them in order.
+///
+/// Streams from `synthesize_parallel` synthesize in the background; dropping the
+/// stream stops any sentences that haven't started yet from being synthesized.
+#[must_use]
+pub struct PiperSpeechStreamParallel {
+    results: ParallelResults,
+    limit: OutputLimit,
+    /// The estimated number of samples of the whole stream, or 0 if unknown
+    estimated_len: usize,
+}
+
+enum ParallelResults {
+    Precalculated(std::vec::IntoIter<(PiperAudioResult, Duration)>),
+    Pending(PendingResults),
+}
+
+impl ParallelResults {
+    fn next(&mut self) -> Option<(PiperAudioResult, Duration)> {
+        match self {
+            Self::Precalculated(results) => results.next(),
+            Self::Pending(pending) => pending.next(None),
+        }
+    }
+    fn num_remaining(&self) -> usize {
+        match self {
+            Self::Precalculated(results) => results.len(),
+            Self::Pending(pending) => pending.num_sentences - pending.next_index,
+        }
+    }
+}
+
+/// Results sent by the sentences synthesizing in the pool, in the order they finish
+struct PendingResults {
+    receiver: Receiver<(usize, (PiperAudioResult, Duration))>,
+    /// Results that finished before the ones preceding them
+    arrived: HashMap<usize, (PiperAudioResult, Duration)>,
+    next_index: usize,
+    num_sentences: usize,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PendingResults {
+    /// The next result in order, or `None` once there are none left or `deadline`,
+    /// if any, passed before it arrived
+    fn next(&mut self, deadline: Option<Instant>) -> Option<(PiperAudioResult, Duration)> {
+        if self.next_index >= self.num_sentences {
+            return None;
+        }
+        let result = loop {
+            if let Some(result) = self.arrived.remove(&self.next_index) {
+                break result;
+            }
+            // every sentence sends a result, even one that panicked, so this only
+            // fails if the pool dropped the work without running it
+            let (index, result) = match deadline {
+                Some(deadline) => self.receiver.recv_deadline(deadline).ok()?,
+                None => self.receiver.recv().ok()?,
+            };
+            self.arrived.insert(index, result);
+        };
+        self.next_index += 1;
+        Some(result)
+    }
+}
+
+impl Drop for PendingResults {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl PiperSpeechStreamParallel {
+    fn new(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
+        let sentences = provider.get_sentences()?;
+        let estimated_len = provider.estimate_len(&sentences);
+        Ok(Self {
+            estimated_len,
+            ..Self::spawn(provider, HashMap::new(), sentences)
+        })
+    }
+    /// Like `new`, but synthesizes the first sentence before returning
+    fn new_hybrid(provider: SpeechSynthesisTaskProvider) -> PiperResult<Self> {
+        let mut sentences = provider.get_sentences()?.into_iter();
+        let mut arrived = HashMap::new();
+        if let Some(first) = sentences.next() {
+            let result = timed(|| provider.process_sentence(first));
+            arrived.insert(0, result);
+        }
+        Ok(Self::spawn(provider, arrived, sentences.collect()))
+    }
+    /// Synthesize `sentences` in the background, after the results in `arrived`
+    fn spawn(
+        provider: SpeechSynthesisTaskProvider,
+        arrived: HashMap<usize, (PiperAudioResult, Duration)>,
+        sentences: Vec<Sentence>,
+    ) -> Self {
+        let first_index = arrived.len();
+        let num_sentences = first_index + sentences.len();
+        let limit = provider.output_limit();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = Arc::clone(&cancelled);
+        let (tx, receiver) = flume::unbounded();
+        let pool = provider.options.thread_pool.clone();
+        let work = move || {
+            sentences
+                .into_par_iter()
+                .enumerate()
+                .for_each(|(index, sentence)| {
+                    if worker_cancelled.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    let index = first_index + index;
+                    // a panic on a spawned task would abort the process
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                        timed(|| provider.process_sentence(sentence))
+                    }))
+                    .unwrap_or_else(|_| {
+                        let error = PiperError::OperationError(format!(
+                            "Synthesis of sentence {} panicked",
+                            index
+                        ));
+                        (Err(error), Duration::ZERO)
+                    });
+                    if tx.send((index, result)).is_err() {
+                        worker_cancelled.store(true, Ordering::Relaxed);
+                    }
+                })
+        };
+        match pool {
+            Some(pool) => pool.spawn(work),
+            None => rayon::spawn(work),
+        }
+        Self {
+            results: ParallelResults::Pending(PendingResults {
+                receiver,
+                arrived,
+                next_index: 0,
+                num_sentences,
+                cancelled,
+            }),
+            limit,
+            estimated_len: 0,
+        }
+    }
+    /// Wait until `deadline` at the latest for the sentences still pending, then
+    /// cancel the ones that aren't done, keeping the results completed in order
+    fn completed_by(mut self, deadline: Instant) -> Self {
+        let mut completed = Vec::new();
+        if let ParallelResults::Pending(ref mut pending) = self.results {
+            while let Some(result) = pending.next(Some(deadline)) {
+                completed.push(result);
+            }
+        }
+        self.limit.truncated |= self.results.num_remaining() > 0;
+        // dropping the pending results cancels the sentences that haven't started
+        self.results = ParallelResults::Precalculated(completed.into_iter());
+        self
+    }
+    fn with_processor<T, F>(
+        provider: &SpeechSynthesisTaskProvider,
+        items: Vec<T>,
+        process: F,
+    ) -> PiperResult<Self>
+    where
+        T: Send,
+        F: Fn(T) -> PiperAudioResult + Sync + Send,
+    {
+        let calculated_result = provider.run_in_pool(|| {
+            items
+                .into_par_iter()
+                .map(|item| timed(|| process(item)))
+                .collect()
+        });
+        Ok(Self::from_results(provider, calculated_result))
+    }
+    fn from_results(
+        provider: &SpeechSynthesisTaskProvider,
+        results: Vec<(PiperAudioResult, Duration)>,
+    ) -> Self {
+        Self {
+            results: ParallelResults::Precalculated(results.into_iter()),
+            limit: provider.output_limit(),
+            estimated_len: 0,
+        }
+    }
+    /// Whether audio was dropped to honor the synthesizer's `max_output_duration`,
+    /// or the deadline passed to `synthesize_parallel_deadline`
+    pub fn was_truncated(&self) -> bool {
+        self.limit.truncated
+    }
+    /// Yield each sentence along with how long it took to synthesize and process
+    pub fn timed(mut self) -> impl Iterator<Item = TimedAudioResult> + Send {
+        std::iter::from_fn(move || self.next_timed())
+    }
+    fn next_timed(&mut self) -> Option<TimedAudioResult> {
+        if self.limit.is_exhausted() {
+            if self.results.num_remaining() > 0 {
+                self.limit.truncated = true;
+                // also cancels the sentences that are still pending
+                self.results = ParallelResults::Precalculated(Vec::new().into_iter());
+            }
+            return None;
+        }
+        let (result, elapsed) = self.results.next()?;
+        Some(result.map(|mut audio| {
+            self.limit.apply(&mut audio.samples);
+            (audio, elapsed)
+        }))
+    }
+}
+
+impl Iterator for PiperSpeechStreamParallel {
+    type Item = PiperAudioResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_timed()?.map(|(audio, _)| audio))
+    }
+}
+
+/// A boxed stream of synthesized sentences, e.g. a `PiperSpeechStreamLazy`
+pub type AudioResultIterator<'a> = Box<dyn Iterator<Item = PiperAudioResult> + Send + 'a>;
+
+/// Yields the audio of several streams, possibly from different synthesizers, one
+/// stream after the other.
+///
+/// All audio must have the same channel count. Without a target sample rate it
+/// must also have the same sample rate, and the stream ends with an error at the
+/// first audio that doesn't match; with one, audio at other rates is resampled.
+pub struct ChainedSpeechStream<'a> {
+    streams: std::vec::IntoIter<AudioResultIterator<'a>>,
+    current: Option<AudioResultIterator<'a>>,
+    target_sample_rate: Option<usize>,
+    expected_info: Option<AudioInfo>,
+}
+
+impl<'a> ChainedSpeechStream<'a> {
+    pub fn new(streams: Vec<AudioResultIterator<'a>>) -> Self {
+        Self {
+            streams: streams.into_iter(),
+            current: None,
+            target_sample_rate: None,
+            expected_info: None,
+        }
+    }
+    /// Like `new`, but resamples all audio to `sample_rate`
+    pub fn with_target_sample_rate(
+        streams: Vec<AudioResultIterator<'a>>,
+        sample_rate: usize,
+    ) -> Self {
+        Self {
+            target_sample_rate: Some(sample_rate),
+            ..Self::new(streams)
+        }
+    }
+    fn check_info(&mut self, audio: Audio) -> PiperAudioResult {
+        let audio = match self.target_sample_rate {
+            Some(sample_rate) => audio.resample_linear(sample_rate),
+            None => audio,
+        };
+        let expected = self.expected_info.get_or_insert_with(|| audio.info.clone());
+        if audio.info.sample_rate != expected.sample_rate
+            || audio.info.num_channels != expected.num_channels
+        {
+            return Err(PiperError::OperationError(format!(
+                "Chained audio is {} Hz with {} channel(s), expected {} Hz with {} channel(s)",
+                audio.info.sample_rate,
+                audio.info.num_channels,
+                expected.sample_rate,
+                expected.num_channels
+            )));
+        }
+        Ok(audio)
+    }
+}
+
+impl Iterator for ChainedSpeechStream<'_> {
+    type Item = PiperAudioResult;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some(stream) = self.current.as_mut() else {
+                self.current = Some(self.streams.next()?);
+                continue;
+            };
+            match stream.next() {
+                Some(Ok(audio)) => {
+                    let result = self.check_info(audio);
+                    if result.is_err() {
+                        self.current = None;
+                        self.streams = Vec::new().into_iter();
+                    }
+                    return Some(result);
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                None => self.current = None,
+            }
+        }
+    }
+}
+
+/// The gain a realtime stream is ducked to, set by `RealtimeSpeechStream::duck`
+struct DuckControl {
+    /// An `f32` stored as its bit pattern
+    target_gain: AtomicU32,
+    ramp_ms: AtomicU32,
+}
+
+impl Default for DuckControl {
+    fn default() -> Self {
+        Self {
+            target_gain: AtomicU32::new(1f32.to_bits()),
+            ramp_ms: AtomicU32::new(0),
+        }
+    }
+}
+
+/// Ramps the gain of every frame toward the target of a `DuckControl`
+struct DuckRamp {
+    control: Arc<DuckControl>,
+    sample_rate: usize,
+    num_channels: usize,
+    gain: f32,
+    target_gain: f32,
+    /// How much the gain moves per frame
+    step: f32,
+}
+
+impl DuckRamp {
+    fn new(control: Arc<DuckControl>, sample_rate: usize, num_channels: usize) -> Self {
+        Self {
+            control,
+            sample_rate,
+            num_channels: num_channels.max(1),
+            gain: 1.0,
+            target_gain: 1.0,
+            step: 0.0,
+        }
+    }
+    fn apply(&mut self, samples: &mut AudioSamples) {
+        let target_gain = f32::from_bits(self.control.target_gain.load(Ordering::Acquire));
+        if target_gain != self.target_gain {
+            let ramp_ms = self.control.ramp_ms.load(Ordering::Relaxed);
+            let ramp_frames = ramp_ms as usize * self.sample_rate / 1000;
+            self.target_gain = target_gain;
+            self.step = (target_gain - self.gain).abs() / ramp_frames.max(1) as f32;
+        }
+        if self.gain == self.target_gain && self.gain == 1.0 {
+            return;
+        }
+        for frame in samples.as_mut_vec().chunks_mut(self.num_channels) {
+            self.gain = if self.gain < self.target_gain {
+                (self.gain + self.step).min(self.target_gain)
+            } else {
+                (self.gain - self.step).max(self.target_gain)
+            };
+            frame.iter_mut().for_each(|sample| *sample *= self.gain);
+        }
+    }
+}
+
+/// The sample rate G.711 audio is written at
+const G711_SAMPLE_RATE: usize = 8000;
+
+/// The G.711 companded samples of `audio` resampled to `G711_SAMPLE_RATE`, if
+/// `encoding` is a G.711 encoding
+fn g711_bytes(audio: &Audio, encoding: WavEncoding) -> PiperResult<Option<Vec<u8>>> {
+    let encode = match encoding {
+        WavEncoding::MuLaw => AudioSamples::to_ulaw,
+        WavEncoding::ALaw => AudioSamples::to_alaw,
+        WavEncoding::PcmI16 | WavEncoding::Float32 => return Ok(None),
+    };
+    let audio = audio.resample(G711_SAMPLE_RATE, ResampleQuality::Sinc)?;
+    Ok(Some(encode(&audio.samples)))
+}
+
+/// Join the phrases of a sentence, crossfading each into the next
+fn join_phrases(parts: Vec<Audio>) -> PiperAudioResult {
+    let Some(info) = parts.first().map(|part| part.info.clone()) else {
+        return Audio::concat(parts);
+    };
+    let mut crossfade = Crossfader::new(PHRASE_CROSSFADE_MS, info.sample_rate, info.num_channels);
+    let mut samples = AudioSamples::default();
+    let mut inference_ms = None;
+    for part in parts {
+        if let Some(ms) = part.inference_ms {
+            *inference_ms.get_or_insert(0.0) += ms;
+        }
+        samples.merge(crossfade.push(part.samples).unwrap_or_default());
+        samples.merge(crossfade.end_sentence().unwrap_or_default());
+    }
+    samples.merge(crossfade.finish().unwrap_or_default());
+    Ok(Audio {
+        samples,
+        info,
+        inference_ms,
+    })
+}
+
+/// Crossfades the end of each sentence of a realtime stream into the start of the
+/// next, holding back the last chunk of a sentence until it's known to be the last
+struct Crossfader {
+    num_frames: usize,
+    num_channels: usize,
+    held: Option<AudioSamples>,
+    /// The end of the previous sentence, to mix into the next chunk
+    tail: Option<AudioSamples>,
+}
+
+impl Crossfader {
+    fn new(duration_ms: u32, sample_rate: usize, num_channels: usize) -> Self {
+        Self {
+            num_frames: duration_ms as usize * sample_rate / 1000,
+            num_channels: num_channels.max(1),
+            held: None,
+            tail: None,
+        }
+    }
+    /// Take the next chunk of the current sentence, returning the chunk it replaces
+    fn push(&mut self, samples: AudioSamples) -> Option<AudioSamples> {
+        if self.num_frames == 0 {
+            return Some(samples);
+        }
+        let samples = match self.tail.take() {
+            Some(tail) => self.mix(tail, samples),
+            None => samples,
+        };
+        self.held.replace(samples)
+    }
+    /// End the current sentence, returning what's left of it but the tail to crossfade
+    fn end_sentence(&mut self) -> Option<AudioSamples> {
+        let mut held = self.held.take()?.into_vec();
+        let num_frames = held.len() / self.num_channels;
+        let tail_len = self.num_frames.min(num_frames) * self.num_channels;
+        self.tail = Some(held.split_off(held.len() - tail_len).into());
+        (!held.is_empty()).then(|| held.into())
+    }
+    /// Everything still held back, at the end of the stream
+    fn finish(&mut self) -> Option<AudioSamples> {
+        let mut samples = self.tail.take().unwrap_or_default();
+        if let Some(held) = self.held.take() {
+            samples.merge(held);
+        }
+        (!samples.is_empty()).then_some(samples)
+    }
+    /// `tail` fading out over the start of `head` fading in
+    fn mix(&self, tail: AudioSamples, head: AudioSamples) -> AudioSamples {
+        let (mut out, head) = (tail.into_vec(), head.into_vec());
+        let num_overlap_frames = out.len().min(head.len()) / self.num_channels;
+        let overlap = num_overlap_frames * self.num_channels;
+        let start = out.len() - overlap;
+        for (i, (a, b)) in out[start..].iter_mut().zip(&head[..overlap]).enumerate() {
+            let weight = ((i / self.num_channels) as f32 + 0.5) / num_overlap_frames as f32;
+            *a = *a * (1.0 - weight) + b * weight;
+        }
+        out.extend_from_slice(&head[overlap..]);
+        out.into()
+    }
+}
+
+/// Where the realtime producer sends its output, applying the gain, the ducking,
+/// the crossfade and the output limit on the way
+struct ChunkSink<'a> {
+    tx: &'a Sender<PiperResult<AudioSamples>>,
+    limit: OutputLimit,
+    gain: Option<&'a AtomicU32>,
+    duck: DuckRamp,
+    crossfade: Crossfader,
+    /// Runs last, so the output stays under its ceiling whatever the gain
+    limiter: Option<TruePeakLimiter>,
+}
+
+type ChunkSendResult = Result<bool, SendError<PiperResult<AudioSamples>>>;
+
+impl ChunkSink<'_> {
+    /// Send `result`, returning whether there's room for more output
+    fn send(&mut self, result: PiperResult<AudioSamples>) -> ChunkSendResult {
+        match result {
+            Ok(samples) => match self.crossfade.push(samples) {
+                Some(samples) => self.emit(Ok(samples)),
+                None => Ok(!self.limit.is_exhausted()),
+            },
+            Err(e) => {
+                self.finish()?;
+                self.emit(Err(e))
+            }
+        }
+    }
+    /// Send what's left of the current sentence, but the tail kept for the crossfade
+    fn end_sentence(&mut self) -> ChunkSendResult {
+        match self.crossfade.end_sentence() {
+            Some(samples) => self.emit(Ok(samples)),
+            None => Ok(!self.limit.is_exhausted()),
+        }
+    }
+    /// Send everything that's still held back
+    fn finish(&mut self) -> ChunkSendResult {
+        if let Some(samples) = self.crossfade.finish() {
+            if !self.emit(Ok(samples))? {
+                return Ok(false);
+            }
+        }
+        match self.limiter.as_mut().map(TruePeakLimiter::flush) {
+            Some(samples) => self.send_limited(samples),
+            None => Ok(!self.limit.is_exhausted()),
+        }
+    }
+    fn emit(&mut self, result: PiperResult<AudioSamples>) -> ChunkSendResult {
+        match result {
+            Ok(mut samples) => {
+                if let Some(gain) = self.gain {
+                    samples.apply_gain(f32::from_bits(gain.load(Ordering::Relaxed)));
+                }
+                self.duck.apply(&mut samples);
+                if let Some(ref mut limiter) = self.limiter {
+                    samples = limiter.process(samples);
+                }
+                self.send_limited(samples)
+            }
+            Err(e) => {
+                if self.limit.is_exhausted() {
+                    self.limit.truncated = true;
+                    return Ok(false);
+                }
+                self.tx.send(Err(e))?;
+                Ok(true)
+            }
+        }
+    }
+    /// Send samples that went through every stage, but the output limit
+    fn send_limited(&mut self, mut samples: AudioSamples) -> ChunkSendResult {
+        if self.limit.is_exhausted() {
+            self.limit.truncated = true;
+            return Ok(false);
+        }
+        if samples.is_empty() {
+            return Ok(true);
+        }
+        self.limit.apply(&mut samples);
+        self.tx.send(Ok(samples))?;
+        Ok(true)
+    }
+}
+
+pub struct RealtimeSpeechStream {
+    receiver: Receiver<PiperResult<AudioSamples>>,
+    info: AudioInfo,
+    truncated: Arc<AtomicBool>,
+    duck: Arc<DuckControl>,
+}
+
+/// Number of chunks a realtime stream fed from a channel buffers ahead of its consumer
+const CHANNEL_STREAM_CAPACITY: usize = 8;
+
+/// Where a realtime stream gets its sentences from
+enum SentenceSource {
+    Sentences(std::vec::IntoIter<Sentence>),
+    /// Text arriving over a channel, and the phonemes of the last text received
+    Text {
+        receiver: Receiver<String>,
+        pending: std::vec::IntoIter<String>,
+    },
+}
+
+impl SentenceSource {
+    fn next_sentence(
+        &mut self,
+        provider: &SpeechSynthesisTaskProvider,
+    ) -> Option<PiperResult<Sentence>> {
+        match self {
+            Self::Sentences(sentences) => sentences.next().map(Ok),
+            Self::Text { receiver, pending } => loop {
+                if let Some(phonemes) = pending.next() {
+                    return Some(Ok(Sentence {
+                        phonemes,
+                        speaker: None,
+                        pause_ms: 0,
+                        emphasis: None,
+                    }));
+                }
+                let text = receiver.recv().ok()?;
+                match provider.phonemize(&text) {
+                    Ok(phonemes) => *pending = phonemes.into_iter(),
+                    Err(e) => return Some(Err(e)),
+                }
+            },
+        }
+    }
+}
+
+struct StreamParams {
+    /// The size of the first chunk of every sentence, or of only the first chunk
+    /// of the stream with `steady_chunk_size`
+    chunk_size: usize,
+    steady_chunk_size: Option<usize>,
+    chunk_padding: usize,
+    context_phonemes: usize,
+    gain: Option<Arc<AtomicU32>>,
+}
 
 impl RealtimeSpeechStream {
     fn new(
         provider: SpeechSynthesisTaskProvider,
-        chunk_size: usize,
-        chunk_padding: usize,
-        sample_rate: usize,
-        num_channels: usize,
-    ) -> PiperResult<Self> {
-        let phonemes = provider.get_phonemes()?.into_iter();
-        let (tx, rx) = flume::unbounded();
-        SYNTHESIS_THREAD_POOL.spawn(move || {
-            let mut chunk_size = chunk_size;
-            let chunk_factor = 1;
-            let mut num_processed_chunks = 0;
-            for ph_sent in phonemes {
-                chunk_size = if num_processed_chunks != 0 {
-                    chunk_size * chunk_factor * num_processed_chunks
-                } else {
-                    chunk_size
+        mut source: SentenceSource,
+        params: StreamParams,
+        info: AudioInfo,
+    ) -> Self {
+        let StreamParams {
+            chunk_size,
+            steady_chunk_size,
+            chunk_padding,
+            context_phonemes,
+            gain,
+        } = params;
+        let (tx, rx) = match source {
+            SentenceSource::Sentences(_) => flume::unbounded(),
+            SentenceSource::Text { .. } => flume::bounded(CHANNEL_STREAM_CAPACITY),
+        };
+        let (sample_rate, num_channels) = (info.sample_rate, info.num_channels);
+        let info = match provider.output_config {
+            Some(ref config) => config.output_info(info),
+            None => info,
+        };
+        let output_channels = info.num_channels;
+        let pool = provider.options.thread_pool.clone();
+        let pool: &ThreadPool = pool.as_deref().unwrap_or(&SYNTHESIS_THREAD_POOL);
+        let truncated = Arc::new(AtomicBool::new(false));
+        let producer_truncated = Arc::clone(&truncated);
+        let duck = Arc::new(DuckControl::default());
+        let producer_duck = Arc::clone(&duck);
+        pool.spawn(move || {
+            let crossfade_ms = provider
+                .output_config
+                .as_ref()
+                .map_or(0, |c| c.crossfade_ms);
+            let mut sink = ChunkSink {
+                tx: &tx,
+                limit: provider.output_limit(),
+                gain: gain.as_deref(),
+                duck: DuckRamp::new(producer_duck, sample_rate, output_channels),
+                crossfade: Crossfader::new(crossfade_ms, sample_rate, output_channels),
+                limiter: provider
+                    .output_config
+                    .as_ref()
+                    .and_then(|c| c.true_peak_limiter(sample_rate, output_channels)),
+            };
+            if let Some(Err(e)) = provider
+                .output_config
+                .as_ref()
+                .map(|c| c.check_channel_gains(output_channels))
+            {
+                sink.send(Err(e)).ok();
+                return;
+            }
+            let mut is_first_sentence = true;
+            let mut previous_sentence: Option<String> = None;
+            while let Some(sentence) = source.next_sentence(&provider) {
+                let sentence = match sentence {
+                    Ok(sentence) => sentence,
+                    Err(e) => {
+                        sink.send(Err(e)).ok();
+                        return;
+                    }
                 };
-                match provider
-                    .model
-                    .stream_synthesis(ph_sent, chunk_size, chunk_padding)
-                {
+                if sink.limit.is_exhausted() {
+                    sink.limit.truncated = true;
+                    break;
+                }
+                let context = match previous_sentence.take() {
+                    Some(previous) => {
+                        let num_chars = previous.chars().count();
+                        String::from_iter(
+                            previous
+                                .chars()
+                                .skip(num_chars.saturating_sub(context_phonemes)),
+                        )
+                    }
+                    None => String::new(),
+                };
+                let ph_sent = sentence.phonemes;
+                if context_phonemes > 0 {
+                    previous_sentence = Some(ph_sent.clone());
+                }
+                let stream_result = match steady_chunk_size {
+                    // a sentence without phonemes is streamed to its pause alone
+                    _ if ph_sent.is_empty() => {
+                        Ok(Box::new(std::iter::empty()) as AudioStreamIterator)
+                    }
+                    Some(steady_chunk_size) => {
+                        let schedule = ChunkSchedule {
+                            first_chunk_size: if is_first_sentence {
+                                chunk_size
+                            } else {
+                                steady_chunk_size
+                            },
+                            steady_chunk_size,
+                        };
+                        provider.model.stream_synthesis_scheduled(
+                            context,
+                            ph_sent,
+                            schedule,
+                            chunk_padding,
+                        )
+                    }
+                    None => provider.model.stream_synthesis_with_context(
+                        context,
+                        ph_sent,
+                        chunk_size,
+                        chunk_padding,
+                    ),
+                };
+                is_first_sentence = false;
+                match stream_result {
                     Ok(stream) => {
                         let send_result = RealtimeSpeechStream::process_rt_stream(
                             stream,
-                            &tx,
-                            provider.output_config.as_ref(),
+                            &mut sink,
+                            provider
+                                .emphasized_output_config(sentence.emphasis)
+                                .as_deref(),
                             sample_rate,
                             num_channels,
+                            sentence.pause_ms,
                         );
-                        match send_result {
-                            Ok(num_chunks) => num_processed_chunks += num_chunks,
-                            Err(_) => return,
-                        };
+                        if send_result.is_err() {
+                            return;
+                        }
                     }
                     Err(e) => {
-                        tx.send(Err(e)).ok();
+                        sink.send(Err(e)).ok();
                         return;
                     }
                 };
             }
+            if sink.finish().is_err() {
+                return;
+            }
+            producer_truncated.store(sink.limit.truncated, Ordering::Release);
         });
-        Ok(Self(rx))
+        Self {
+            receiver: rx,
+            info,
+            truncated,
+            duck,
+        }
+    }
+    /// Fade the chunks emitted from now on to `target_gain` over `ramp_ms`, e.g.
+    /// to talk quieter while the user barges in. Chunks already emitted are not
+    /// affected. Applied on top of any other gain.
+    pub fn duck(&self, target_gain: f32, ramp_ms: u32) {
+        self.duck.ramp_ms.store(ramp_ms, Ordering::Relaxed);
+        self.duck
+            .target_gain
+            .store(target_gain.max(0.0).to_bits(), Ordering::Release);
+    }
+    /// Fade back to full volume over `ramp_ms`, undoing `duck`
+    pub fn unduck(&self, ramp_ms: u32) {
+        self.duck(1.0, ramp_ms);
+    }
+    /// Whether audio was dropped to honor the synthesizer's `max_output_duration`.
+    /// Only final once the stream is exhausted.
+    pub fn was_truncated(&self) -> bool {
+        self.truncated.load(Ordering::Acquire)
+    }
+    /// The format of the emitted samples, after output processing
+    pub fn info(&self) -> AudioInfo {
+        self.info.clone()
+    }
+    /// Yield each chunk along with its peak and RMS level, e.g. for a live level
+    /// meter, measured over the interleaved samples of every channel
+    pub fn with_metering(
+        self,
+    ) -> impl Iterator<Item = PiperResult<(AudioSamples, ChunkLevels)>> + Send {
+        self.map(|result| {
+            result.map(|samples| {
+                let levels = ChunkLevels::of(&samples);
+                (samples, levels)
+            })
+        })
+    }
+    /// Yield each chunk no sooner than the audio before it would have finished
+    /// playing, counting from the arrival of the first chunk. Time spent waiting on
+    /// synthesis counts towards the wait.
+    pub fn throttle_to_realtime(self) -> impl Iterator<Item = PiperResult<AudioSamples>> + Send {
+        let info = self.info();
+        let samples_per_sec = (info.sample_rate * info.num_channels.max(1)).max(1) as f64;
+        let mut start = None;
+        let mut played = Duration::ZERO;
+        self.map(move |result| {
+            let due = *start.get_or_insert_with(Instant::now) + played;
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+            if let Ok(ref samples) = result {
+                played += Duration::from_secs_f64(samples.len() as f64 / samples_per_sec);
+            }
+            result
+        })
+    }
+    /// Block until the producer is done, and join all chunks into one `Audio`.
+    /// Returns the first error encountered, if any.
+    pub fn collect_all(self) -> PiperAudioResult {
+        let info = self.info.clone();
+        let mut samples = AudioSamples::default();
+        for chunk in self {
+            samples.merge(chunk?);
+        }
+        Ok(Audio {
+            samples,
+            info,
+            inference_ms: None,
+        })
     }
+    /// Process and send the chunks of a sentence, followed by the `pause_ms` pause
+    /// marked after it
     #[inline(always)]
     fn process_rt_stream(
         stream: AudioStreamIterator,
-        tx: &Sender<PiperResult<AudioSamples>>,
+        sink: &mut ChunkSink,
         audio_output_config: Option<&AudioOutputConfig>,
         sample_rate: usize,
         num_channels: usize,
-    ) -> Result<usize, SendError<PiperResult<AudioSamples>>> {
-        let mut num_chunks = 0;
+        pause_ms: u32,
+    ) -> Result<(), SendError<PiperResult<AudioSamples>>> {
+        let pause = |silence: SilenceKind, num_channels| {
+            AudioSamples::from(silence.generate_ms(pause_ms, sample_rate, num_channels))
+        };
         if let Some(output_config) = audio_output_config {
+            let mut pipeline = ChunkPipeline::new(output_config, sample_rate, num_channels);
             for result in stream {
-                match result {
-                    Ok(samples) => {
-                        tx.send(output_config.apply_to_raw_samples(
-                            samples,
-                            sample_rate,
-                            num_channels,
-                        ))?;
-                        num_chunks += 1;
-                    }
-                    Err(e) => {
-                        tx.send(Err(e))?;
+                let result = result.map(|samples| pipeline.process(samples));
+                if result.as_ref().is_ok_and(AudioSamples::is_empty) {
+                    continue;
+                }
+                if !sink.send(result)? {
+                    return Ok(());
+                }
+            }
+            match pipeline.flush() {
+                Ok(samples) if samples.is_empty() => {}
+                Ok(samples) => {
+                    if !sink.send(Ok(samples))? {
+                        return Ok(());
                     }
-                };
+                }
+                Err(e) => {
+                    sink.send(Err(e))?;
+                    return Ok(());
+                }
             }
             if let Some(silence_ms) = output_config.appended_silence_ms {
-                let silence_result =
-                    output_config.generate_silence(silence_ms as usize, sample_rate, num_channels);
-                tx.send(silence_result)?;
+                let silence_result = output_config.generate_silence(
+                    silence_ms as usize,
+                    sample_rate,
+                    output_config.output_channels(num_channels),
+                );
+                if !sink.send(silence_result)? {
+                    return Ok(());
+                }
             }
-            Ok(num_chunks)
+            if pause_ms > 0 {
+                let num_channels = output_config.output_channels(num_channels);
+                if !sink.send(Ok(pause(output_config.silence, num_channels)))? {
+                    return Ok(());
+                }
+            }
+            sink.end_sentence()?;
+            Ok(())
         } else {
             for result in stream {
-                tx.send(result)?;
-                num_chunks += 1;
+                if !sink.send(result)? {
+                    break;
+                }
+            }
+            if pause_ms > 0 && !sink.send(Ok(pause(SilenceKind::default(), num_channels)))? {
+                return Ok(());
             }
-            Ok(num_chunks)
+            sink.end_sentence()?;
+            Ok(())
         }
     }
 }
 
+/// Bounds on how long the ring buffer pump waits for a chunk before checking the
+/// buffer's level and whether the consumer is gone
+#[cfg(feature = "ringbuf")]
+const MIN_PUMP_WAIT: Duration = Duration::from_millis(1);
+#[cfg(feature = "ringbuf")]
+const MAX_PUMP_WAIT: Duration = Duration::from_millis(50);
+
+#[cfg(feature = "ringbuf")]
+impl RealtimeSpeechStream {
+    /// Feed the stream into an `rtrb` ring buffer from a dedicated pump thread,
+    /// for audio callbacks that pull samples.
+    ///
+    /// Whenever synthesis falls behind and the buffer holds fewer than
+    /// `low_watermark` samples, the pump tops it up with silence so the consumer
+    /// never underruns. The pump ends with the stream, on its first error, or
+    /// once the consumer is dropped.
+    pub fn pump_into(
+        self,
+        mut producer: rtrb::Producer<f32>,
+        low_watermark: usize,
+    ) -> PiperResult<std::thread::JoinHandle<PiperResult<()>>> {
+        let samples_per_sec = (self.info.sample_rate * self.info.num_channels.max(1)).max(1);
+        // how long `num_samples` samples take to play
+        let play_time = move |num_samples: usize| {
+            Duration::from_secs_f64(num_samples as f64 / samples_per_sec as f64)
+                .clamp(MIN_PUMP_WAIT, MAX_PUMP_WAIT)
+        };
+        std::thread::Builder::new()
+            .name("piper_ringbuf_pump".to_string())
+            .spawn(move || loop {
+                if producer.is_abandoned() {
+                    return Ok(());
+                }
+                let num_buffered = producer.buffer().capacity() - producer.slots();
+                // wake up before the buffer runs below the watermark
+                let timeout = match low_watermark {
+                    0 => MAX_PUMP_WAIT,
+                    _ => play_time(
+                        num_buffered
+                            .saturating_sub(low_watermark)
+                            .max(low_watermark / 2),
+                    ),
+                };
+                match self.receiver.recv_timeout(timeout) {
+                    Ok(chunk) => {
+                        let chunk = chunk?;
+                        let mut samples = chunk.as_slice();
+                        while !samples.is_empty() {
+                            if producer.is_abandoned() {
+                                return Ok(());
+                            }
+                            let num_samples = producer.slots().min(samples.len());
+                            if num_samples == 0 {
+                                // wait for the consumer to make room for some of the chunk
+                                let capacity = producer.buffer().capacity();
+                                std::thread::sleep(play_time(samples.len().min(capacity / 4)));
+                                continue;
+                            }
+                            let (head, tail) = samples.split_at(num_samples);
+                            if let Ok(write_chunk) = producer.write_chunk_uninit(num_samples) {
+                                write_chunk.fill_from_iter(head.iter().copied());
+                            }
+                            samples = tail;
+                        }
+                    }
+                    Err(flume::RecvTimeoutError::Timeout) => {
+                        let num_buffered = producer.buffer().capacity() - producer.slots();
+                        let num_silence = low_watermark.saturating_sub(num_buffered);
+                        if num_silence > 0 {
+                            if let Ok(write_chunk) = producer.write_chunk_uninit(num_silence) {
+                                write_chunk.fill_from_iter(std::iter::repeat(0.0));
+                            }
+                        }
+                    }
+                    Err(flume::RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            })
+            .map_err(|e| {
+                PiperError::OperationError(format!(
+                    "Failed to spawn ring buffer pump thread. Error: {}",
+                    e
+                ))
+            })
+    }
+}
+
 impl Iterator for RealtimeSpeechStream {
     type Item = PiperResult<AudioSamples>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.recv().ok()
+        self.receiver.recv().ok()
+    }
+}
+
+/// The levels of one chunk of a stream, see `RealtimeSpeechStream::with_metering`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChunkLevels {
+    /// The largest absolute sample value
+    pub peak: f32,
+    pub rms: f32,
+}
+
+impl ChunkLevels {
+    fn of(samples: &AudioSamples) -> Self {
+        let stats = samples.stats();
+        Self {
+            peak: stats.peak,
+            rms: stats.rms,
+        }
+    }
+}
+
+/// Applies an `AudioOutputConfig` to every chunk of a stream of raw samples,
+/// e.g. a `RealtimeSpeechStream` synthesized without an output config.
+///
+/// The chunks are processed like those of `synthesize_streamed`, keeping the
+/// processing state from one chunk to the next; the samples it still holds back
+/// are yielded once the inner stream ends. The stream has no sentence
+/// boundaries, so `appended_silence_ms` is ignored.
+pub struct ApplyConfig<I> {
+    inner: I,
+    /// `None` once flushed at the end of the inner stream
+    pipeline: Option<ChunkPipeline<'static>>,
+}
+
+impl<I> ApplyConfig<I> {
+    pub fn new(
+        inner: I,
+        config: AudioOutputConfig,
+        sample_rate: usize,
+        num_channels: usize,
+    ) -> Self {
+        Self {
+            inner,
+            pipeline: Some(ChunkPipeline::with_config(
+                Cow::Owned(config),
+                sample_rate,
+                num_channels,
+            )),
+        }
+    }
+}
+
+impl<I> Iterator for ApplyConfig<I>
+where
+    I: Iterator<Item = PiperResult<AudioSamples>>,
+{
+    type Item = PiperResult<AudioSamples>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let pipeline = self.pipeline.as_mut()?;
+            let processed = match self.inner.next() {
+                Some(result) => result.map(|samples| pipeline.process(samples)),
+                None => {
+                    let flushed = pipeline.flush();
+                    self.pipeline = None;
+                    flushed
+                }
+            };
+            if !processed.as_ref().is_ok_and(AudioSamples::is_empty) {
+                return Some(processed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockPiperModel;
+
+    #[test]
+    fn test_raw_appended_silence_is_exact() {
+        let config = AudioOutputConfig::builder()
+            .rate(1.5)
+            .appended_silence_ms(250)
+            .raw_appended_silence(true)
+            .build();
+        let silence = config.generate_silence(250, 22050, 1).unwrap();
+        assert_eq!(silence.len(), 22050 / 4);
+        assert!(silence.into_iter().all(|f| f == 0.0));
+    }
+
+    #[test]
+    fn test_channel_gains() {
+        let stereo = || Audio::from_planar(vec![vec![0.5; 4], vec![0.5; 4]], 22050).unwrap();
+        let config = AudioOutputConfig::builder()
+            .channel_gains(vec![1.0, 0.5])
+            .build();
+        let planar = config.apply(stereo()).unwrap().to_planar();
+        assert!(planar[0].iter().all(|s| (s - 0.5).abs() < 1e-3));
+        assert!(planar[1].iter().all(|s| (s - 0.25).abs() < 1e-3));
+        let config = AudioOutputConfig::builder()
+            .channel_gains(vec![0.5])
+            .build();
+        assert!(config.apply(stereo()).is_err());
+        let mut pipeline = ChunkPipeline::new(&config, 22050, 1);
+        let mut processed = pipeline.process(vec![0.5; 2048].into());
+        processed.merge(pipeline.flush().unwrap());
+        assert_eq!(processed.len(), 2048);
+        assert!(processed.as_slice().iter().all(|s| (s - 0.25).abs() < 1e-3));
+    }
+
+    #[test]
+    fn test_output_info() {
+        let stereo = AudioInfo {
+            sample_rate: 16000,
+            num_channels: 2,
+            sample_width: 2,
+            native_sample_rate: 16000,
+        };
+        let info = AudioOutputConfig::builder()
+            .force_mono(true)
+            .rate(1.5)
+            .build()
+            .output_info(stereo.clone());
+        assert_eq!(
+            (info.sample_rate, info.num_channels, info.sample_width),
+            (16000, 1, 2)
+        );
+        let info = AudioOutputConfig::default().output_info(stereo);
+        assert_eq!(info.num_channels, 2);
+    }
+
+    #[test]
+    fn test_apply_reporting_neutral_config() {
+        let config = AudioOutputConfig::builder().rate(1.0).build();
+        let audio = Audio::new(vec![0.25, -0.5, 0.125].into(), 22050, None);
+        let (processed, modified) = config.apply_reporting(audio.clone()).unwrap();
+        assert!(!modified);
+        assert_eq!(processed.samples.as_slice(), audio.samples.as_slice());
+    }
+
+    #[test]
+    fn test_comfort_noise_is_reproducible() {
+        let kind = SilenceKind::ComfortNoise {
+            level_db: -60.0,
+            seed: Some(42),
+        };
+        let noise = kind.generate(1000);
+        assert_eq!(noise, kind.generate(1000));
+        assert!(noise.iter().any(|s| *s != 0.0));
+        assert!(noise.iter().all(|s| s.abs() <= 0.001));
+    }
+
+    #[test]
+    fn test_apply_odd_rate_pitch_combinations() {
+        let samples = Vec::from_iter((0..2205).map(|i| (i as f32 * 0.05).sin() * 0.5));
+        for (rate, pitch) in [(0.37, 1.9), (2.71, 0.43), (1.0001, 0.999), (3.9, 3.9)] {
+            let config = AudioOutputConfig::builder().rate(rate).pitch(pitch).build();
+            let output = config
+                .apply_to_raw_samples(samples.clone().into(), 22050, 1)
+                .unwrap();
+            assert!(!output.is_empty());
+            assert!(output.as_slice().iter().all(|s| s.is_finite()));
+        }
+    }
+
+    #[test]
+    fn test_apply_config_adapter() {
+        let config = AudioOutputConfig::builder()
+            .volume(0.5)
+            .appended_silence_ms(500)
+            .build();
+        let chunks: Vec<PiperResult<AudioSamples>> = vec![
+            Ok(vec![0.5f32; 2205].into()),
+            Err(PiperError::with_message("bad chunk")),
+        ];
+        let results = Vec::from_iter(ApplyConfig::new(chunks.into_iter(), config, 22050, 1));
+        let (samples, errors): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+        assert_eq!(errors.len(), 1);
+        let samples = Vec::from_iter(samples.into_iter().flat_map(|s| s.unwrap().into_vec()));
+        assert_eq!(samples.len(), 2205);
+        assert!(samples.iter().all(|s| *s < 0.3));
+    }
+
+    #[test]
+    fn test_apply_config_matches_rt_stream() {
+        let config = AudioOutputConfig::builder().rate(1.5).volume(0.8).build();
+        let chunks = || -> AudioStreamIterator {
+            Box::new((0..8).map(|i| {
+                let samples = (0..1000).map(|j| ((i * 1000 + j) as f32 * 0.05).sin() * 0.5);
+                Ok(AudioSamples::from(Vec::from_iter(samples)))
+            }))
+        };
+        let adapted = Vec::from_iter(
+            ApplyConfig::new(chunks(), config.clone(), 22050, 1)
+                .flat_map(|samples| samples.unwrap().into_vec()),
+        );
+        let (tx, rx) = flume::unbounded();
+        let mut sink = test_sink(&tx, None, 0);
+        RealtimeSpeechStream::process_rt_stream(chunks(), &mut sink, Some(&config), 22050, 1, 0)
+            .unwrap();
+        drop(sink);
+        drop(tx);
+        let streamed = Vec::from_iter(
+            rx.into_iter()
+                .flat_map(|samples| samples.unwrap().into_vec()),
+        );
+        assert!(!adapted.is_empty());
+        assert_eq!(adapted, streamed);
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let info = AudioInfo {
+            sample_rate: 1000,
+            num_channels: 1,
+            sample_width: 2,
+            native_sample_rate: 1000,
+        };
+        let mut limit = OutputLimit::new(Some(Duration::from_millis(150)), &info);
+        let mut first = AudioSamples::from(vec![0.0; 100]);
+        limit.apply(&mut first);
+        assert_eq!(first.len(), 100);
+        assert!(!limit.truncated);
+        let mut second = AudioSamples::from(vec![0.0; 100]);
+        limit.apply(&mut second);
+        assert_eq!(second.len(), 50);
+        assert!(limit.truncated && limit.is_exhausted());
+    }
+
+    #[test]
+    fn test_rt_stream_keeps_sonic_state_across_chunks() {
+        let config = AudioOutputConfig::builder().rate(1.5).build();
+        let samples = Vec::from_iter((0..22050).map(|i| (i as f32 * 0.05).sin() * 0.5));
+        let one_shot = config
+            .apply_to_raw_samples(samples.clone().into(), 22050, 1)
+            .unwrap();
+        let chunks = Vec::from_iter(
+            samples
+                .chunks(1000)
+                .map(|c| Ok(AudioSamples::from(c.to_vec()))),
+        );
+        let (tx, rx) = flume::unbounded();
+        let mut sink = test_sink(&tx, None, 0);
+        RealtimeSpeechStream::process_rt_stream(
+            Box::new(chunks.into_iter()),
+            &mut sink,
+            Some(&config),
+            22050,
+            1,
+            0,
+        )
+        .unwrap();
+        drop(sink);
+        drop(tx);
+        let streamed: usize = rx.iter().map(|chunk| chunk.unwrap().len()).sum();
+        // sonic's rounding may differ by a sample or so
+        assert!(streamed.abs_diff(one_shot.len()) <= 4);
+    }
+
+    #[test]
+    fn test_chained_stream() {
+        let stream = |sample_rate, num_samples| -> AudioResultIterator<'static> {
+            let audio = Audio::new(vec![0.5; num_samples].into(), sample_rate, None);
+            Box::new(std::iter::once(Ok(audio)))
+        };
+        let chained = ChainedSpeechStream::new(vec![stream(22050, 10), stream(22050, 20)]);
+        let lens = Vec::from_iter(chained.map(|audio| audio.unwrap().len()));
+        assert_eq!(lens, vec![10, 20]);
+        let mut chained = ChainedSpeechStream::new(vec![stream(22050, 10), stream(16000, 20)]);
+        assert!(chained.next().unwrap().is_ok());
+        assert!(chained.next().unwrap().is_err());
+        assert!(chained.next().is_none());
+        let chained = ChainedSpeechStream::with_target_sample_rate(
+            vec![stream(22050, 22050), stream(16000, 16000)],
+            44100,
+        );
+        let lens = Vec::from_iter(chained.map(|audio| audio.unwrap().len()));
+        assert_eq!(lens, vec![44100, 44100]);
+    }
+
+    #[test]
+    fn test_chunk_pipeline_flush_drains_held_samples() {
+        let config = AudioOutputConfig::builder().rate(1.5).build();
+        let mut pipeline = ChunkPipeline::new(&config, 22050, 1);
+        let samples = Vec::from_iter((0..300).map(|i| (i as f32 * 0.05).sin() * 0.5));
+        // too short for sonic to analyze, so it's all held back until the flush
+        let processed = pipeline.process(samples.into());
+        let flushed = pipeline.flush().unwrap();
+        assert!(processed.is_empty());
+        assert!(!flushed.is_empty());
+    }
+
+    #[test]
+    fn test_highpass_removes_dc_across_chunks() {
+        let config = AudioOutputConfig::builder().highpass_hz(50.0).build();
+        let mut highpass = config.highpass(16000, 1).unwrap();
+        let mut first = vec![0.5; 2000];
+        let mut second = vec![0.5; 2000];
+        highpass.process(&mut first);
+        highpass.process(&mut second);
+        let mut whole = vec![0.5; 4000];
+        config.highpass(16000, 1).unwrap().process(&mut whole);
+        first.append(&mut second);
+        assert_eq!(first, whole);
+        assert!(whole[2000..].iter().all(|s| s.abs() < 0.01));
+    }
+
+    #[test]
+    fn test_noise_gate_holds_across_chunks() {
+        let config = AudioOutputConfig::builder().noise_gate(-40.0, 1).build();
+        // 1ms at 4kHz is a 4 frame hold
+        let mut gate = config.noise_gate(4000, 1).unwrap();
+        let mut first = AudioSamples::from(vec![0.5, 0.001, 0.001]);
+        let mut second = AudioSamples::from(vec![0.001, 0.001, 0.001, 0.5, 0.001]);
+        gate.process(&mut first);
+        gate.process(&mut second);
+        assert_eq!(first.into_vec(), vec![0.5, 0.001, 0.001]);
+        assert_eq!(second.into_vec(), vec![0.001, 0.001, 0.0, 0.5, 0.001]);
+    }
+
+    #[test]
+    fn test_rt_stream_reads_gain_per_chunk() {
+        let gain = AtomicU32::new(0.5f32.to_bits());
+        let (tx, rx) = flume::unbounded();
+        let mut sink = test_sink(&tx, Some(&gain), 0);
+        let chunks = vec![Ok(AudioSamples::from(vec![1.0; 4]))];
+        RealtimeSpeechStream::process_rt_stream(
+            Box::new(chunks.into_iter()),
+            &mut sink,
+            None,
+            22050,
+            1,
+            0,
+        )
+        .unwrap();
+        gain.store(0f32.to_bits(), Ordering::Relaxed);
+        let chunks = vec![Ok(AudioSamples::from(vec![1.0; 4]))];
+        RealtimeSpeechStream::process_rt_stream(
+            Box::new(chunks.into_iter()),
+            &mut sink,
+            None,
+            22050,
+            1,
+            0,
+        )
+        .unwrap();
+        drop(sink);
+        drop(tx);
+        let chunks = Vec::from_iter(rx.iter().map(|chunk| chunk.unwrap().into_vec()));
+        assert_eq!(chunks, vec![vec![0.5; 4], vec![0.0; 4]]);
+    }
+
+    fn test_sink<'a>(
+        tx: &'a Sender<PiperResult<AudioSamples>>,
+        gain: Option<&'a AtomicU32>,
+        crossfade_ms: u32,
+    ) -> ChunkSink<'a> {
+        ChunkSink {
+            tx,
+            limit: OutputLimit::new(None, &Audio::new(vec![].into(), 1000, None).info),
+            gain,
+            duck: DuckRamp::new(Arc::default(), 1000, 1),
+            crossfade: Crossfader::new(crossfade_ms, 1000, 1),
+            limiter: None,
+        }
+    }
+
+    #[test]
+    fn test_true_peak_limiter() {
+        // a quarter of the sample rate, sampled 45 degrees off its peaks, so every
+        // sample is at 0.9 but the waveform peaks at 0.9 * sqrt(2) between them
+        let input = Vec::from_iter([0.9, 0.9, -0.9, -0.9].into_iter().cycle().take(400));
+        let ceiling = 10f32.powf(-1.0 / 20.0);
+        let mut limiter = TruePeakLimiter::new(ceiling, 1000, 1);
+        let mut output = limiter.process(input[..150].to_vec().into());
+        output.merge(limiter.process(input[150..].to_vec().into()));
+        output.merge(limiter.flush());
+        let output = output.into_vec();
+        assert_eq!(output.len(), input.len());
+        let mut whole = TruePeakLimiter::new(ceiling, 1000, 1);
+        let mut expected = whole.process(input.clone().into());
+        expected.merge(whole.flush());
+        assert_eq!(output, expected.into_vec());
+        // past the attack, the interpolated peaks stay under the ceiling too
+        let true_peak = output[4..]
+            .windows(4)
+            .flat_map(|w| {
+                LIMITER_OVERSAMPLED_POINTS.map(|t| catmull_rom([w[0], w[1], w[2], w[3]], t))
+            })
+            .fold(0f32, |peak, s| peak.max(s.abs()));
+        assert!(true_peak <= ceiling * 1.001, "{}", true_peak);
+        // quiet input goes through untouched, only delayed
+        let mut limiter = TruePeakLimiter::new(ceiling, 1000, 2);
+        let quiet = vec![0.1, -0.1, 0.2, -0.2, 0.3, -0.3];
+        let mut output = limiter.process(quiet.clone().into());
+        assert_eq!(output.len(), 2);
+        output.merge(limiter.flush());
+        assert_eq!(output.into_vec(), quiet);
+    }
+
+    #[test]
+    fn test_duck_ramps_between_chunks() {
+        let control = Arc::new(DuckControl::default());
+        let mut ramp = DuckRamp::new(Arc::clone(&control), 1000, 2);
+        let mut chunk = AudioSamples::from(vec![1.0; 4]);
+        ramp.apply(&mut chunk);
+        assert_eq!(chunk.into_vec(), vec![1.0; 4]);
+        control.ramp_ms.store(4, Ordering::Relaxed);
+        control.target_gain.store(0f32.to_bits(), Ordering::Release);
+        let mut chunk = AudioSamples::from(vec![1.0; 4]);
+        ramp.apply(&mut chunk);
+        // both channels of a frame share its gain
+        assert_eq!(chunk.into_vec(), vec![0.75, 0.75, 0.5, 0.5]);
+        let mut chunk = AudioSamples::from(vec![1.0; 6]);
+        ramp.apply(&mut chunk);
+        assert_eq!(chunk.into_vec(), vec![0.25, 0.25, 0.0, 0.0, 0.0, 0.0]);
+        control.ramp_ms.store(0, Ordering::Relaxed);
+        control.target_gain.store(1f32.to_bits(), Ordering::Release);
+        let mut chunk = AudioSamples::from(vec![1.0; 2]);
+        ramp.apply(&mut chunk);
+        assert_eq!(chunk.into_vec(), vec![1.0; 2]);
+    }
+
+    #[test]
+    fn test_rt_stream_crossfades_sentences() {
+        let (tx, rx) = flume::unbounded();
+        let mut sink = test_sink(&tx, None, 2);
+        for sentence in [vec![1.0; 6], vec![0.0; 4]] {
+            let chunks = Vec::from_iter(
+                sentence
+                    .chunks(3)
+                    .map(|c| Ok(AudioSamples::from(c.to_vec()))),
+            );
+            RealtimeSpeechStream::process_rt_stream(
+                Box::new(chunks.into_iter()),
+                &mut sink,
+                None,
+                1000,
+                1,
+                0,
+            )
+            .unwrap();
+        }
+        sink.finish().unwrap();
+        drop(sink);
+        drop(tx);
+        let samples = Vec::from_iter(rx.iter().flat_map(|chunk| chunk.unwrap().into_vec()));
+        // the first sentence's last 2 samples overlap the second's first 2
+        assert_eq!(samples, vec![1.0, 1.0, 1.0, 1.0, 0.75, 0.25, 0.0, 0.0]);
+    }
+
+    #[cfg(feature = "ringbuf")]
+    #[test]
+    fn test_pump_into_ring_buffer() {
+        let (tx, rx) = flume::unbounded();
+        let stream = RealtimeSpeechStream {
+            receiver: rx,
+            info: Audio::new(vec![].into(), 22050, None).info,
+            truncated: Arc::new(AtomicBool::new(false)),
+            duck: Arc::default(),
+        };
+        let expected = Vec::from_iter((0..200).map(|i| i as f32));
+        tx.send(Ok(expected[..150].to_vec().into())).unwrap();
+        tx.send(Ok(expected[150..].to_vec().into())).unwrap();
+        drop(tx);
+        let (producer, mut consumer) = rtrb::RingBuffer::new(64);
+        let pump = stream.pump_into(producer, 0).unwrap();
+        let mut received = Vec::new();
+        while received.len() < expected.len() {
+            match consumer.pop() {
+                Ok(sample) => received.push(sample),
+                Err(_) => std::thread::yield_now(),
+            }
+        }
+        assert!(pump.join().unwrap().is_ok());
+        assert_eq!(received, expected);
+    }
+
+    /// A synthesizer over `model`, along with the model to check it against
+    fn mock_synth(model: MockPiperModel) -> (Arc<MockPiperModel>, PiperSpeechSynthesizer) {
+        let model = Arc::new(model);
+        let synth = PiperSpeechSynthesizer::new(model.clone()).unwrap();
+        (model, synth)
+    }
+
+    #[test]
+    fn test_duration_progress_ends_at_one() {
+        let (_, synth) = mock_synth(MockPiperModel::new());
+        let mut fractions = Vec::new();
+        let audio = synth
+            .synthesize_with_duration_progress("Hi. Hello there.".to_string(), None, |p| {
+                fractions.push(p.fraction())
+            })
+            .unwrap();
+        assert!(!audio.is_empty());
+        assert_eq!(fractions.len(), 3);
+        assert!(fractions.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(fractions.last(), Some(&1.0));
+    }
+
+    #[test]
+    fn test_synthesize_channel() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let (tx, rx) = flume::unbounded();
+        let stream = synth.synthesize_channel(rx, None, 1, 0);
+        tx.send("Hi.".to_string()).unwrap();
+        tx.send("Hello.".to_string()).unwrap();
+        drop(tx);
+        let audio = stream.collect_all().unwrap();
+        assert_eq!(audio.len(), model.num_samples_for("Hi.Hello.".len()));
+    }
+
+    #[test]
+    fn test_synthesize_raw_and_processed() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let config = AudioOutputConfig::builder()
+            .appended_silence_ms(100)
+            .build();
+        let (raw, processed) = synth
+            .synthesize_raw_and_processed("Hi. Hello.".to_string(), Some(config))
+            .unwrap();
+        assert_eq!(raw.len(), model.num_samples_for("Hi.Hello.".len()));
+        assert!(processed.len() > raw.len());
+    }
+
+    #[test]
+    fn test_raw_and_processed_matches_synthesize() {
+        let speakers = HashMap::from([(0, "a".to_string()), (1, "b".to_string())]);
+        let (_, mut synth) = mock_synth(MockPiperModel::new().with_speakers(speakers));
+        synth.set_parse_speaker_tags(true);
+        synth.set_speaker_config(1, AudioOutputConfig::builder().volume(0.5).build());
+        let text = "Hi <emphasis>there</emphasis>. [spk:1] Hello.".to_string();
+        let config = AudioOutputConfig::builder().rate(1.2).build();
+        let (_, processed) = synth
+            .synthesize_raw_and_processed(text.clone(), Some(config.clone()))
+            .unwrap();
+        let expected = synth.synthesize(text, Some(config)).unwrap();
+        assert_eq!(processed.samples.as_slice(), expected.samples.as_slice());
+    }
+
+    #[test]
+    fn test_synthesize_verbose() {
+        let (_, synth) = mock_synth(MockPiperModel::new().with_max_phoneme_length(8));
+        let (audio, warnings) = synth
+            .synthesize_verbose("Hi. A long sentence.".to_string(), None)
+            .unwrap();
+        assert!(!audio.is_empty());
+        assert_eq!(
+            warnings,
+            vec![SynthesisWarning {
+                sentence_index: 1,
+                kind: SynthesisWarningKind::SentenceTooLong {
+                    num_phonemes: 16,
+                    max_length: 8
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_script_policy() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new().with_language("en-us"));
+        let text = "Hi 你好 there.".to_string();
+        synth.set_unsupported_script_policy(Some(UnsupportedScriptPolicy::Drop));
+        let (audio, warnings) = synth.synthesize_verbose(text.clone(), None).unwrap();
+        // the run is replaced with a space: "Hi   there."
+        assert_eq!(audio.len(), model.num_samples_for(11));
+        assert_eq!(
+            warnings[0].kind,
+            SynthesisWarningKind::UnsupportedScript {
+                text: "你好".to_string(),
+                replacement: None
+            }
+        );
+        synth.set_unsupported_script_policy(Some(UnsupportedScriptPolicy::Transliterate(
+            Arc::new(|run: &str| (run == "你好").then(|| "ni hao".to_string())),
+        )));
+        let (audio, _) = synth.synthesize_verbose(text.clone(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(16));
+        synth.set_unsupported_script_policy(Some(UnsupportedScriptPolicy::Error));
+        assert!(synth.synthesize(text, None).is_err());
+    }
+
+    #[test]
+    fn test_prepared_utterance() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let mut utterance = synth.prepare("Hi. Hello.".to_string()).unwrap();
+        assert_eq!(utterance.num_sentences(), 2);
+        assert_eq!(utterance.phonemes(1), Some("Hello."));
+        utterance
+            .set_phonemes(1, "Hello there.".to_string())
+            .unwrap();
+        assert!(utterance.set_phonemes(2, String::new()).is_err());
+        let audio = synth.synthesize_prepared(&utterance, None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for("Hi.Hello there.".len()));
+    }
+
+    #[test]
+    fn test_synthesize_segments() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let output_config = AudioOutputConfig::builder()
+            .appended_silence_ms(100)
+            .build();
+        let segments = synth
+            .synthesize_segments("Hi. Hello there.".to_string(), Some(output_config))
+            .unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].text, "Hi.");
+        assert_eq!(segments[1].text, "Hello there.");
+        assert_eq!(segments[0].start_offset, Duration::ZERO);
+        let first_len = model.num_samples_for("Hi.".len()) + 2205;
+        assert_eq!(segments[0].audio.len(), first_len);
+        assert_eq!(
+            segments[1].start_offset,
+            Duration::from_secs_f64(first_len as f64 / 22050.0)
+        );
+    }
+
+    #[test]
+    fn test_parallel_stream_order_and_drop() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "A long first sentence. Hi. Middle one.".to_string();
+        let lengths = Vec::from_iter(
+            synth
+                .synthesize_parallel(text.clone(), None)
+                .unwrap()
+                .map(|audio| audio.unwrap().len()),
+        );
+        let expected = Vec::from_iter(
+            ["A long first sentence.", "Hi.", "Middle one."]
+                .map(|sentence| model.num_samples_for(sentence.len())),
+        );
+        assert_eq!(lengths, expected);
+        // dropping a stream early must not block or affect later streams
+        let mut stream = synth.synthesize_parallel(text.clone(), None).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        drop(stream);
+        assert_eq!(synth.synthesize_parallel(text, None).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn test_parallel_stream_drop_stops_scheduling() {
+        let model = MockPiperModel::new().with_synthesis_delay(Duration::from_millis(10));
+        let (model, mut synth) = mock_synth(model);
+        synth
+            .set_thread_config(&SynthesisThreadConfig {
+                num_threads: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        let text = "Hi. ".repeat(20);
+        let mut stream = synth.synthesize_parallel(text, None).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        drop(stream);
+        // long enough for all 20 sentences, had they still been scheduled
+        std::thread::sleep(Duration::from_millis(300));
+        let num_synthesized = model.num_synthesized();
+        assert!(
+            num_synthesized < 20,
+            "{} sentences synthesized",
+            num_synthesized
+        );
+    }
+
+    #[test]
+    fn test_parallel_deadline_bounds_the_call() {
+        let model = MockPiperModel::new().with_synthesis_delay(Duration::from_millis(100));
+        let (_, mut synth) = mock_synth(model);
+        synth
+            .set_thread_config(&SynthesisThreadConfig {
+                num_threads: Some(1),
+                ..Default::default()
+            })
+            .unwrap();
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(250);
+        let text = "Hi. ".repeat(10);
+        let stream = synth
+            .synthesize_parallel_deadline(text, None, deadline)
+            .unwrap();
+        // returns without waiting for the sentence in flight at the deadline
+        assert!(start.elapsed() < Duration::from_millis(300));
+        assert!(stream.was_truncated());
+        let results = Vec::from_iter(stream);
+        assert!(!results.is_empty() && results.len() < 10);
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn test_parallel_stream_sentence_panic() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        model.set_panic_synthesis(true);
+        let results = Vec::from_iter(
+            synth
+                .synthesize_parallel("Hi. Yo.".to_string(), None)
+                .unwrap(),
+        );
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_err()));
+        model.set_panic_synthesis(false);
+        assert_eq!(
+            synth
+                .synthesize_parallel("Hi. Yo.".to_string(), None)
+                .unwrap()
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_timed_streams() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi. Hello.".to_string();
+        let lazy = synth.synthesize_lazy(text.clone(), None).unwrap();
+        let parallel = synth.synthesize_parallel(text, None).unwrap();
+        for results in [
+            Vec::from_iter(lazy.timed()),
+            Vec::from_iter(parallel.timed()),
+        ] {
+            let lengths = Vec::from_iter(results.into_iter().map(|result| {
+                let (audio, elapsed) = result.unwrap();
+                assert!(elapsed > Duration::ZERO);
+                audio.len()
+            }));
+            assert_eq!(
+                lengths,
+                vec![model.num_samples_for(3), model.num_samples_for(6)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_two_phase_streaming() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let stream_chunk_lens = |synth: &PiperSpeechSynthesizer| {
+            Vec::from_iter(
+                synth
+                    .synthesize_streamed_two_phase("Hi. Hello.".to_string(), None, 1, 2, 0)
+                    .unwrap()
+                    .map(|chunk| chunk.unwrap().len()),
+            )
+        };
+        let chunk_lens = stream_chunk_lens(&synth);
+        // only the stream's first chunk is small
+        let (first, steady) = (SAMPLES_PER_FRAME, 2 * SAMPLES_PER_FRAME);
+        let (hi_len, hello_len) = (model.num_samples_for(3), model.num_samples_for(6));
+        assert_eq!(
+            chunk_lens,
+            vec![
+                first,
+                hi_len - first,
+                steady,
+                steady,
+                hello_len - 2 * steady
+            ]
+        );
+        // a synthesizer used as a model keeps the schedule
+        let nested = PiperSpeechSynthesizer::new(Arc::new(synth)).unwrap();
+        assert_eq!(stream_chunk_lens(&nested), chunk_lens);
+    }
+
+    #[test]
+    fn test_streamed_by_duration() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let chunk_lens = Vec::from_iter(
+            synth
+                .synthesize_streamed_by_duration("Hello.".to_string(), None, 20, 0)
+                .unwrap()
+                .map(|chunk| chunk.unwrap().len()),
+        );
+        // 20 ms at 22050 Hz is 441 samples, rounded up to two frames
+        let chunk_len = 2 * SAMPLES_PER_FRAME;
+        assert_eq!(
+            chunk_lens,
+            vec![
+                chunk_len,
+                chunk_len,
+                model.num_samples_for(6) - 2 * chunk_len
+            ]
+        );
+    }
+
+    #[test]
+    fn test_synthesize_preview() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "One. Two. Three.".to_string();
+        let preview = synth.synthesize_preview(text.clone(), None, 2).unwrap();
+        assert_eq!(preview.len(), model.num_samples_for(8));
+        let whole = synth.synthesize_preview(text, None, 10).unwrap();
+        assert_eq!(whole.len(), model.num_samples_for(14));
+    }
+
+    #[test]
+    fn test_synthesize_to_wav_bytes() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let num_samples = model.num_samples_for(3);
+        for (encoding, format_tag, sample_rate, data_len) in [
+            (WavEncoding::PcmI16, 1, 22050, num_samples * 2),
+            (WavEncoding::Float32, 3, 22050, num_samples * 4),
+            (WavEncoding::MuLaw, 7, 8000, num_samples * 8000 / 22050),
+            (WavEncoding::ALaw, 6, 8000, num_samples * 8000 / 22050),
+        ] {
+            let wav = synth
+                .synthesize_to_wav_bytes("Hi.".to_string(), None, encoding)
+                .unwrap();
+            let fmt_pos = wav.windows(4).position(|w| w == b"fmt ").unwrap() + 8;
+            assert_eq!(
+                u16::from_le_bytes([wav[fmt_pos], wav[fmt_pos + 1]]),
+                format_tag
+            );
+            let rate = u32::from_le_bytes(wav[fmt_pos + 4..fmt_pos + 8].try_into().unwrap());
+            assert_eq!(rate, sample_rate);
+            let data_pos = wav.windows(4).position(|w| w == b"data").unwrap();
+            let len = u32::from_le_bytes(wav[data_pos + 4..data_pos + 8].try_into().unwrap());
+            assert_eq!(len as usize, data_len);
+        }
+    }
+
+    #[test]
+    fn test_estimate_peak_memory() {
+        let (_, synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi. Hello there.";
+        let duration = synth.estimate_duration(text.to_string(), None).unwrap();
+        let num_samples = (duration.as_secs_f64() * 22050.0).ceil() as usize;
+        let estimate = synth.estimate_peak_memory(text, None).unwrap();
+        assert_eq!(estimate, num_samples * 12 * 5 / 4);
+        assert_eq!(synth.estimate_peak_memory("", None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_synthesize_hybrid() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi. Hello. Hello there.".to_string();
+        let lengths = Vec::from_iter(
+            synth
+                .synthesize_hybrid(text, None)
+                .unwrap()
+                .map(|audio| audio.unwrap().len()),
+        );
+        assert_eq!(lengths, [3, 6, 12].map(|n| model.num_samples_for(n)));
+        assert_eq!(
+            synth
+                .synthesize_hybrid(String::new(), None)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_streamed_with_phonemes() {
+        let (_, synth) =
+            mock_synth(MockPiperModel::new().with_samples_per_phoneme(SAMPLES_PER_FRAME / 2));
+        let chunks = Vec::from_iter(
+            synth
+                .synthesize_streamed_with_phonemes("Hi there. Yo.".to_string(), 1, 0)
+                .unwrap()
+                .map(|chunk| chunk.unwrap()),
+        );
+        let labels = Vec::from_iter(chunks.iter().map(|(_, phonemes)| phonemes.as_str()));
+        assert_eq!(labels, ["Hi", " t", "he", "re", ".", "Yo", "."]);
+        assert!(chunks
+            .iter()
+            .all(|(samples, _)| samples.len() <= SAMPLES_PER_FRAME));
+        // a synthesizer used as a model streams its model's phonemes
+        let nested = PiperSpeechSynthesizer::new(Arc::new(synth)).unwrap();
+        let num_chunks = nested
+            .synthesize_streamed_with_phonemes("Hi there. Yo.".to_string(), 1, 0)
+            .unwrap()
+            .map(Result::unwrap)
+            .count();
+        assert_eq!(num_chunks, chunks.len());
+    }
+
+    #[test]
+    fn test_streamed_with_phonemes_panic() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        model.set_panic_synthesis(true);
+        let results = Vec::from_iter(
+            synth
+                .synthesize_streamed_with_phonemes("Hi. Yo.".to_string(), 1, 0)
+                .unwrap(),
+        );
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        let speakers = HashMap::from([(2, "other".to_string())]);
+        let (_, mut synth) = mock_synth(MockPiperModel::new().with_speakers(speakers));
+        let text = "Hi. [spk:2] Hello there. Bye.";
+        let sentences = synth.split_sentences(text).unwrap();
+        assert_eq!(sentences, ["Hi.", "[spk:2] Hello there.", "Bye."]);
+        synth.set_parse_speaker_tags(true);
+        let sentences = synth.split_sentences(text).unwrap();
+        assert_eq!(sentences, ["Hi.", "[spk:2] Hello there.", "[spk:2] Bye."]);
+        let joined = synth.synthesize(text.to_string(), None).unwrap();
+        let parts = Vec::from_iter(
+            sentences
+                .into_iter()
+                .map(|sentence| synth.synthesize(sentence, None).unwrap()),
+        );
+        assert_eq!(Audio::concat(parts).unwrap().into_vec(), joined.into_vec());
+    }
+
+    #[test]
+    fn test_speaker_configs() {
+        let speakers = HashMap::from([(2, "other".to_string())]);
+        let (model, mut synth) = mock_synth(MockPiperModel::new().with_speakers(speakers));
+        synth.set_parse_speaker_tags(true);
+        let silence = |ms| Some(AudioOutputConfig::builder().appended_silence_ms(ms).build());
+        synth.set_speaker_config(2, silence(100).unwrap());
+        assert!(synth.speaker_config(2).is_some());
+        let text = "Hi. [spk:2] Hello.".to_string();
+        let audio = synth.synthesize(text.clone(), silence(50)).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(9) + 1102 + 2205);
+        assert!(synth.clear_speaker_config(2).is_some());
+        let audio = synth.synthesize(text, silence(50)).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(9) + 2 * 1102);
+    }
+
+    #[test]
+    fn test_pause_markers() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi... Yo <p200>there <b>now.".to_string();
+        let unparsed = synth.synthesize(text.clone(), None).unwrap();
+        assert_eq!(unparsed.len(), model.num_samples_for(27));
+        synth.set_pause_markers(Some(UnknownMarkerPolicy::Keep));
+        let audio = synth.synthesize(text.clone(), None).unwrap();
+        // "Hi", "Yo" and "there <b>now.", then 500 and 200 ms of silence
+        assert_eq!(audio.len(), model.num_samples_for(17) + 11025 + 4410);
+        let pause = &audio.samples.as_slice()[model.num_samples_for(2)..][..11025];
+        assert!(pause.iter().all(|s| *s == 0.0));
+        // the streaming and per-config paths keep the pauses too
+        let streamed = Vec::from_iter(
+            synth
+                .synthesize_streamed(text.clone(), None, 4, 0)
+                .unwrap()
+                .flat_map(|chunk| chunk.unwrap().into_vec()),
+        );
+        assert_eq!(streamed, audio.samples.as_slice());
+        let with_phonemes: usize = synth
+            .synthesize_streamed_with_phonemes(text.clone(), 4, 0)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().0.len())
+            .sum();
+        assert_eq!(with_phonemes, audio.len());
+        let synth_config = PiperSynthesisConfig {
+            speaker: None,
+            noise_scale: 0.667,
+            length_scale: 1.0,
+            noise_w: 0.8,
+        };
+        let with_config: usize = synth
+            .synthesize_with_config(text.clone(), None, &synth_config)
+            .unwrap()
+            .map(|audio| audio.unwrap().len())
+            .sum();
+        assert_eq!(with_config, audio.len());
+        synth.set_pause_markers(Some(UnknownMarkerPolicy::Strip));
+        let audio = synth.synthesize(text, None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(14) + 11025 + 4410);
+    }
+
+    #[test]
+    fn test_digit_mode() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi 42 <digits>007</digits>.".to_string();
+        let audio = synth.synthesize(text.clone(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for("Hi 42 0 0 7.".len()));
+        synth.set_digit_mode(DigitMode::Digits);
+        let audio = synth.synthesize(text, None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for("Hi 4 2 0 0 7.".len()));
+    }
+
+    #[test]
+    fn test_shutdown_thread_pool() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        synth.shutdown_thread_pool();
+        synth
+            .set_thread_config(&SynthesisThreadConfig {
+                num_threads: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        let audio = synth.synthesize("Hi. Yo.".to_string(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(6));
+        // returns once both threads have exited
+        synth.shutdown_thread_pool();
+        let audio = synth.synthesize("Hi. Yo.".to_string(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(6));
+    }
+
+    #[test]
+    fn test_synthesize_profiled() {
+        let (_, synth) = mock_synth(MockPiperModel::new());
+        let config = AudioOutputConfig::builder().appended_silence_ms(50).build();
+        let (audio, profile) = synth
+            .synthesize_profiled("Hi. Hello.".to_string(), Some(config.clone()))
+            .unwrap();
+        let expected = synth
+            .synthesize("Hi. Hello.".to_string(), Some(config))
+            .unwrap();
+        assert_eq!(audio.samples.as_slice(), expected.samples.as_slice());
+        assert!(profile.total >= profile.phonemize);
+    }
+
+    #[test]
+    fn test_emphasis() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        let slower = EmphasisProsody {
+            volume: 1.0,
+            pitch: 1.0,
+            rate: 0.5,
+        };
+        synth.set_emphasis_prosody(EmphasisLevel::Strong, slower);
+        let text = "Hi <emphasis level=\"strong\">there</emphasis>.".to_string();
+        let audio = synth.synthesize(text, None).unwrap();
+        // "Hi" and "." as they are, "there" at about half the rate
+        let emphasized_len = audio.len() - model.num_samples_for(3);
+        assert!(emphasized_len > model.num_samples_for(5) * 3 / 2);
+        let text = "Hi <emphasis level=\"none\">there</emphasis>.".to_string();
+        let audio = synth.synthesize(text, None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(8));
+    }
+
+    #[test]
+    fn test_with_default_config() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let silence = |ms| AudioOutputConfig::builder().appended_silence_ms(ms).build();
+        let narrator = synth.with_default_config(silence(50));
+        let text = "Hi. Yo.".to_string();
+        let audio = narrator.synthesize(text.clone(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(6) + 2 * 1102);
+        let audio = narrator
+            .synthesize(text.clone(), Some(silence(100)))
+            .unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(6) + 2 * 2205);
+        let audio = synth.synthesize(text, None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(6));
+    }
+
+    #[test]
+    fn test_oov_spell_out() {
+        let (model, mut synth) =
+            mock_synth(MockPiperModel::new().with_unpronounceable_words(vec!["XKCD".to_string()]));
+        let text = "Hi. Buy XKCD now.".to_string();
+        assert!(synth.synthesize(text.clone(), None).is_err());
+        synth.set_oov_policy(OovPolicy::SpellOut);
+        assert_eq!(synth.spell_out_phonemes("XKCD").unwrap(), "X K C D");
+        let (audio, warnings) = synth.synthesize_verbose(text, None).unwrap();
+        assert_eq!(
+            audio.len(),
+            model.num_samples_for("Hi.Buy X K C D now.".len())
+        );
+        assert_eq!(
+            warnings,
+            vec![SynthesisWarning {
+                sentence_index: 1,
+                kind: SynthesisWarningKind::SpelledOut {
+                    word: "XKCD".to_string()
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_empty_input_policy() {
+        let (_, mut synth) = mock_synth(MockPiperModel::new());
+        let text = "  ".to_string();
+        assert_eq!(
+            synth.synthesize_lazy(text.clone(), None).unwrap().count(),
+            0
+        );
+        let path = std::env::temp_dir().join(format!("piper-empty-{}.wav", std::process::id()));
+        assert!(synth.synthesize_to_file(&path, text.clone(), None).is_err());
+        synth.set_empty_input_policy(EmptyInputPolicy::Error);
+        assert!(synth.synthesize_parallel(text.clone(), None).is_err());
+        synth.set_empty_input_policy(EmptyInputPolicy::SilentClip { ms: 100 });
+        let audio = synth.synthesize(text.clone(), None).unwrap();
+        assert_eq!(audio.len(), 2205);
+        assert!(audio.samples.as_slice().iter().all(|s| *s == 0.0));
+        synth.synthesize_to_file(&path, text, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_reverse_sentences() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        synth.set_reverse_sentences(true);
+        let text = "One. Three more.".to_string();
+        let parts = Vec::from_iter(
+            synth
+                .synthesize_lazy(text.clone(), None)
+                .unwrap()
+                .map(|audio| audio.unwrap().len()),
+        );
+        assert_eq!(
+            parts,
+            vec![model.num_samples_for(11), model.num_samples_for(4)]
+        );
+        let segments = synth.synthesize_segments(text, None).unwrap();
+        assert_eq!(segments[0].text, "Three more.");
+    }
+
+    #[test]
+    fn test_synthesize_to_f64() {
+        let (_, synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi there.".to_string();
+        let config = AudioOutputConfig::builder().volume(0.8).build();
+        let gained = AudioOutputConfig {
+            channel_gains: Some(vec![0.3]),
+            ..config.clone()
+        };
+        let samples = synth
+            .synthesize_to_f64(text.clone(), Some(gained.clone()))
+            .unwrap();
+        // the gain is applied after widening, with no rounding to `f32` after it
+        let ungained = synth.synthesize(text.clone(), Some(config)).unwrap();
+        let expected = Vec::from_iter(
+            ungained
+                .samples
+                .into_f64_vec()
+                .into_iter()
+                .map(|sample| sample * f64::from(0.3f32)),
+        );
+        assert_eq!(samples, expected);
+        let f32_audio = synth
+            .synthesize(text.clone(), Some(gained.clone()))
+            .unwrap();
+        assert_eq!(samples.len(), f32_audio.len());
+        assert!(samples
+            .iter()
+            .zip(f32_audio.samples.as_slice())
+            .all(|(a, b)| (a - f64::from(*b)).abs() < 1e-6));
+        let bad_gains = AudioOutputConfig {
+            channel_gains: Some(vec![1.0, 1.0]),
+            ..gained
+        };
+        assert!(synth.synthesize_to_f64(text, Some(bad_gains)).is_err());
+    }
+
+    #[test]
+    fn test_speaker_embedding() {
+        let speakers = HashMap::from([(0, "a".to_string()), (1, "b".to_string())]);
+        let (model, synth) = mock_synth(MockPiperModel::new().with_speakers(speakers));
+        let embedding = synth.get_speaker_embedding(1).unwrap();
+        assert_eq!(embedding, vec![1.0]);
+        assert!(synth.get_speaker_embedding(7).is_err());
+        let audio = synth
+            .synthesize_with_embedding("Hi there.".to_string(), &embedding, None)
+            .unwrap();
+        let expected = model
+            .speak_one_sentence_as("Hi there.".to_string(), 1)
+            .unwrap();
+        assert_eq!(audio.samples.as_slice(), expected.samples.as_slice());
+        assert!(synth
+            .synthesize_with_embedding("Hi there.".to_string(), &[1.0, 2.0], None)
+            .is_err());
+        // pauses and the output limit apply as they do to `synthesize`
+        let mut synth = synth;
+        synth.set_pause_markers(Some(UnknownMarkerPolicy::Keep));
+        let text = "Hi... there.".to_string();
+        let audio = synth
+            .synthesize_with_embedding(text.clone(), &embedding, None)
+            .unwrap();
+        assert_eq!(audio.len(), model.num_samples_for(8) + 11025);
+        synth.set_max_output_duration(Some(Duration::from_millis(30)));
+        let audio = synth
+            .synthesize_with_embedding(text, &embedding, None)
+            .unwrap();
+        assert_eq!(audio.len(), 661);
+    }
+
+    #[test]
+    fn test_throttle_to_realtime() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let start = std::time::Instant::now();
+        let chunks = Vec::from_iter(
+            synth
+                .synthesize_streamed("Hello there.".to_string(), None, 4, 0)
+                .unwrap()
+                .throttle_to_realtime()
+                .map(Result::unwrap),
+        );
+        let elapsed = start.elapsed();
+        let num_samples: usize = chunks.iter().map(|samples| samples.len()).sum();
+        assert_eq!(num_samples, model.num_samples_for(12));
+        // the last chunk is due once the others have played
+        let played = num_samples - chunks.last().unwrap().len();
+        assert!(elapsed >= Duration::from_secs_f64(played as f64 / 22050.0));
+    }
+
+    #[test]
+    fn test_stream_metering() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let chunks = Vec::from_iter(
+            synth
+                .synthesize_streamed("Hello there.".to_string(), None, 4, 0)
+                .unwrap()
+                .with_metering()
+                .map(Result::unwrap),
+        );
+        assert!(chunks.len() > 1);
+        let num_samples: usize = chunks.iter().map(|(samples, _)| samples.len()).sum();
+        assert_eq!(num_samples, model.num_samples_for(12));
+        for (samples, levels) in chunks {
+            assert_eq!(levels.peak, samples.stats().peak);
+            assert!(levels.peak <= 0.5 && levels.rms > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_phoneme_duration_report() {
+        let (_, synth) = mock_synth(MockPiperModel::new());
+        let report = synth.phoneme_duration_report("Hi. Yo.").unwrap();
+        let phonemes = String::from_iter(report.iter().map(|d| d.phoneme));
+        assert_eq!(phonemes, "Hi.Yo.");
+        assert_eq!(report[3].sentence_index, 1);
+        assert!((report[0].duration_ms - 220.0 * 1000.0 / 22050.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_synthesize_to_stream() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let mut out = Vec::new();
+        synth
+            .synthesize_to_stream(&mut out, "Hi. Hello.".to_string(), None, 1, 0)
+            .unwrap();
+        assert_eq!(&out[..4], b"RIFF");
+        assert_eq!(out[4..8], [0xFF; 4]);
+        assert_eq!(&out[36..40], b"data");
+        assert_eq!(out[40..44], [0xFF; 4]);
+        assert_eq!(out.len() - 44, model.num_samples_for(9) * 2);
+
+        struct Closed;
+        impl std::io::Write for Closed {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let result = synth.synthesize_to_stream(Closed, "Hi.".to_string(), None, 1, 0);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_synthesize_sequential() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "Hi. Hello there.".to_string();
+        let sequential = Vec::from_iter(
+            synth
+                .synthesize_sequential(text.clone(), None)
+                .unwrap()
+                .map(|audio| audio.unwrap().into_vec()),
+        );
+        let parallel = Vec::from_iter(
+            synth
+                .synthesize_parallel(text, None)
+                .unwrap()
+                .map(|audio| audio.unwrap().into_vec()),
+        );
+        assert_eq!(sequential, parallel);
+        assert_eq!(sequential[1].len(), model.num_samples_for(12));
+    }
+
+    #[test]
+    fn test_synthesize_with_word_durations() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let text = "I said NO. Fine.".to_string();
+        let audio = synth
+            .synthesize_with_word_durations(text.clone(), vec![1.0, 1.0, 3.0], None)
+            .unwrap();
+        // "NO" is spoken three times as long, "Fine" has no scale
+        assert_eq!(audio.len(), model.num_samples_for(10 + 4 + 5));
+        let plain = synth
+            .synthesize_with_word_durations(text, Vec::new(), None)
+            .unwrap();
+        assert_eq!(plain.len(), model.num_samples_for(10 + 5));
+        assert!(model
+            .speak_with_durations("ab".to_string(), vec![1.0])
+            .is_err());
+    }
+
+    #[test]
+    fn test_word_durations_keep_pauses() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        synth.set_pause_markers(Some(UnknownMarkerPolicy::Keep));
+        let text = "I said... <emphasis>NO</emphasis>. Fine.".to_string();
+        let expected = synth.synthesize(text.clone(), None).unwrap();
+        for word_scales in [Vec::new(), vec![1.0; 4]] {
+            let audio = synth
+                .synthesize_with_word_durations(text.clone(), word_scales, None)
+                .unwrap();
+            assert_eq!(audio.samples.as_slice(), expected.samples.as_slice());
+        }
+        // the pause after "said" stays, however long the words are spoken
+        let scaled = synth
+            .synthesize_with_word_durations(text, vec![2.0, 1.0, 1.0, 1.0], None)
+            .unwrap();
+        assert_eq!(scaled.len(), expected.len() + model.num_samples_for(1));
+    }
+
+    #[test]
+    fn test_phrase_split_threshold() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        let text = "one two, three four, five six".to_string();
+        let whole = synth.synthesize(text.clone(), None).unwrap();
+        assert_eq!(whole.len(), model.num_samples_for(29));
+        synth.set_phrase_split_threshold(Some(12));
+        let split = synth.synthesize(text, None).unwrap();
+        // three phrases without the spaces between them, joined by two 5 ms crossfades
+        let fade_len = 5 * model.audio_output_info().sample_rate / 1000;
+        assert_eq!(split.len(), model.num_samples_for(27) - 2 * fade_len);
+    }
+
+    #[test]
+    fn test_synthesize_to_file_with_cues() {
+        let (model, synth) = mock_synth(MockPiperModel::new());
+        let path = std::env::temp_dir().join(format!("piper-cues-{}.wav", std::process::id()));
+        let text = "Hello there. Bye.".to_string();
+        synth
+            .synthesize_to_file_with_cues(&path, text, None)
+            .unwrap();
+        let out = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        let cue_pos = out.windows(4).position(|w| w == b"cue ").unwrap();
+        assert_eq!(u32_at(cue_pos + 8), 3);
+        let offsets = Vec::from_iter((0..3).map(|i| u32_at(cue_pos + 12 + i * 24 + 4) as usize));
+        assert_eq!(offsets, [0, 6, 12].map(|n| model.num_samples_for(n)));
+        let labels = Vec::from_iter(
+            out.windows(4)
+                .enumerate()
+                .filter(|(_, w)| w == b"labl")
+                .map(|(pos, _)| {
+                    let len = u32_at(pos + 4) as usize;
+                    String::from_utf8(out[pos + 12..pos + 8 + len - 1].to_vec()).unwrap()
+                }),
+        );
+        assert_eq!(labels, ["Hello", "there", "Bye"]);
+    }
+
+    #[test]
+    fn test_cues_follow_pauses_and_emphasis() {
+        let (model, mut synth) = mock_synth(MockPiperModel::new());
+        synth.set_pause_markers(Some(UnknownMarkerPolicy::Keep));
+        let path =
+            std::env::temp_dir().join(format!("piper-cues-pause-{}.wav", std::process::id()));
+        let text = "Hello there... <emphasis>Bye</emphasis>. Now.".to_string();
+        synth
+            .synthesize_to_file_with_cues(&path, text.clone(), None)
+            .unwrap();
+        let out = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        let cue_pos = out.windows(4).position(|w| w == b"cue ").unwrap();
+        assert_eq!(u32_at(cue_pos + 8), 4);
+        let offsets = Vec::from_iter((0..4).map(|i| u32_at(cue_pos + 12 + i * 24 + 4) as usize));
+        // every cue starts where its sentence starts in the written audio
+        let parts = Vec::from_iter(
+            synth
+                .synthesize_parallel(text, None)
+                .unwrap()
+                .map(|audio| audio.unwrap().len()),
+        );
+        // "Hello there", "Bye", the "." after the emphasis span, and "Now."
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], model.num_samples_for(11) + 11025);
+        let starts = Vec::from_iter(parts.iter().scan(0, |start, len| {
+            let sentence_start = *start;
+            *start += len;
+            Some(sentence_start)
+        }));
+        assert_eq!(offsets, [0, model.num_samples_for(6), starts[1], starts[3]]);
     }
 }
@@ -0,0 +1,77 @@
+use std::fmt::Write;
+
+pub(crate) struct Interval {
+    pub start_s: f32,
+    pub end_s: f32,
+    pub text: String,
+}
+
+pub(crate) struct IntervalTier {
+    pub name: &'static str,
+    pub intervals: Vec<Interval>,
+}
+
+/// Render `tiers` as a Praat TextGrid (long text format) spanning `0..duration_s`.
+/// Gaps between the intervals of a tier are filled with empty intervals.
+pub(crate) fn to_textgrid(tiers: &[IntervalTier], duration_s: f32) -> String {
+    let mut out = String::new();
+    writeln!(out, "File type = \"ooTextFile\"").unwrap();
+    writeln!(out, "Object class = \"TextGrid\"\n").unwrap();
+    writeln!(out, "xmin = 0").unwrap();
+    writeln!(out, "xmax = {}", duration_s).unwrap();
+    writeln!(out, "tiers? <exists>").unwrap();
+    writeln!(out, "size = {}", tiers.len()).unwrap();
+    writeln!(out, "item []:").unwrap();
+    for (tier_index, tier) in tiers.iter().enumerate() {
+        let intervals = fill_gaps(&tier.intervals, duration_s);
+        writeln!(out, "    item [{}]:", tier_index + 1).unwrap();
+        writeln!(out, "        class = \"IntervalTier\"").unwrap();
+        writeln!(out, "        name = \"{}\"", tier.name).unwrap();
+        writeln!(out, "        xmin = 0").unwrap();
+        writeln!(out, "        xmax = {}", duration_s).unwrap();
+        writeln!(out, "        intervals: size = {}", intervals.len()).unwrap();
+        for (index, (start_s, end_s, text)) in intervals.into_iter().enumerate() {
+            writeln!(out, "        intervals [{}]:", index + 1).unwrap();
+            writeln!(out, "            xmin = {}", start_s).unwrap();
+            writeln!(out, "            xmax = {}", end_s).unwrap();
+            writeln!(out, "            text = \"{}\"", text.replace('"', "\"\"")).unwrap();
+        }
+    }
+    out
+}
+
+fn fill_gaps(intervals: &[Interval], duration_s: f32) -> Vec<(f32, f32, &str)> {
+    let mut filled = Vec::with_capacity(intervals.len() * 2 + 1);
+    let mut cursor = 0f32;
+    for interval in intervals {
+        if interval.start_s > cursor {
+            filled.push((cursor, interval.start_s, ""));
+        }
+        filled.push((interval.start_s, interval.end_s, interval.text.as_str()));
+        cursor = interval.end_s;
+    }
+    if duration_s > cursor {
+        filled.push((cursor, duration_s, ""));
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaps_are_filled() {
+        let tier = IntervalTier {
+            name: "words",
+            intervals: vec![Interval {
+                start_s: 0.5,
+                end_s: 1.0,
+                text: "a\"b".to_string(),
+            }],
+        };
+        let textgrid = to_textgrid(&[tier], 2.0);
+        assert!(textgrid.contains("intervals: size = 3"));
+        assert!(textgrid.contains("text = \"a\"\"b\""));
+    }
+}
@@ -48,6 +48,300 @@ where
     Ok(())
 }
 
+/// A marker at a sample frame, shown by audio editors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WavCue {
+    pub frame_offset: u32,
+    pub label: String,
+}
+
+/// Tags written to a `LIST`/`INFO` chunk, and cue points written to a `cue `
+/// chunk with their labels in a `LIST`/`adtl` chunk
+#[derive(Debug, Clone, Default)]
+pub struct WavMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub date: Option<String>,
+    pub comment: Option<String>,
+    pub cues: Vec<WavCue>,
+}
+
+impl WavMetadata {
+    fn has_tags(&self) -> bool {
+        self.title.is_some()
+            || self.artist.is_some()
+            || self.date.is_some()
+            || self.comment.is_some()
+    }
+    fn to_list_chunk(&self) -> Vec<u8> {
+        if !self.has_tags() {
+            return Vec::new();
+        }
+        let mut info = Vec::from(*b"INFO");
+        let tags = [
+            (b"INAM", &self.title),
+            (b"IART", &self.artist),
+            (b"ICRD", &self.date),
+            (b"ICMT", &self.comment),
+        ];
+        for (tag_id, value) in tags {
+            if let Some(value) = value {
+                // INFO strings are NUL terminated
+                let mut text = Vec::from(value.as_bytes());
+                text.push(0);
+                append_chunk(&mut info, tag_id, &text);
+            }
+        }
+        let mut chunk = Vec::with_capacity(info.len() + 8);
+        append_chunk(&mut chunk, b"LIST", &info);
+        chunk
+    }
+    fn to_cue_chunks(&self) -> Vec<u8> {
+        if self.cues.is_empty() {
+            return Vec::new();
+        }
+        let mut points = Vec::from((self.cues.len() as u32).to_le_bytes());
+        let mut labels = Vec::from(*b"adtl");
+        for (index, cue) in self.cues.iter().enumerate() {
+            // cue ids start at 1
+            let id = (index as u32 + 1).to_le_bytes();
+            points.extend_from_slice(&id);
+            points.extend_from_slice(&cue.frame_offset.to_le_bytes());
+            points.extend_from_slice(b"data");
+            // chunk start and block start, both 0 for a single `data` chunk
+            points.extend_from_slice(&[0; 8]);
+            points.extend_from_slice(&cue.frame_offset.to_le_bytes());
+            let mut label = Vec::from(id);
+            label.extend_from_slice(cue.label.as_bytes());
+            label.push(0);
+            append_chunk(&mut labels, b"labl", &label);
+        }
+        let mut chunks = Vec::with_capacity(points.len() + labels.len() + 16);
+        append_chunk(&mut chunks, b"cue ", &points);
+        append_chunk(&mut chunks, b"LIST", &labels);
+        chunks
+    }
+}
+
+/// Append a RIFF chunk, padded to an even number of bytes as the spec requires
+fn append_chunk(buf: &mut Vec<u8>, chunk_id: &[u8; 4], data: &[u8]) {
+    buf.extend_from_slice(chunk_id);
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(data);
+    if !data.len().is_multiple_of(2) {
+        buf.push(0);
+    }
+}
+
+/// How samples are stored in a WAV file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WavEncoding {
+    /// 16-bit integer PCM (format tag 1)
+    #[default]
+    PcmI16,
+    /// 32-bit IEEE float (format tag 3), written without any quantization
+    Float32,
+    /// 8-bit G.711 μ-law (format tag 7), for telephony at 8 kHz
+    MuLaw,
+    /// 8-bit G.711 A-law (format tag 6), for telephony at 8 kHz
+    ALaw,
+}
+
+impl WavEncoding {
+    fn format_tag(self) -> u16 {
+        match self {
+            Self::PcmI16 => 1,
+            Self::Float32 => 3,
+            Self::ALaw => 6,
+            Self::MuLaw => 7,
+        }
+    }
+}
+
+/// Write a whole RIFF/WAVE file holding the little-endian sample bytes `data`
+fn write_riff<B: Write>(
+    mut buf: B,
+    encoding: WavEncoding,
+    data: &[u8],
+    sample_rate: u32,
+    num_channels: u32,
+    sample_width: u32,
+    metadata: &WavMetadata,
+) -> Result<(), WaveWriterError> {
+    let block_align = num_channels * sample_width;
+    let mut fmt = Vec::with_capacity(18);
+    fmt.extend_from_slice(&encoding.format_tag().to_le_bytes());
+    fmt.extend_from_slice(&(num_channels as u16).to_le_bytes());
+    fmt.extend_from_slice(&sample_rate.to_le_bytes());
+    fmt.extend_from_slice(&(sample_rate * block_align).to_le_bytes());
+    fmt.extend_from_slice(&(block_align as u16).to_le_bytes());
+    fmt.extend_from_slice(&((sample_width * 8) as u16).to_le_bytes());
+
+    let mut riff = Vec::from(*b"WAVE");
+    if encoding == WavEncoding::PcmI16 {
+        append_chunk(&mut riff, b"fmt ", &fmt);
+    } else {
+        // non-PCM formats need the extension size, and a `fact` chunk with the
+        // number of frames
+        fmt.extend_from_slice(&0u16.to_le_bytes());
+        append_chunk(&mut riff, b"fmt ", &fmt);
+        let num_frames = data.len() as u32 / block_align.max(1);
+        append_chunk(&mut riff, b"fact", &num_frames.to_le_bytes());
+    }
+    riff.append(&mut metadata.to_list_chunk());
+    riff.append(&mut metadata.to_cue_chunks());
+    append_chunk(&mut riff, b"data", data);
+    let mut out = Vec::with_capacity(riff.len() + 8);
+    append_chunk(&mut out, b"RIFF", &riff);
+    buf.write_all(&out)
+        .map_err(|e| WaveWriterError(format!("Failed to write wave bytes. Error: {}", e)))
+}
+
+pub fn write_wave_samples_with_metadata_to_buffer<'a, I, B>(
+    buf: B,
+    samples: I,
+    sample_rate: u32,
+    num_channels: u32,
+    sample_width: u32,
+    metadata: &WavMetadata,
+) -> Result<(), WaveWriterError>
+where
+    I: Iterator<Item = &'a i16>,
+    B: Write,
+{
+    let data = Vec::from_iter(samples.flat_map(|i| i.to_le_bytes()));
+    write_riff(
+        buf,
+        WavEncoding::PcmI16,
+        &data,
+        sample_rate,
+        num_channels,
+        sample_width,
+        metadata,
+    )
+}
+
+/// Write `samples` as a 32-bit float WAV
+pub fn write_wave_f32_samples_to_buffer<'a, I, B>(
+    buf: B,
+    samples: I,
+    sample_rate: u32,
+    num_channels: u32,
+    metadata: &WavMetadata,
+) -> Result<(), WaveWriterError>
+where
+    I: Iterator<Item = &'a f32>,
+    B: Write,
+{
+    let data = Vec::from_iter(samples.flat_map(|f| f.to_le_bytes()));
+    write_riff(
+        buf,
+        WavEncoding::Float32,
+        &data,
+        sample_rate,
+        num_channels,
+        4,
+        metadata,
+    )
+}
+
+/// The header of a PCM WAV of unknown length, with `0xFFFFFFFF` RIFF and data
+/// sizes, for streaming the samples after it
+pub fn streaming_wave_header(sample_rate: u32, num_channels: u32, sample_width: u32) -> Vec<u8> {
+    let block_align = num_channels * sample_width;
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header.extend_from_slice(b"WAVE");
+    header.extend_from_slice(b"fmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&WavEncoding::PcmI16.format_tag().to_le_bytes());
+    header.extend_from_slice(&(num_channels as u16).to_le_bytes());
+    header.extend_from_slice(&sample_rate.to_le_bytes());
+    header.extend_from_slice(&(sample_rate * block_align).to_le_bytes());
+    header.extend_from_slice(&(block_align as u16).to_le_bytes());
+    header.extend_from_slice(&((sample_width * 8) as u16).to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&u32::MAX.to_le_bytes());
+    header
+}
+
+/// Write G.711 companded `data` (see `AudioSamples::to_ulaw` and `to_alaw`) as a
+/// WAV with `encoding`, which must be `MuLaw` or `ALaw`
+pub fn write_wave_g711_to_buffer<B: Write>(
+    buf: B,
+    encoding: WavEncoding,
+    data: &[u8],
+    sample_rate: u32,
+    num_channels: u32,
+) -> Result<(), WaveWriterError> {
+    if !matches!(encoding, WavEncoding::MuLaw | WavEncoding::ALaw) {
+        return Err(WaveWriterError(format!(
+            "`{:?}` is not a G.711 encoding",
+            encoding
+        )));
+    }
+    write_riff(
+        buf,
+        encoding,
+        data,
+        sample_rate,
+        num_channels,
+        1,
+        &WavMetadata::default(),
+    )
+}
+
+pub fn write_wave_g711_to_file(
+    filename: &Path,
+    encoding: WavEncoding,
+    data: &[u8],
+    sample_rate: u32,
+    num_channels: u32,
+) -> Result<(), WaveWriterError> {
+    let mut out: Vec<u8> = Vec::new();
+    write_wave_g711_to_buffer(&mut out, encoding, data, sample_rate, num_channels)?;
+    write_bytes_to_file(filename, &out)
+}
+
+pub fn write_wave_f32_samples_to_file<'a, I>(
+    filename: &Path,
+    samples: I,
+    sample_rate: u32,
+    num_channels: u32,
+    metadata: &WavMetadata,
+) -> Result<(), WaveWriterError>
+where
+    I: Iterator<Item = &'a f32>,
+{
+    let mut out: Vec<u8> = Vec::new();
+    write_wave_f32_samples_to_buffer(&mut out, samples, sample_rate, num_channels, metadata)?;
+    write_bytes_to_file(filename, &out)
+}
+
+pub fn write_wave_samples_with_metadata_to_file<'a, I>(
+    filename: &Path,
+    samples: I,
+    sample_rate: u32,
+    num_channels: u32,
+    sample_width: u32,
+    metadata: &WavMetadata,
+) -> Result<(), WaveWriterError>
+where
+    I: Iterator<Item = &'a i16>,
+{
+    let mut out: Vec<u8> = Vec::new();
+    write_wave_samples_with_metadata_to_buffer(
+        &mut out,
+        samples,
+        sample_rate,
+        num_channels,
+        sample_width,
+        metadata,
+    )?;
+    write_bytes_to_file(filename, &out)
+}
+
 pub fn write_wave_samples_to_file<'a, I>(
     filename: &Path,
     samples: I,
@@ -66,8 +360,12 @@ where
         num_channels,
         sample_width,
     )?;
+    write_bytes_to_file(filename, &out)
+}
+
+fn write_bytes_to_file(filename: &Path, out: &[u8]) -> Result<(), WaveWriterError> {
     match File::create(filename) {
-        Ok(mut file) => match file.write(out.as_slice()) {
+        Ok(mut file) => match file.write(out) {
             Ok(_) => Ok(()),
             Err(e) => {
                 std::fs::remove_file(filename).ok();
@@ -85,3 +383,125 @@ where
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_chunk_is_counted_and_padded() {
+        let metadata = WavMetadata {
+            // odd length once NUL terminated, so it needs a pad byte
+            title: Some("Hello".to_string()),
+            artist: Some("piper".to_string()),
+            ..Default::default()
+        };
+        let samples = [0i16, 1, -1];
+        let mut out = Vec::new();
+        write_wave_samples_with_metadata_to_buffer(
+            &mut out,
+            samples.iter(),
+            22050,
+            1,
+            2,
+            &metadata,
+        )
+        .unwrap();
+        assert_eq!(out.len() % 2, 0);
+        let riff_size = u32::from_le_bytes(out[4..8].try_into().unwrap()) as usize;
+        assert_eq!(riff_size, out.len() - 8);
+        let list_pos = out.windows(4).position(|w| w == b"LIST").unwrap();
+        let list_size = u32::from_le_bytes(out[list_pos + 4..list_pos + 8].try_into().unwrap());
+        // "INFO" + two 8 byte headers + "Hello\0" and "piper\0" (6 bytes each)
+        assert_eq!(list_size, 4 + 2 * (8 + 6));
+        let data_pos = out.windows(4).position(|w| w == b"data").unwrap();
+        let data_size = u32::from_le_bytes(out[data_pos + 4..data_pos + 8].try_into().unwrap());
+        assert_eq!(data_size as usize, samples.len() * 2);
+    }
+
+    #[test]
+    fn test_cue_chunks() {
+        let metadata = WavMetadata {
+            cues: vec![
+                WavCue {
+                    frame_offset: 0,
+                    label: "Hi".to_string(),
+                },
+                WavCue {
+                    frame_offset: 300,
+                    label: "there".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        write_wave_samples_with_metadata_to_buffer(
+            &mut out,
+            [0i16; 4].iter(),
+            22050,
+            1,
+            2,
+            &metadata,
+        )
+        .unwrap();
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        assert_eq!(u32_at(4) as usize, out.len() - 8);
+        // one info-less LIST, which holds the labels
+        assert_eq!(out.windows(4).filter(|w| w == b"LIST").count(), 1);
+        let cue_pos = out.windows(4).position(|w| w == b"cue ").unwrap();
+        assert_eq!(u32_at(cue_pos + 4), 4 + 2 * 24);
+        assert_eq!(u32_at(cue_pos + 8), 2);
+        // the second point's id, position and sample offset
+        assert_eq!(u32_at(cue_pos + 12 + 24), 2);
+        assert_eq!(u32_at(cue_pos + 16 + 24), 300);
+        assert_eq!(u32_at(cue_pos + 32 + 24), 300);
+        let list_pos = out.windows(4).position(|w| w == b"LIST").unwrap();
+        assert_eq!(&out[list_pos + 8..list_pos + 12], b"adtl");
+        // "adtl" + "labl" chunks with an id and "Hi\0" (padded), then "there\0"
+        assert_eq!(u32_at(list_pos + 4), 4 + (8 + 4 + 4) + (8 + 4 + 6));
+        assert_eq!(&out[list_pos + 24..list_pos + 27], b"Hi\0");
+    }
+
+    #[test]
+    fn test_g711_header() {
+        let mut out = Vec::new();
+        write_wave_g711_to_buffer(&mut out, WavEncoding::MuLaw, &[0xFF; 3], 8000, 1).unwrap();
+        let u16_at = |pos: usize| u16::from_le_bytes(out[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        let fmt_pos = out.windows(4).position(|w| w == b"fmt ").unwrap() + 8;
+        assert_eq!(u16_at(fmt_pos), 7);
+        assert_eq!(u32_at(fmt_pos + 8), 8000);
+        assert_eq!(u16_at(fmt_pos + 12), 1);
+        assert_eq!(u16_at(fmt_pos + 14), 8);
+        let fact_pos = out.windows(4).position(|w| w == b"fact").unwrap();
+        assert_eq!(u32_at(fact_pos + 8), 3);
+        assert!(write_wave_g711_to_buffer(Vec::new(), WavEncoding::PcmI16, &[], 8000, 1).is_err());
+    }
+
+    #[test]
+    fn test_float_header() {
+        let samples = [0.0f32, 0.5, -0.25, 1.0];
+        let mut out = Vec::new();
+        write_wave_f32_samples_to_buffer(&mut out, samples.iter(), 16000, 2, &Default::default())
+            .unwrap();
+        let u16_at = |pos: usize| u16::from_le_bytes(out[pos..pos + 2].try_into().unwrap());
+        let u32_at = |pos: usize| u32::from_le_bytes(out[pos..pos + 4].try_into().unwrap());
+        assert_eq!(u32_at(4) as usize, out.len() - 8);
+        let fmt_pos = out.windows(4).position(|w| w == b"fmt ").unwrap() + 8;
+        assert_eq!(u16_at(fmt_pos), 3);
+        assert_eq!(u16_at(fmt_pos + 2), 2);
+        assert_eq!(u32_at(fmt_pos + 8), 16000 * 8);
+        assert_eq!(u16_at(fmt_pos + 12), 8);
+        assert_eq!(u16_at(fmt_pos + 14), 32);
+        let fact_pos = out.windows(4).position(|w| w == b"fact").unwrap();
+        assert_eq!(u32_at(fact_pos + 8), 2);
+        let data_pos = out.windows(4).position(|w| w == b"data").unwrap();
+        let data = &out[data_pos + 8..];
+        assert_eq!(u32_at(data_pos + 4) as usize, data.len());
+        let decoded = Vec::from_iter(
+            data.chunks(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap())),
+        );
+        assert_eq!(decoded, samples);
+    }
+}
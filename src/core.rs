@@ -3,12 +3,18 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 
-pub use crate::audio::{Audio, AudioInfo, AudioSamples, WaveWriterError};
+pub use crate::audio::{
+    Audio, AudioInfo, AudioSamples, AudioStats, BandKind, ConversionMode, EqBand, ResampleQuality,
+    WavCue, WavEncoding, WavMetadata, WaveWriterError,
+};
 
 pub type PiperResult<T> = Result<T, PiperError>;
 pub type PiperAudioResult = PiperResult<Audio>;
 pub type AudioStreamIterator<'a> =
     Box<dyn Iterator<Item = PiperResult<AudioSamples>> + Send + Sync + 'a>;
+/// Streamed chunks, each with the phonemes spoken in it
+pub type PhonemeChunkIterator<'a> =
+    Box<dyn Iterator<Item = PiperResult<(AudioSamples, String)>> + Send + Sync + 'a>;
 
 #[derive(Debug)]
 pub enum PiperError {
@@ -73,11 +79,255 @@ impl std::string::ToString for Phonemes {
     }
 }
 
+/// Copy `samples` to the start of `out`, returning the number of samples copied
+pub(crate) fn copy_samples_into(samples: &[f32], out: &mut [f32]) -> PiperResult<usize> {
+    let Some(dest) = out.get_mut(..samples.len()) else {
+        return Err(PiperError::OperationError(format!(
+            "Output buffer too small: {} samples needed, but it only holds {}",
+            samples.len(),
+            out.len()
+        )));
+    };
+    dest.copy_from_slice(samples);
+    Ok(samples.len())
+}
+
+/// Look up the speaker id of `name` (case-insensitive) among `(name, sid)` pairs.
+/// Fails if no name matches, or if matching names refer to different speakers.
+pub(crate) fn find_speaker_by_name<'a>(
+    speakers: impl Iterator<Item = (&'a str, i64)>,
+    name: &str,
+) -> PiperResult<i64> {
+    let wanted = name.to_lowercase();
+    let mut found: Option<i64> = None;
+    for (speaker_name, sid) in speakers {
+        if speaker_name.to_lowercase() != wanted {
+            continue;
+        }
+        match found {
+            Some(other) if other != sid => {
+                return Err(PiperError::OperationError(format!(
+                    "Speaker name `{}` is ambiguous: it matches speaker ids {} and {}",
+                    name,
+                    other.min(sid),
+                    other.max(sid)
+                )))
+            }
+            _ => found = Some(sid),
+        }
+    }
+    found.ok_or_else(|| PiperError::OperationError(format!("Unknown speaker name `{}`", name)))
+}
+
+/// When a single phoneme sounds in a sentence's synthesized audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhonemeTiming {
+    pub phoneme: char,
+    pub start_ms: f32,
+    pub duration_ms: f32,
+}
+
+/// The samples every phoneme of a sentence is spoken over, for labelling chunks
+pub(crate) struct PhonemeSpans(Vec<(char, std::ops::Range<usize>)>);
+
+impl PhonemeSpans {
+    /// From each phoneme and its number of samples, in order. `None` is silence
+    /// that isn't reported, e.g. the sentence's leading and trailing markers.
+    pub fn new(phonemes: impl IntoIterator<Item = (Option<char>, usize)>) -> Self {
+        let mut start = 0;
+        let mut spans = Vec::new();
+        for (phoneme, num_samples) in phonemes {
+            if let Some(phoneme) = phoneme {
+                spans.push((phoneme, start..start + num_samples));
+            }
+            start += num_samples;
+        }
+        Self(spans)
+    }
+    fn overlapping(&self, samples: std::ops::Range<usize>) -> String {
+        String::from_iter(
+            self.0
+                .iter()
+                .filter(|(_, span)| span.start < samples.end && span.end > samples.start)
+                .map(|(phoneme, _)| phoneme),
+        )
+    }
+    /// Pair every chunk of `chunks` with the phonemes it overlaps
+    pub fn label_chunks<'a>(self, chunks: AudioStreamIterator<'a>) -> PhonemeChunkIterator<'a> {
+        let mut start = 0;
+        Box::new(chunks.map(move |chunk| {
+            let chunk = chunk?;
+            let end = start + chunk.len();
+            let phonemes = self.overlapping(start..end);
+            start = end;
+            Ok((chunk, phonemes))
+        }))
+    }
+}
+
+/// What a model takes as input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputMode {
+    /// Phonemes from the model's phonemizer
+    #[default]
+    Phoneme,
+    /// The text's characters, taken as is
+    Character,
+}
+
+/// Chunk sizes, in mel frames, for streaming synthesis that trades a small first
+/// chunk for a low time to first audio against larger, more efficient later ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkSchedule {
+    pub first_chunk_size: usize,
+    /// The size of every chunk after the first
+    pub steady_chunk_size: usize,
+}
+
+/// What goes between the phonemes of a sentence in a model's input ids
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PhonemeSeparator {
+    /// The pad id after every phoneme, as piper models are trained
+    #[default]
+    Pad,
+    /// This phoneme's id after every phoneme
+    Phoneme(char),
+    /// Nothing, the phoneme ids follow each other directly
+    Omit,
+}
+
+/// How a model assembles a sentence's phonemes into its input ids, which are
+/// always wrapped in the bos and eos ids. Phonemes the model doesn't know are
+/// dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhonemeJoinPolicy {
+    pub separator: PhonemeSeparator,
+    /// A phoneme to put in place of each whitespace between words, for models
+    /// trained with a word boundary marker. `None` keeps the whitespace.
+    pub word_boundary: Option<char>,
+}
+
+impl PhonemeJoinPolicy {
+    /// The phonemes of the policy that must be in the model's vocabulary
+    pub(crate) fn required_phonemes(&self) -> impl Iterator<Item = char> {
+        let separator = match self.separator {
+            PhonemeSeparator::Phoneme(phoneme) => Some(phoneme),
+            _ => None,
+        };
+        separator.into_iter().chain(self.word_boundary)
+    }
+    pub(crate) fn join(
+        &self,
+        phonemes: &str,
+        phoneme_id_map: &HashMap<char, Vec<i64>>,
+        (pad_id, bos_id, eos_id): (i64, i64, i64),
+    ) -> Vec<i64> {
+        let id_of = |phoneme| phoneme_id_map.get(&phoneme).and_then(|ids| ids.first());
+        let separator_id = match self.separator {
+            PhonemeSeparator::Pad => Some(pad_id),
+            PhonemeSeparator::Phoneme(phoneme) => id_of(phoneme).copied(),
+            PhonemeSeparator::Omit => None,
+        };
+        let mut phoneme_ids: Vec<i64> = Vec::with_capacity((phonemes.len() + 1) * 2);
+        phoneme_ids.push(bos_id);
+        for phoneme in phonemes.chars() {
+            let phoneme = match self.word_boundary {
+                Some(marker) if phoneme.is_whitespace() => marker,
+                _ => phoneme,
+            };
+            if let Some(id) = id_of(phoneme) {
+                phoneme_ids.push(*id);
+                phoneme_ids.extend(separator_id);
+            }
+        }
+        phoneme_ids.push(eos_id);
+        phoneme_ids
+    }
+}
+
 pub trait PiperModel {
     fn audio_output_info(&self) -> AudioInfo;
+    fn input_mode(&self) -> InputMode {
+        InputMode::Phoneme
+    }
+    /// The phonemes in `phonemes` that the model can't speak and skips, each listed once
+    fn unknown_phonemes(&self, #[allow(unused_variables)] phonemes: &str) -> Vec<char> {
+        Vec::new()
+    }
+    /// The longest phoneme sequence per sentence the model handles well, if known
+    fn max_phoneme_length(&self) -> Option<usize> {
+        None
+    }
     fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes>;
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>>;
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult;
+    /// Like `speak_one_sentence`, but uses `synth_config` for this call only,
+    /// instead of the model's shared fallback synthesis config
+    fn speak_one_sentence_with(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Per-call synthesis config is not supported for this model".to_string(),
+        ))
+    }
+
+    /// Like `speak_one_sentence`, but writes the samples to the start of `out` and
+    /// returns how many were written. Fails if `out` is too small.
+    fn speak_one_sentence_into(&self, phonemes: String, out: &mut [f32]) -> PiperResult<usize> {
+        let audio = self.speak_one_sentence(phonemes)?;
+        copy_samples_into(audio.samples.as_slice(), out)
+    }
+    /// Like `speak_one_sentence`, but speaks as `speaker` for this call only
+    fn speak_one_sentence_as(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] speaker: i64,
+    ) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Speaker selection is not supported for this model".to_string(),
+        ))
+    }
+
+    /// Synthesize a complete, pre-computed phoneme id sequence, bypassing the
+    /// phonemizer and the phoneme to id mapping
+    fn speak_input_ids(&self, #[allow(unused_variables)] input_ids: Vec<i64>) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Synthesis from phoneme ids is not supported for this model".to_string(),
+        ))
+    }
+
+    /// The speaker embedding that `sid` selects, whose length is the model's
+    /// embedding dimension
+    fn speaker_embedding(&self, #[allow(unused_variables)] sid: i64) -> PiperResult<Vec<f32>> {
+        Err(PiperError::OperationError(
+            "Speaker embeddings are not supported for this model".to_string(),
+        ))
+    }
+    /// Like `speak_one_sentence`, but conditions the output on `embedding` instead
+    /// of the embedding of the model's speaker
+    fn speak_with_embedding(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] embedding: &[f32],
+    ) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Speaker embeddings are not supported for this model".to_string(),
+        ))
+    }
+
+    /// Like `speak_one_sentence`, but scales the predicted duration of every
+    /// phoneme of `phonemes` by the matching entry of `duration_scales`
+    fn speak_with_durations(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] duration_scales: Vec<f32>,
+    ) -> PiperAudioResult {
+        Err(PiperError::OperationError(
+            "Duration scaling is not supported for this model".to_string(),
+        ))
+    }
 
     fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>>;
     fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>>;
@@ -90,12 +340,40 @@ pub trait PiperModel {
         Ok(None)
     }
     fn set_speaker(&self, sid: i64) -> Option<PiperError>;
+    /// Set how the phonemes of every sentence are assembled into input ids.
+    /// Phoneme timings and streaming context need the default policy; with any
+    /// other, timings are an error and the context is ignored.
+    fn set_phoneme_join(
+        &self,
+        #[allow(unused_variables)] policy: PhonemeJoinPolicy,
+    ) -> PiperResult<()> {
+        Err(PiperError::OperationError(
+            "Changing the phoneme join policy is not supported for this model".to_string(),
+        ))
+    }
     fn speaker_id_to_name(&self, sid: i64) -> PiperResult<Option<String>> {
         Ok(self
             .get_speakers()?
             .and_then(|speakers| speakers.get(&sid))
             .cloned())
     }
+    fn get_speaker_name(&self, sid: i64) -> Option<&str> {
+        let speakers = self.get_speakers().ok()??;
+        speakers.get(&sid).map(String::as_str)
+    }
+    /// Like `set_speaker`, but selects the speaker by its (case-insensitive) name
+    fn set_speaker_by_name(&self, name: &str) -> Option<PiperError> {
+        let speakers = match self.get_speakers() {
+            Ok(Some(speakers)) => speakers,
+            Ok(None) => return Some(PiperError::with_message("This model has no named speakers")),
+            Err(e) => return Some(e),
+        };
+        let speakers = speakers.iter().map(|(sid, name)| (name.as_str(), *sid));
+        match find_speaker_by_name(speakers, name) {
+            Ok(sid) => self.set_speaker(sid),
+            Err(e) => Some(e),
+        }
+    }
     fn speaker_name_to_id(&self, name: &str) -> PiperResult<Option<i64>> {
         Ok(self.get_speakers()?.and_then(|speakers| {
             for (sid, sname) in speakers {
@@ -110,6 +388,29 @@ pub trait PiperModel {
         Ok(HashMap::with_capacity(0))
     }
 
+    /// Predicted timing of every phoneme in `phonemes` that the model knows,
+    /// relative to the start of the sentence's audio. The sentence's leading and
+    /// trailing silence is reported as the `^` and `$` markers.
+    fn phoneme_timings(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+    ) -> PiperResult<Vec<PhonemeTiming>> {
+        Err(PiperError::OperationError(
+            "Phoneme durations are not supported for this model".to_string(),
+        ))
+    }
+
+    /// Like `speak_one_sentence`, but also returns the timing of every phoneme, as
+    /// `phoneme_timings` reports it, from the same inference as the audio
+    fn speak_with_timings(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+    ) -> PiperResult<(Audio, Vec<PhonemeTiming>)> {
+        Err(PiperError::OperationError(
+            "Phoneme durations are not supported for this model".to_string(),
+        ))
+    }
+
     fn supports_streaming_output(&self) -> bool {
         false
     }
@@ -123,4 +424,82 @@ pub trait PiperModel {
             "Streaming synthesis is not supported for this model".to_string(),
         ))
     }
+    /// Like `stream_synthesis`, but runs `context` (phonemes preceding `phonemes`)
+    /// through the model as well, without emitting audio for it.
+    /// Models that can't trim the context audio ignore it.
+    fn stream_synthesis_with_context(
+        &self,
+        #[allow(unused_variables)] context: String,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.stream_synthesis(phonemes, chunk_size, chunk_padding)
+    }
+    /// Like `stream_synthesis`, but pairs every chunk with the phonemes spoken in
+    /// it. Phonemes spanning two chunks are reported with both.
+    fn stream_synthesis_with_phonemes(
+        &self,
+        #[allow(unused_variables)] phonemes: String,
+        #[allow(unused_variables)] chunk_size: usize,
+        #[allow(unused_variables)] chunk_padding: usize,
+    ) -> PiperResult<PhonemeChunkIterator<'_>> {
+        Err(PiperError::OperationError(
+            "Phoneme chunks are not supported for this model".to_string(),
+        ))
+    }
+    /// Like `stream_synthesis_with_context`, but sizes the chunks by `schedule`
+    /// instead of growing them by `chunk_size` every chunk. Models with fixed
+    /// chunking only use the first chunk size.
+    fn stream_synthesis_scheduled(
+        &self,
+        context: String,
+        phonemes: String,
+        schedule: ChunkSchedule,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        self.stream_synthesis_with_context(
+            context,
+            phonemes,
+            schedule.first_chunk_size,
+            chunk_padding,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phoneme_join_policy() {
+        let id_map = HashMap::from([
+            ('a', vec![5]),
+            ('b', vec![6]),
+            (' ', vec![3]),
+            ('#', vec![4]),
+        ]);
+        let meta_ids = (0, 1, 2);
+        let join = |policy: PhonemeJoinPolicy| policy.join("ab ?a", &id_map, meta_ids);
+        assert_eq!(join(Default::default()), vec![1, 5, 0, 6, 0, 3, 0, 5, 0, 2]);
+        let policy = PhonemeJoinPolicy {
+            separator: PhonemeSeparator::Omit,
+            word_boundary: Some('#'),
+        };
+        assert_eq!(join(policy), vec![1, 5, 6, 4, 5, 2]);
+        let policy = PhonemeJoinPolicy {
+            separator: PhonemeSeparator::Phoneme(' '),
+            word_boundary: None,
+        };
+        assert_eq!(join(policy), vec![1, 5, 3, 6, 3, 3, 3, 5, 3, 2]);
+    }
+
+    #[test]
+    fn test_find_speaker_by_name() {
+        let speakers = [("Narrator", 4), ("narrator", 4), ("Alice", 1), ("ALICE", 2)];
+        let find = |name| find_speaker_by_name(speakers.iter().copied(), name);
+        assert_eq!(find("NARRATOR").unwrap(), 4);
+        assert!(find("alice").is_err());
+        assert!(find("bob").is_err());
+    }
 }
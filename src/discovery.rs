@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::{ModelConfig, PiperError, PiperResult};
+
+/// A model found by `discover_models`, described by its config alone
+#[derive(Clone, Debug)]
+pub struct DiscoveredModel {
+    pub config_path: PathBuf,
+    /// The onnx files the model needs: the model itself, or a streaming
+    /// model's encoder and decoder
+    pub onnx_paths: Vec<PathBuf>,
+    pub key: Option<String>,
+    pub language: String,
+    pub num_speakers: u32,
+    pub sample_rate: u32,
+    pub quality: Option<String>,
+    pub streaming: bool,
+}
+
+/// Find the models in `dir`, without loading any of them.
+///
+/// A model is a `<name>.onnx.json` config next to `<name>.onnx`, or, for streaming
+/// models, a config next to `encoder.onnx` and `decoder.onnx`. Configs that can't
+/// be parsed, or whose onnx files are missing, are skipped with a warning.
+/// Only fails if `dir` can't be read.
+pub fn discover_models(dir: &Path) -> PiperResult<Vec<DiscoveredModel>> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        PiperError::FailedToLoadResource(format!(
+            "Failed to read model directory `{}`. Caused by: `{}`",
+            dir.display(),
+            e
+        ))
+    })?;
+    let mut config_paths = Vec::from_iter(
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json")),
+    );
+    config_paths.sort();
+    let mut models = Vec::new();
+    for config_path in config_paths {
+        match read_model(&config_path) {
+            Ok(model) => models.push(model),
+            Err(e) => tracing::warn!("Skipping `{}`: {}", config_path.display(), e),
+        }
+    }
+    Ok(models)
+}
+
+fn read_model(config_path: &Path) -> Result<DiscoveredModel, String> {
+    let file = File::open(config_path).map_err(|e| e.to_string())?;
+    let config: ModelConfig = serde_json::from_reader(file).map_err(|e| e.to_string())?;
+    let onnx_paths = if config.streaming {
+        vec![
+            config_path.with_file_name("encoder.onnx"),
+            config_path.with_file_name("decoder.onnx"),
+        ]
+    } else {
+        vec![config_path.with_extension("")]
+    };
+    if let Some(missing) = onnx_paths.iter().find(|path| !path.is_file()) {
+        return Err(format!("model file `{}` not found", missing.display()));
+    }
+    let language = match config.language {
+        Some(ref language) => language.code.clone(),
+        None => config.espeak.voice.clone(),
+    };
+    Ok(DiscoveredModel {
+        config_path: config_path.to_path_buf(),
+        onnx_paths,
+        key: config.key,
+        language,
+        num_speakers: config.num_speakers,
+        sample_rate: config.audio.sample_rate,
+        quality: config.audio.quality,
+        streaming: config.streaming,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_models() {
+        let dir = std::env::temp_dir().join(format!("piper-discover-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = r#"{
+            "audio": {"sample_rate": 16000},
+            "espeak": {"voice": "en-us"},
+            "inference": {"noise_scale": 0.667, "length_scale": 1, "noise_w": 0.8},
+            "num_speakers": 1, "speaker_id_map": {}, "num_symbols": 1,
+            "phoneme_map": {}, "phoneme_id_map": {}, "dataset": null
+        }"#;
+        std::fs::write(dir.join("voice.onnx.json"), config).unwrap();
+        std::fs::write(dir.join("voice.onnx"), b"").unwrap();
+        // no onnx file
+        std::fs::write(dir.join("orphan.onnx.json"), config).unwrap();
+        std::fs::write(dir.join("broken.onnx.json"), "{").unwrap();
+        std::fs::write(dir.join("broken.onnx"), b"").unwrap();
+        let models = discover_models(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].language, "en-us");
+        assert_eq!(models[0].sample_rate, 16000);
+        assert_eq!(models[0].onnx_paths, vec![dir.join("voice.onnx")]);
+    }
+}
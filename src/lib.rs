@@ -1,25 +1,45 @@
 use espeak_rs::text_to_phonemes;
 use ndarray::Axis;
 use ndarray::{Array, Array1, Array2, ArrayView, Dim, IxDynImpl};
+use ort::execution_providers::{
+    CPUExecutionProvider, CUDAExecutionProvider, CoreMLExecutionProvider,
+    DirectMLExecutionProvider, ExecutionProviderDispatch,
+};
+use ort::session::builder::GraphOptimizationLevel;
 use ort::session::{Session, SessionInputValue, SessionInputs, SessionOutputs};
 use ort::value::Value;
 use serde::Deserialize;
 
 mod audio;
 mod core;
+mod discovery;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mock;
+mod text;
 pub use audio::synth;
-pub use core::{Audio, AudioInfo, AudioSamples, AudioStreamIterator, Phonemes, PiperModel};
-pub use core::{PiperAudioResult, PiperError, PiperResult};
+pub use core::{
+    Audio, AudioInfo, AudioSamples, AudioStats, AudioStreamIterator, ChunkSchedule,
+    PhonemeChunkIterator, PhonemeJoinPolicy, PhonemeSeparator, Phonemes, PiperModel,
+};
+pub use core::{
+    BandKind, ConversionMode, EqBand, InputMode, PhonemeTiming, PiperAudioResult, PiperError,
+    PiperResult, ResampleQuality, WavCue, WavEncoding, WavMetadata,
+};
+pub use discovery::{discover_models, DiscoveredModel};
+
+use core::PhonemeSpans;
 
 use std::any::Any;
-use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 
 const MIN_CHUNK_SIZE: isize = 44;
 const MAX_CHUNK_SIZE: usize = 1024;
+const SAMPLES_PER_FRAME: usize = 256;
 const BOS: char = '^';
 const EOS: char = '$';
 const PAD: char = '_';
@@ -63,23 +83,288 @@ fn load_model_config(config_path: &Path) -> PiperResult<(ModelConfig, PiperSynth
     Ok((model_config, synth_config))
 }
 
-fn create_inference_session(model_path: &Path) -> Result<Session, ort::Error> {
-    Session::builder()?
+fn create_inference_session(
+    model_path: &Path,
+    provider: ExecutionProvider,
+    graph_cache_dir: Option<&Path>,
+) -> Result<Session, ort::Error> {
+    let builder = || {
+        Session::builder()?.with_execution_providers([provider.dispatch().error_on_failure()])
         // .with_parallel_execution(true)?
         // .with_inter_threads(16)?
         // .with_optimization_level(ort::GraphOptimizationLevel::Level3)?
         // .with_memory_pattern(false)?
-        .commit_from_file(model_path)
+    };
+    let Some(cache_dir) = graph_cache_dir else {
+        return builder()?.commit_from_file(model_path);
+    };
+    match graph_cache_path(model_path, cache_dir, provider) {
+        // already optimized
+        Ok(cache_path) if cache_path.is_file() => {
+            let cached = builder()?
+                .with_optimization_level(GraphOptimizationLevel::Disable)?
+                .commit_from_file(&cache_path);
+            match cached {
+                Ok(session) => Ok(session),
+                Err(e) => {
+                    // a truncated or otherwise unloadable graph; optimize it again
+                    tracing::warn!(
+                        "Failed to load the cached graph `{}`, rebuilding it: {}",
+                        cache_path.display(),
+                        e
+                    );
+                    let _ = std::fs::remove_file(&cache_path);
+                    builder()?
+                        .with_optimized_model_path(cache_path)?
+                        .commit_from_file(model_path)
+                }
+            }
+        }
+        Ok(cache_path) => builder()?
+            .with_optimized_model_path(cache_path)?
+            .commit_from_file(model_path),
+        Err(e) => {
+            tracing::warn!(
+                "Not caching the optimized graph of `{}`: {}",
+                model_path.display(),
+                e
+            );
+            builder()?.commit_from_file(model_path)
+        }
+    }
+}
+
+/// Where the optimized graph of `model_path` is cached in `cache_dir`. The file name
+/// is keyed on the model file's size and modification time and on the onnxruntime
+/// API version, so the graph is optimized again when any of them change; older graphs
+/// of the same model and `provider` are removed. Every part of the name is stable
+/// across builds, so a cache written by one build is found by the next.
+fn graph_cache_path(
+    model_path: &Path,
+    cache_dir: &Path,
+    provider: ExecutionProvider,
+) -> std::io::Result<PathBuf> {
+    let metadata = std::fs::metadata(model_path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let prefix = format!(
+        "{}-{:016x}-{}-",
+        model_path.file_stem().unwrap_or_default().to_string_lossy(),
+        fnv1a(
+            std::fs::canonicalize(model_path)?
+                .as_os_str()
+                .as_encoded_bytes()
+        ),
+        provider.cache_key()
+    );
+    let file_name = format!(
+        "{}{:x}-{:x}.{:09}-ort{}.onnx",
+        prefix,
+        metadata.len(),
+        modified.as_secs(),
+        modified.subsec_nanos(),
+        ort::MINOR_VERSION
+    );
+    std::fs::create_dir_all(cache_dir)?;
+    for entry in std::fs::read_dir(cache_dir)?.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(&prefix) && name != file_name {
+            let _ = std::fs::remove_file(entry.path());
+        }
+    }
+    Ok(cache_dir.join(file_name))
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` gives the same value in every build
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The hardware onnxruntime runs inference on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    Cuda {
+        device_id: i32,
+    },
+    CoreML,
+    DirectML,
+}
+
+impl ExecutionProvider {
+    fn dispatch(self) -> ExecutionProviderDispatch {
+        match self {
+            Self::Cpu => CPUExecutionProvider::default().build(),
+            Self::Cuda { device_id } => CUDAExecutionProvider::default()
+                .with_device_id(device_id)
+                .build(),
+            Self::CoreML => CoreMLExecutionProvider::default().build(),
+            Self::DirectML => DirectMLExecutionProvider::default().build(),
+        }
+    }
+    /// Names the provider in graph cache file names
+    fn cache_key(self) -> String {
+        match self {
+            Self::Cpu => "cpu".to_string(),
+            Self::Cuda { device_id } => format!("cuda{}", device_id),
+            Self::CoreML => "coreml".to_string(),
+            Self::DirectML => "directml".to_string(),
+        }
+    }
+    fn is_available(self) -> bool {
+        fn is_available(provider: impl ort::execution_providers::ExecutionProvider) -> bool {
+            provider.supported_by_platform() && provider.is_available().unwrap_or(false)
+        }
+        match self {
+            Self::Cpu => true,
+            Self::Cuda { .. } => is_available(CUDAExecutionProvider::default()),
+            Self::CoreML => is_available(CoreMLExecutionProvider::default()),
+            Self::DirectML => is_available(DirectMLExecutionProvider::default()),
+        }
+    }
+    /// `self` if onnxruntime was built with it and it's supported here, the CPU otherwise
+    fn or_cpu(self) -> Self {
+        if self.is_available() {
+            return self;
+        }
+        tracing::warn!(
+            "The `{}` execution provider isn't available in this build of onnxruntime, \
+             falling back to the CPU",
+            self
+        );
+        Self::Cpu
+    }
+}
+
+impl fmt::Display for ExecutionProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cpu => write!(f, "cpu"),
+            Self::Cuda { device_id } => write!(f, "cuda:{}", device_id),
+            Self::CoreML => write!(f, "coreml"),
+            Self::DirectML => write!(f, "directml"),
+        }
+    }
+}
+
+/// Inference sessions of one onnx file, so that as many inferences can run at once
+struct SessionPool {
+    sessions: Vec<Mutex<Session>>,
+    next: AtomicUsize,
+    /// The execution provider the sessions run on
+    provider: ExecutionProvider,
+}
+
+impl SessionPool {
+    fn new(
+        model_path: &Path,
+        size: usize,
+        provider: ExecutionProvider,
+        graph_cache_dir: Option<&Path>,
+    ) -> PiperResult<Self> {
+        let mut sessions = Vec::with_capacity(size.max(1));
+        for _ in 0..size.max(1) {
+            match create_inference_session(model_path, provider, graph_cache_dir) {
+                Ok(session) => sessions.push(Mutex::new(session)),
+                Err(err) => {
+                    return Err(PiperError::OperationError(format!(
+                        "Failed to initialize onnxruntime inference session: `{}`",
+                        err
+                    )))
+                }
+            }
+        }
+        Ok(Self {
+            sessions,
+            next: AtomicUsize::new(0),
+            provider,
+        })
+    }
+    /// An idle session if there is one, otherwise the next one in turn once it's free
+    fn get(&self) -> MutexGuard<'_, Session> {
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let num_sessions = self.sessions.len();
+        for offset in 0..num_sessions {
+            if let Ok(session) = self.sessions[(start + offset) % num_sessions].try_lock() {
+                return session;
+            }
+        }
+        self.sessions[start % num_sessions].lock().unwrap()
+    }
+}
+
+/// Options for loading a model with `from_config_path_with_options`
+#[derive(Clone, Debug, Default)]
+pub struct ModelOptions {
+    /// The directory that contains the `espeak-ng-data` directory. If unset, it's looked
+    /// up in `PIPER_ESPEAKNG_DATA_DIRECTORY`, the working directory, then next to the
+    /// executable. eSpeak-ng is process-wide, so all models must agree on this path.
+    pub espeak_data_path: Option<PathBuf>,
+    /// How many inferences the model can run at once, each on its own inference
+    /// session. Every session holds its own copy of the model's weights. If unset,
+    /// there's a single session, and parallel synthesis waits on it.
+    pub max_concurrent_inferences: Option<usize>,
+    /// The hardware to run inference on. If onnxruntime wasn't built with it, or it
+    /// isn't supported on this platform, the CPU is used instead, with a warning.
+    /// The provider in use is the `execution_provider` of the model's `properties()`.
+    pub execution_provider: ExecutionProvider,
+    /// A directory to cache the optimized graphs of the model's onnx files in, so
+    /// later loads skip graph optimization. A cached graph is rebuilt when its onnx
+    /// file or the execution provider changes.
+    pub graph_cache_dir: Option<PathBuf>,
 }
 
 pub fn from_config_path(config_path: &Path) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    from_config_path_with_options(config_path, &ModelOptions::default())
+}
+
+/// Like `from_config_path`, but caches the optimized graph of the model in
+/// `cache_dir`, see `ModelOptions::graph_cache_dir`
+pub fn load_with_cache(
+    config_path: &Path,
+    cache_dir: &Path,
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
+    let options = ModelOptions {
+        graph_cache_dir: Some(cache_dir.to_path_buf()),
+        ..ModelOptions::default()
+    };
+    from_config_path_with_options(config_path, &options)
+}
+
+/// Like `from_config_path`, but loads the model with `options`.
+/// Fails right away if the phonemizer data can't be loaded.
+pub fn from_config_path_with_options(
+    config_path: &Path,
+    options: &ModelOptions,
+) -> PiperResult<Arc<dyn PiperModel + Send + Sync>> {
     let (config, synth_config) = load_model_config(config_path)?;
+    let pool_size = options.max_concurrent_inferences.unwrap_or(1);
+    let provider = options.execution_provider.or_cpu();
+    let graph_cache_dir = options.graph_cache_dir.as_deref();
+    // character models don't need the phonemizer
+    let uses_espeak = config.phoneme_type.as_deref() != Some("text");
+    if uses_espeak {
+        if let Err(e) = espeak_rs::initialize(options.espeak_data_path.as_deref()) {
+            return Err(PiperError::FailedToLoadResource(format!(
+                "Failed to load the phonemizer data. Caused by: `{}`",
+                e
+            )));
+        }
+    }
     if config.streaming {
         Ok(Arc::new(VitsStreamingModel::from_config(
             config,
             synth_config,
             &config_path.with_file_name("encoder.onnx"),
             &config_path.with_file_name("decoder.onnx"),
+            pool_size,
+            provider,
+            graph_cache_dir,
         )?))
     } else {
         let Some(onnx_filename) = config_path.file_stem() else {
@@ -92,6 +377,9 @@ pub fn from_config_path(config_path: &Path) -> PiperResult<Arc<dyn PiperModel +
             config,
             synth_config,
             &config_path.with_file_name(onnx_filename),
+            pool_size,
+            provider,
+            graph_cache_dir,
         )?))
     }
 }
@@ -132,12 +420,18 @@ pub struct ModelConfig {
     pub speaker_id_map: HashMap<String, i64>,
     #[serde(default)]
     pub streaming: bool,
+    #[serde(default)]
     pub espeak: ESpeakConfig,
+    /// `"text"` for models that take characters instead of espeak phonemes
+    #[serde(default)]
+    pub phoneme_type: Option<String>,
     pub inference: InferenceConfig,
     pub num_symbols: u32,
     pub phoneme_map: HashMap<i64, char>,
     pub phoneme_id_map: HashMap<char, Vec<i64>>,
     pub dataset: Option<String>,
+    #[serde(default, alias = "max_phoneme_ids")]
+    pub max_phoneme_length: Option<usize>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -150,8 +444,10 @@ pub struct PiperSynthesisConfig {
 
 trait VitsModelCommons {
     fn get_synth_config(&self) -> &RwLock<PiperSynthesisConfig>;
+    fn get_phoneme_join(&self) -> &RwLock<PhonemeJoinPolicy>;
     fn get_config(&self) -> &ModelConfig;
     fn get_speaker_map(&self) -> &HashMap<i64, String>;
+    fn get_execution_provider(&self) -> ExecutionProvider;
     fn get_meta_ids(&self) -> (i64, i64, i64) {
         let config = self.get_config();
         let pad_id = *config.phoneme_id_map.get(&PAD).unwrap().first().unwrap();
@@ -180,14 +476,20 @@ trait VitsModelCommons {
             .or_else(|| Some(self.get_config().espeak.voice.clone()))
     }
     fn get_properties(&self) -> HashMap<String, String> {
-        HashMap::from([(
-            "quality".to_string(),
-            self.get_config()
-                .audio
-                .quality
-                .clone()
-                .unwrap_or("unknown".to_string()),
-        )])
+        HashMap::from([
+            (
+                "quality".to_string(),
+                self.get_config()
+                    .audio
+                    .quality
+                    .clone()
+                    .unwrap_or("unknown".to_string()),
+            ),
+            (
+                "execution_provider".to_string(),
+                self.get_execution_provider().to_string(),
+            ),
+        ])
     }
 
     fn _do_set_default_synth_config(&self, new_config: &PiperSynthesisConfig) -> PiperResult<()> {
@@ -207,6 +509,28 @@ trait VitsModelCommons {
         }
         Ok(())
     }
+    fn downcast_synth_config<'a>(
+        &self,
+        synth_config: &'a dyn Any,
+    ) -> PiperResult<&'a PiperSynthesisConfig> {
+        let Some(config) = synth_config.downcast_ref::<PiperSynthesisConfig>() else {
+            return Err(PiperError::OperationError(
+                "Invalid configuration for Vits Model".to_string(),
+            ));
+        };
+        match config.speaker {
+            Some(sid)
+                if self.get_config().num_speakers > 1
+                    && !self.get_speaker_map().contains_key(&sid) =>
+            {
+                Err(PiperError::OperationError(format!(
+                    "No speaker was found with the given id `{}`",
+                    sid
+                )))
+            }
+            _ => Ok(config),
+        }
+    }
     fn phonemes_to_input_ids(
         &self,
         phonemes: &str,
@@ -214,30 +538,77 @@ trait VitsModelCommons {
         bos_id: i64,
         eos_id: i64,
     ) -> Vec<i64> {
-        let config = self.get_config();
-        let mut phoneme_ids: Vec<i64> = Vec::with_capacity((phonemes.len() + 1) * 2);
-        phoneme_ids.push(bos_id);
+        self.get_phoneme_join().read().unwrap().join(
+            phonemes,
+            &self.get_config().phoneme_id_map,
+            (pad_id, bos_id, eos_id),
+        )
+    }
+    fn set_phoneme_join(&self, policy: PhonemeJoinPolicy) -> PiperResult<()> {
+        let id_map = &self.get_config().phoneme_id_map;
+        if let Some(phoneme) = policy.required_phonemes().find(|p| !id_map.contains_key(p)) {
+            return Err(PiperError::OperationError(format!(
+                "Phoneme `{}` of the join policy is not in the model's vocabulary",
+                phoneme
+            )));
+        }
+        *self.get_phoneme_join().write().unwrap() = policy;
+        Ok(())
+    }
+    /// Whether input ids are laid out as `bos, (phoneme, pad)*, eos`
+    fn has_default_phoneme_join(&self) -> bool {
+        *self.get_phoneme_join().read().unwrap() == PhonemeJoinPolicy::default()
+    }
+    fn validate_input_ids(&self, input_ids: &[i64]) -> PiperResult<()> {
+        let num_symbols = self.get_config().num_symbols as i64;
+        match input_ids
+            .iter()
+            .position(|id| !(0..num_symbols).contains(id))
+        {
+            Some(index) => Err(PiperError::OperationError(format!(
+                "Phoneme id `{}` at index {} is outside the model's vocabulary of {} symbols",
+                input_ids[index], index, num_symbols
+            ))),
+            None if input_ids.is_empty() => Err(PiperError::OperationError(
+                "Empty phoneme id sequence".to_string(),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn get_unknown_phonemes(&self, phonemes: &str) -> Vec<char> {
+        let mut unknown = Vec::new();
         for phoneme in phonemes.chars() {
-            if let Some(id) = config.phoneme_id_map.get(&phoneme) {
-                phoneme_ids.push(*id.first().unwrap());
-                phoneme_ids.push(pad_id);
+            if !self.get_config().phoneme_id_map.contains_key(&phoneme)
+                && !unknown.contains(&phoneme)
+            {
+                unknown.push(phoneme);
             }
         }
-        phoneme_ids.push(eos_id);
-        phoneme_ids
+        unknown
+    }
+    fn get_input_mode(&self) -> InputMode {
+        match self.get_config().phoneme_type.as_deref() {
+            Some("text") => InputMode::Character,
+            _ => InputMode::Phoneme,
+        }
     }
     fn do_phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+        if self.get_input_mode() == InputMode::Character {
+            return Ok(text::split_character_sentences(text).into());
+        }
         let config = self.get_config();
-        let text = Cow::from(text);
-        let phonemes = match text_to_phonemes(&text, &config.espeak.voice, None, true, false) {
-            Ok(ph) => ph,
-            Err(e) => {
-                return Err(PiperError::PhonemizationError(format!(
-                    "Failed to phonemize given text using espeak-ng. Error: {}",
-                    e
-                )))
-            }
-        };
+        let mut phonemes = Vec::new();
+        for sentence in text::split_sentences(text) {
+            match text_to_phonemes(&sentence, &config.espeak.voice, None, true, false) {
+                Ok(mut ph) => phonemes.append(&mut ph),
+                Err(e) => {
+                    return Err(PiperError::PhonemizationError(format!(
+                        "Failed to phonemize given text using espeak-ng. Error: {}",
+                        e
+                    )))
+                }
+            };
+        }
         Ok(phonemes.into())
     }
 
@@ -246,21 +617,26 @@ trait VitsModelCommons {
             sample_rate: self.get_config().audio.sample_rate as usize,
             num_channels: 1usize,
             sample_width: 2usize,
+            native_sample_rate: self.get_config().audio.sample_rate as usize,
         }
     }
 }
 
 pub struct VitsModel {
     synth_config: RwLock<PiperSynthesisConfig>,
+    phoneme_join: RwLock<PhonemeJoinPolicy>,
     config: ModelConfig,
     speaker_map: HashMap<i64, String>,
-    session: Mutex<Session>,
+    session: SessionPool,
 }
 
 impl VitsModel {
     pub fn new(config_path: PathBuf, onnx_path: &Path) -> PiperResult<Self> {
         match load_model_config(&config_path) {
-            Ok((config, synth_config)) => Self::from_config(config, synth_config, onnx_path),
+            Ok((config, synth_config)) => {
+                let provider = ExecutionProvider::Cpu;
+                Self::from_config(config, synth_config, onnx_path, 1, provider, None)
+            }
             Err(error) => Err(error),
         }
     }
@@ -268,28 +644,46 @@ impl VitsModel {
         config: ModelConfig,
         synth_config: PiperSynthesisConfig,
         onnx_path: &Path,
+        pool_size: usize,
+        provider: ExecutionProvider,
+        graph_cache_dir: Option<&Path>,
     ) -> PiperResult<Self> {
-        let session = match create_inference_session(onnx_path) {
-            Ok(session) => Mutex::new(session),
-            Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
+        let session = SessionPool::new(onnx_path, pool_size, provider, graph_cache_dir)?;
         let speaker_map = reversed_mapping(&config.speaker_id_map);
 
         Ok(Self {
             synth_config: RwLock::new(synth_config),
+            phoneme_join: RwLock::default(),
             config,
             speaker_map,
             session,
         })
     }
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> PiperAudioResult {
-        let synth_config = self.synth_config.read().unwrap();
-
+    fn infer_with_values(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
+        let (audio, inference_ms) = self.infer_raw(input_phonemes, synth_config, |samples| {
+            Ok(Vec::from(samples))
+        })?;
+        Ok(Audio::new(
+            audio.into(),
+            self.config.audio.sample_rate as usize,
+            Some(inference_ms),
+        ))
+    }
+    /// Run inference, handing the raw output samples to `consume` before they're
+    /// released. Also returns the inference time
+    fn infer_raw<T, F>(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+        consume: F,
+    ) -> PiperResult<(T, f32)>
+    where
+        F: FnOnce(&[f32]) -> PiperResult<T>,
+    {
         let input_len = input_phonemes.len();
         let phoneme_inputs = Array2::<i64>::from_shape_vec((1, input_len), input_phonemes).unwrap();
         let input_lengths = Array1::<i64>::from_iter([input_len as i64]);
@@ -305,7 +699,7 @@ impl VitsModel {
             None
         };
 
-        let mut session = self.session.lock().unwrap();
+        let mut session = self.session.get();
         let timer = std::time::Instant::now();
         let outputs = {
             let mut inputs = vec![
@@ -340,13 +734,7 @@ impl VitsModel {
             }
         };
 
-        let audio = Vec::from(outputs.view().as_slice().unwrap());
-
-        Ok(Audio::new(
-            audio.into(),
-            self.config.audio.sample_rate as usize,
-            Some(inference_ms),
-        ))
+        Ok((consume(outputs.view().as_slice().unwrap())?, inference_ms))
     }
     pub fn get_input_output_info(&self) -> PiperResult<Vec<String>> {
         todo!()
@@ -357,12 +745,18 @@ impl VitsModelCommons for VitsModel {
     fn get_synth_config(&self) -> &RwLock<PiperSynthesisConfig> {
         &self.synth_config
     }
+    fn get_phoneme_join(&self) -> &RwLock<PhonemeJoinPolicy> {
+        &self.phoneme_join
+    }
     fn get_config(&self) -> &ModelConfig {
         &self.config
     }
     fn get_speaker_map(&self) -> &HashMap<i64, String> {
         &self.speaker_map
     }
+    fn get_execution_provider(&self) -> ExecutionProvider {
+        self.session.provider
+    }
 }
 
 impl PiperModel for VitsModel {
@@ -370,6 +764,16 @@ impl PiperModel for VitsModel {
         self.do_phonemize_text(text)
     }
 
+    fn speak_one_sentence_into(&self, phonemes: String, out: &mut [f32]) -> PiperResult<usize> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let (num_samples, _) =
+            self.infer_raw(phonemes, &self.synth_config.read().unwrap(), |samples| {
+                core::copy_samples_into(samples, out)
+            })?;
+        Ok(num_samples)
+    }
+
     fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phoneme_batches = Vec::from_iter(
@@ -377,9 +781,10 @@ impl PiperModel for VitsModel {
                 .into_iter()
                 .map(|phonemes| self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id)),
         );
+        let synth_config = self.synth_config.read().unwrap();
         let mut retval = Vec::new();
         for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+            retval.push(self.infer_with_values(phonemes, &synth_config)?);
         }
         Ok(retval)
     }
@@ -387,7 +792,26 @@ impl PiperModel for VitsModel {
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.infer_with_values(phonemes, &self.synth_config.read().unwrap())
+    }
+    fn speak_one_sentence_with(
+        &self,
+        phonemes: String,
+        synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        let synth_config = self.downcast_synth_config(synth_config)?;
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        self.infer_with_values(phonemes, synth_config)
+    }
+    fn speak_one_sentence_as(&self, phonemes: String, speaker: i64) -> PiperAudioResult {
+        let mut synth_config = self.synth_config.read().unwrap().clone();
+        synth_config.speaker = Some(speaker);
+        self.speak_one_sentence_with(phonemes, &synth_config)
+    }
+    fn speak_input_ids(&self, input_ids: Vec<i64>) -> PiperAudioResult {
+        self.validate_input_ids(&input_ids)?;
+        self.infer_with_values(input_ids, &self.synth_config.read().unwrap())
     }
     fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
         Ok(Box::new(PiperSynthesisConfig {
@@ -417,6 +841,20 @@ impl PiperModel for VitsModel {
     fn set_speaker(&self, sid: i64) -> Option<PiperError> {
         VitsModelCommons::set_speaker(self, sid)
     }
+    fn set_phoneme_join(&self, policy: PhonemeJoinPolicy) -> PiperResult<()> {
+        VitsModelCommons::set_phoneme_join(self, policy)
+    }
+    fn set_speaker_by_name(&self, name: &str) -> Option<PiperError> {
+        let speakers = self
+            .config
+            .speaker_id_map
+            .iter()
+            .map(|(name, sid)| (name.as_str(), *sid));
+        match core::find_speaker_by_name(speakers, name) {
+            Ok(sid) => VitsModelCommons::set_speaker(self, sid),
+            Err(e) => Some(e),
+        }
+    }
     fn speaker_name_to_id(&self, name: &str) -> PiperResult<Option<i64>> {
         Ok(self.config.speaker_id_map.get(name).copied())
     }
@@ -426,14 +864,24 @@ impl PiperModel for VitsModel {
     fn audio_output_info(&self) -> AudioInfo {
         self.get_audio_output_info()
     }
+    fn max_phoneme_length(&self) -> Option<usize> {
+        self.config.max_phoneme_length
+    }
+    fn input_mode(&self) -> InputMode {
+        self.get_input_mode()
+    }
+    fn unknown_phonemes(&self, phonemes: &str) -> Vec<char> {
+        self.get_unknown_phonemes(phonemes)
+    }
 }
 
 pub struct VitsStreamingModel {
     synth_config: RwLock<PiperSynthesisConfig>,
+    phoneme_join: RwLock<PhonemeJoinPolicy>,
     config: ModelConfig,
     speaker_map: HashMap<i64, String>,
-    encoder_model: Mutex<Session>,
-    decoder_model: Arc<Mutex<Session>>,
+    encoder_model: SessionPool,
+    decoder_model: Arc<SessionPool>,
 }
 
 impl VitsStreamingModel {
@@ -442,29 +890,22 @@ impl VitsStreamingModel {
         synth_config: PiperSynthesisConfig,
         encoder_path: &Path,
         decoder_path: &Path,
+        pool_size: usize,
+        provider: ExecutionProvider,
+        graph_cache_dir: Option<&Path>,
     ) -> PiperResult<Self> {
-        let encoder_model = match create_inference_session(encoder_path) {
-            Ok(model) => Mutex::new(model),
-            Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
-        let decoder_model = match create_inference_session(decoder_path) {
-            Ok(model) => Arc::new(Mutex::new(model)),
-            Err(err) => {
-                return Err(PiperError::OperationError(format!(
-                    "Failed to initialize onnxruntime inference session: `{}`",
-                    err
-                )))
-            }
-        };
+        let encoder_model = SessionPool::new(encoder_path, pool_size, provider, graph_cache_dir)?;
+        let decoder_model = Arc::new(SessionPool::new(
+            decoder_path,
+            pool_size,
+            provider,
+            graph_cache_dir,
+        )?);
         let speaker_map = reversed_mapping(&config.speaker_id_map);
 
         Ok(Self {
             synth_config: RwLock::new(synth_config),
+            phoneme_join: RwLock::default(),
             config,
             speaker_map,
             encoder_model,
@@ -472,10 +913,14 @@ impl VitsStreamingModel {
         })
     }
 
-    fn infer_with_values(&self, input_phonemes: Vec<i64>) -> PiperAudioResult {
+    fn infer_with_values(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperAudioResult {
         let timer = std::time::Instant::now();
-        let encoder_output = self.infer_encoder(input_phonemes)?;
-        let audio = encoder_output.infer_decoder(&mut *self.decoder_model.lock().unwrap())?;
+        let encoder_output = self.infer_encoder(input_phonemes, synth_config)?;
+        let audio = encoder_output.infer_decoder(&mut self.decoder_model.get())?;
         let inference_ms = timer.elapsed().as_millis() as f32;
         Ok(Audio::new(
             audio,
@@ -483,9 +928,11 @@ impl VitsStreamingModel {
             Some(inference_ms),
         ))
     }
-    fn infer_encoder(&self, input_phonemes: Vec<i64>) -> PiperResult<EncoderOutputs> {
-        let synth_config = self.synth_config.read().unwrap();
-
+    fn infer_encoder(
+        &self,
+        input_phonemes: Vec<i64>,
+        synth_config: &PiperSynthesisConfig,
+    ) -> PiperResult<EncoderOutputs> {
         let input_len = input_phonemes.len();
         let phoneme_inputs = Array2::<i64>::from_shape_vec((1, input_len), input_phonemes).unwrap();
         let input_lengths = Array1::<i64>::from_iter([input_len as i64]);
@@ -503,7 +950,7 @@ impl VitsStreamingModel {
             None
         };
 
-        let mut session = self.encoder_model.lock().unwrap();
+        let mut session = self.encoder_model.get();
         {
             let mut inputs = vec![
                 SessionInputValue::from(Value::from_array(phoneme_inputs).unwrap()),
@@ -533,12 +980,18 @@ impl VitsModelCommons for VitsStreamingModel {
     fn get_synth_config(&self) -> &RwLock<PiperSynthesisConfig> {
         &self.synth_config
     }
+    fn get_phoneme_join(&self) -> &RwLock<PhonemeJoinPolicy> {
+        &self.phoneme_join
+    }
     fn get_config(&self) -> &ModelConfig {
         &self.config
     }
     fn get_speaker_map(&self) -> &HashMap<i64, String> {
         &self.speaker_map
     }
+    fn get_execution_provider(&self) -> ExecutionProvider {
+        self.encoder_model.provider
+    }
 }
 
 impl PiperModel for VitsStreamingModel {
@@ -553,16 +1006,134 @@ impl PiperModel for VitsStreamingModel {
                 .into_iter()
                 .map(|phonemes| self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id)),
         );
+        let synth_config = self.synth_config.read().unwrap();
         let mut retval = Vec::new();
         for phonemes in phoneme_batches.into_iter() {
-            retval.push(self.infer_with_values(phonemes)?);
+            retval.push(self.infer_with_values(phonemes, &synth_config)?);
         }
         Ok(retval)
     }
     fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        self.infer_with_values(phonemes)
+        self.infer_with_values(phonemes, &self.synth_config.read().unwrap())
+    }
+    fn speak_one_sentence_with(
+        &self,
+        phonemes: String,
+        synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        let synth_config = self.downcast_synth_config(synth_config)?;
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        self.infer_with_values(phonemes, synth_config)
+    }
+    fn speak_one_sentence_as(&self, phonemes: String, speaker: i64) -> PiperAudioResult {
+        let mut synth_config = self.synth_config.read().unwrap().clone();
+        synth_config.speaker = Some(speaker);
+        self.speak_one_sentence_with(phonemes, &synth_config)
+    }
+    fn speak_input_ids(&self, input_ids: Vec<i64>) -> PiperAudioResult {
+        self.validate_input_ids(&input_ids)?;
+        self.infer_with_values(input_ids, &self.synth_config.read().unwrap())
+    }
+    /// The `g` output of the encoder, which the decoder is conditioned on. Its
+    /// length is `gin_channels` of the model's training config.
+    fn speaker_embedding(&self, sid: i64) -> PiperResult<Vec<f32>> {
+        if !self.speaker_map.contains_key(&sid) {
+            return Err(PiperError::OperationError(format!(
+                "Invalid speaker id `{}`",
+                sid
+            )));
+        }
+        let mut synth_config = self.synth_config.read().unwrap().clone();
+        synth_config.speaker = Some(sid);
+        // the embedding only depends on the speaker
+        let (_, bos_id, eos_id) = self.get_meta_ids();
+        let encoder_output = self.infer_encoder(vec![bos_id, eos_id], &synth_config)?;
+        if encoder_output.g.is_empty() {
+            return Err(PiperError::OperationError(
+                "The model doesn't output speaker embeddings".to_string(),
+            ));
+        }
+        Ok(encoder_output.g.into_iter().collect())
+    }
+    /// Only the decoder is conditioned on `embedding`; the durations and the flow of
+    /// the encoder still use the embedding of the model's speaker.
+    fn speak_with_embedding(&self, phonemes: String, embedding: &[f32]) -> PiperAudioResult {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let timer = std::time::Instant::now();
+        let mut encoder_output =
+            self.infer_encoder(phonemes, &self.synth_config.read().unwrap())?;
+        if encoder_output.g.is_empty() {
+            return Err(PiperError::OperationError(
+                "The model doesn't take speaker embeddings".to_string(),
+            ));
+        }
+        if embedding.len() != encoder_output.g.len() {
+            return Err(PiperError::OperationError(format!(
+                "Expected a speaker embedding of {} values, got {}",
+                encoder_output.g.len(),
+                embedding.len()
+            )));
+        }
+        encoder_output.g =
+            Array::from_shape_vec(encoder_output.g.raw_dim(), embedding.to_vec()).unwrap();
+        let audio = encoder_output.infer_decoder(&mut self.decoder_model.get())?;
+        let inference_ms = timer.elapsed().as_millis() as f32;
+        Ok(Audio::new(
+            audio,
+            self.config.audio.sample_rate as usize,
+            Some(inference_ms),
+        ))
+    }
+    fn speak_with_durations(
+        &self,
+        phonemes: String,
+        duration_scales: Vec<f32>,
+    ) -> PiperAudioResult {
+        let num_phonemes = phonemes.chars().count();
+        if duration_scales.len() != num_phonemes {
+            return Err(PiperError::OperationError(format!(
+                "Expected {} duration scales, got {}",
+                num_phonemes,
+                duration_scales.len()
+            )));
+        }
+        if !self.has_default_phoneme_join() {
+            return Err(PiperError::OperationError(
+                "Duration scaling needs the default phoneme join policy".to_string(),
+            ));
+        }
+        let timer = std::time::Instant::now();
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let mut encoder_outputs =
+            self.infer_encoder(input_ids, &self.synth_config.read().unwrap())?;
+        let Some(frames_per_id) = encoder_outputs.frames_per_id() else {
+            return Err(PiperError::OperationError(
+                "Phoneme durations are not supported for this model".to_string(),
+            ));
+        };
+        // a phoneme's pad is scaled with it, and the markers keep their duration
+        let known_scales = phonemes
+            .chars()
+            .zip(duration_scales)
+            .filter(|(phoneme, _)| self.config.phoneme_id_map.contains_key(phoneme))
+            .flat_map(|(_, scale)| [scale, scale]);
+        let id_scales = Vec::from_iter(
+            std::iter::once(1.0)
+                .chain(known_scales)
+                .chain(std::iter::once(1.0)),
+        );
+        encoder_outputs.scale_durations(&frames_per_id, &id_scales);
+        let audio = encoder_outputs.infer_decoder(&mut self.decoder_model.get())?;
+        Ok(Audio::new(
+            audio,
+            self.config.audio.sample_rate as usize,
+            Some(timer.elapsed().as_millis() as f32),
+        ))
     }
     fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
         Ok(Box::new(PiperSynthesisConfig {
@@ -592,6 +1163,20 @@ impl PiperModel for VitsStreamingModel {
     fn set_speaker(&self, sid: i64) -> Option<PiperError> {
         VitsModelCommons::set_speaker(self, sid)
     }
+    fn set_phoneme_join(&self, policy: PhonemeJoinPolicy) -> PiperResult<()> {
+        VitsModelCommons::set_phoneme_join(self, policy)
+    }
+    fn set_speaker_by_name(&self, name: &str) -> Option<PiperError> {
+        let speakers = self
+            .config
+            .speaker_id_map
+            .iter()
+            .map(|(name, sid)| (name.as_str(), *sid));
+        match core::find_speaker_by_name(speakers, name) {
+            Ok(sid) => VitsModelCommons::set_speaker(self, sid),
+            Err(e) => Some(e),
+        }
+    }
     fn speaker_name_to_id(&self, name: &str) -> PiperResult<Option<i64>> {
         Ok(self.config.speaker_id_map.get(name).copied())
     }
@@ -601,6 +1186,55 @@ impl PiperModel for VitsStreamingModel {
     fn audio_output_info(&self) -> AudioInfo {
         self.get_audio_output_info()
     }
+    fn max_phoneme_length(&self) -> Option<usize> {
+        self.config.max_phoneme_length
+    }
+    fn input_mode(&self) -> InputMode {
+        self.get_input_mode()
+    }
+    fn unknown_phonemes(&self, phonemes: &str) -> Vec<char> {
+        self.get_unknown_phonemes(phonemes)
+    }
+    fn phoneme_timings(&self, phonemes: String) -> PiperResult<Vec<PhonemeTiming>> {
+        if !self.has_default_phoneme_join() {
+            return Err(PiperError::OperationError(
+                "Phoneme timings need the default phoneme join policy".to_string(),
+            ));
+        }
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let encoder_outputs = self.infer_encoder(input_ids, &self.synth_config.read().unwrap())?;
+        let Some(frames_per_id) = encoder_outputs.frames_per_id() else {
+            return Err(PiperError::OperationError(
+                "Phoneme durations are not supported for this model".to_string(),
+            ));
+        };
+        Ok(self.phoneme_timings_of(&phonemes, &frames_per_id))
+    }
+    fn speak_with_timings(&self, phonemes: String) -> PiperResult<(Audio, Vec<PhonemeTiming>)> {
+        if !self.has_default_phoneme_join() {
+            return Err(PiperError::OperationError(
+                "Phoneme timings need the default phoneme join policy".to_string(),
+            ));
+        }
+        let timer = std::time::Instant::now();
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let encoder_outputs = self.infer_encoder(input_ids, &self.synth_config.read().unwrap())?;
+        let Some(frames_per_id) = encoder_outputs.frames_per_id() else {
+            return Err(PiperError::OperationError(
+                "Phoneme durations are not supported for this model".to_string(),
+            ));
+        };
+        let timings = self.phoneme_timings_of(&phonemes, &frames_per_id);
+        let audio = encoder_outputs.infer_decoder(&mut self.decoder_model.get())?;
+        let audio = Audio::new(
+            audio,
+            self.config.audio.sample_rate as usize,
+            Some(timer.elapsed().as_millis() as f32),
+        );
+        Ok((audio, timings))
+    }
     fn supports_streaming_output(&self) -> bool {
         true
     }
@@ -612,21 +1246,157 @@ impl PiperModel for VitsStreamingModel {
     ) -> PiperResult<AudioStreamIterator> {
         let (pad_id, bos_id, eos_id) = self.get_meta_ids();
         let phonemes = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
-        let encoder_outputs = self.infer_encoder(phonemes)?;
-        let streamer = Box::new(SpeechStreamer::new(
+        let encoder_outputs = self.infer_encoder(phonemes, &self.synth_config.read().unwrap())?;
+        Ok(self.create_streamer(encoder_outputs, chunk_size, None, chunk_padding))
+    }
+    fn stream_synthesis_with_phonemes(
+        &self,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<PhonemeChunkIterator<'_>> {
+        if !self.has_default_phoneme_join() {
+            return Err(PiperError::OperationError(
+                "Phoneme chunks need the default phoneme join policy".to_string(),
+            ));
+        }
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+        let encoder_outputs = self.infer_encoder(input_ids, &self.synth_config.read().unwrap())?;
+        let Some(frames_per_id) = encoder_outputs.frames_per_id() else {
+            return Err(PiperError::OperationError(
+                "Phoneme durations are not supported for this model".to_string(),
+            ));
+        };
+        let spans = PhonemeSpans::new(
+            self.phoneme_frames(&phonemes, &frames_per_id)
+                .into_iter()
+                .map(|(phoneme, num_frames)| {
+                    let is_marker = phoneme == BOS || phoneme == EOS;
+                    (
+                        (!is_marker).then_some(phoneme),
+                        num_frames * SAMPLES_PER_FRAME,
+                    )
+                }),
+        );
+        Ok(spans.label_chunks(self.create_streamer(
+            encoder_outputs,
+            chunk_size,
+            None,
+            chunk_padding,
+        )))
+    }
+    fn stream_synthesis_with_context(
+        &self,
+        context: String,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let encoder_outputs = self.encode_with_context(context, phonemes)?;
+        Ok(self.create_streamer(encoder_outputs, chunk_size, None, chunk_padding))
+    }
+    fn stream_synthesis_scheduled(
+        &self,
+        context: String,
+        phonemes: String,
+        schedule: ChunkSchedule,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let encoder_outputs = self.encode_with_context(context, phonemes)?;
+        Ok(self.create_streamer(
+            encoder_outputs,
+            schedule.first_chunk_size,
+            Some(schedule.steady_chunk_size),
+            chunk_padding,
+        ))
+    }
+}
+
+impl VitsStreamingModel {
+    /// The number of frames of every known phoneme of `phonemes`, between the
+    /// `BOS` and `EOS` markers, given the frames of every input id
+    /// The timing of every phoneme of `phonemes`, from the encoder's `frames_per_id`
+    fn phoneme_timings_of(&self, phonemes: &str, frames_per_id: &[usize]) -> Vec<PhonemeTiming> {
+        let frame_ms = (SAMPLES_PER_FRAME * 1000) as f32 / self.config.audio.sample_rate as f32;
+        let mut start_ms = 0f32;
+        let mut timings = Vec::new();
+        for (phoneme, num_frames) in self.phoneme_frames(phonemes, frames_per_id) {
+            let duration_ms = num_frames as f32 * frame_ms;
+            timings.push(PhonemeTiming {
+                phoneme,
+                start_ms,
+                duration_ms,
+            });
+            start_ms += duration_ms;
+        }
+        timings
+    }
+    fn phoneme_frames(&self, phonemes: &str, frames_per_id: &[usize]) -> Vec<(char, usize)> {
+        // input ids are laid out as `bos, (phoneme, pad)*, eos`, and each
+        // phoneme is credited with the frames of the pad that follows it
+        let (bos_frames, rest) = frames_per_id.split_first().unwrap();
+        let (eos_frames, phoneme_frames) = rest.split_last().unwrap();
+        let known_phonemes = phonemes
+            .chars()
+            .filter(|c| self.config.phoneme_id_map.contains_key(c));
+        Vec::from_iter(
+            std::iter::once((BOS, *bos_frames))
+                .chain(known_phonemes.zip(phoneme_frames.chunks(2).map(|f| f.iter().sum())))
+                .chain(std::iter::once((EOS, *eos_frames))),
+        )
+    }
+    /// Run the encoder over `context` and `phonemes`, and drop the frames of `context`
+    fn encode_with_context(
+        &self,
+        context: String,
+        phonemes: String,
+    ) -> PiperResult<EncoderOutputs> {
+        let (pad_id, bos_id, eos_id) = self.get_meta_ids();
+        // the context's ids can only be told apart with the default layout
+        if context.is_empty() || !self.has_default_phoneme_join() {
+            let input_ids = self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id);
+            return self.infer_encoder(input_ids, &self.synth_config.read().unwrap());
+        }
+        let mut input_ids = self.phonemes_to_input_ids(&context, pad_id, bos_id, eos_id);
+        // drop the context's eos, and the sentence's bos
+        input_ids.pop();
+        let num_context_ids = input_ids.len();
+        input_ids.extend(
+            self.phonemes_to_input_ids(&phonemes, pad_id, bos_id, eos_id)
+                .into_iter()
+                .skip(1),
+        );
+        let mut encoder_outputs =
+            self.infer_encoder(input_ids, &self.synth_config.read().unwrap())?;
+        match encoder_outputs.num_frames_for_ids(num_context_ids) {
+            Some(num_frames) => encoder_outputs.skip_frames(num_frames),
+            // Without predicted durations the context audio can't be trimmed
+            None => return self.encode_with_context(String::new(), phonemes),
+        };
+        Ok(encoder_outputs)
+    }
+    /// Without `steady_chunk_size`, chunks grow by `chunk_size` every chunk
+    fn create_streamer(
+        &self,
+        encoder_outputs: EncoderOutputs,
+        chunk_size: usize,
+        steady_chunk_size: Option<usize>,
+        chunk_padding: usize,
+    ) -> AudioStreamIterator<'static> {
+        Box::new(SpeechStreamer::new(
             Arc::clone(&self.decoder_model),
             encoder_outputs,
             chunk_size,
+            steady_chunk_size,
             chunk_padding,
-        ));
-        Ok(streamer)
+        ))
     }
 }
 
 struct EncoderOutputs {
     z: Array<f32, Dim<IxDynImpl>>,
     y_mask: Array<f32, Dim<IxDynImpl>>,
-    #[allow(dead_code)]
     p_duration: Option<Array<f32, Dim<IxDynImpl>>>,
     g: Array<f32, Dim<IxDynImpl>>,
 }
@@ -693,6 +1463,50 @@ impl EncoderOutputs {
             g,
         })
     }
+    /// Number of mel frames produced by the first `num_ids` input ids,
+    /// or `None` if the encoder didn't output predicted durations
+    fn num_frames_for_ids(&self, num_ids: usize) -> Option<usize> {
+        let p_duration = self.p_duration.as_ref()?;
+        let num_frames: f32 = p_duration.iter().take(num_ids).map(|d| d.ceil()).sum();
+        Some((num_frames as usize).min(self.z.shape()[2]))
+    }
+    /// Number of mel frames produced by each input id
+    fn frames_per_id(&self) -> Option<Vec<usize>> {
+        let p_duration = self.p_duration.as_ref()?;
+        Some(Vec::from_iter(
+            p_duration.iter().map(|d| d.ceil().max(0.0) as usize),
+        ))
+    }
+    /// Stretch the frames of every input id by its scale. Frames are repeated or
+    /// dropped, so a stretched phoneme holds its sound rather than being re-predicted.
+    fn scale_durations(&mut self, frames_per_id: &[usize], scales: &[f32]) {
+        let num_frames = self.z.shape()[2];
+        if num_frames == 0 {
+            return;
+        }
+        let mut indices = Vec::new();
+        let mut start = 0;
+        for (&num_id_frames, scale) in frames_per_id.iter().zip(scales) {
+            let num_scaled = (num_id_frames as f32 * scale.max(0.0)).round() as usize;
+            indices.extend(
+                (0..num_scaled)
+                    .map(|i| (start + i * num_id_frames / num_scaled).min(num_frames - 1)),
+            );
+            start += num_id_frames;
+        }
+        self.z = self.z.select(Axis(2), &indices);
+        self.y_mask = self.y_mask.select(Axis(2), &indices);
+        if let Some(p_duration) = self.p_duration.as_mut() {
+            for (duration, scale) in p_duration.iter_mut().zip(scales) {
+                *duration = (duration.ceil() * scale.max(0.0)).round();
+            }
+        }
+    }
+    fn skip_frames(&mut self, num_frames: usize) {
+        let frames = ndarray::Slice::from(num_frames..);
+        self.z = self.z.slice_axis(Axis(2), frames).to_owned();
+        self.y_mask = self.y_mask.slice_axis(Axis(2), frames).to_owned();
+    }
     fn infer_decoder(&self, session: &mut Session) -> PiperResult<AudioSamples> {
         let outputs = {
             let mut inputs = vec![
@@ -725,7 +1539,7 @@ impl EncoderOutputs {
 }
 
 struct SpeechStreamer {
-    decoder_model: Arc<Mutex<Session>>,
+    decoder_model: Arc<SessionPool>,
     encoder_outputs: EncoderOutputs,
     mel_chunker: AdaptiveMelChunker,
     one_shot: bool,
@@ -733,15 +1547,17 @@ struct SpeechStreamer {
 
 impl SpeechStreamer {
     fn new(
-        decoder_model: Arc<Mutex<Session>>,
+        decoder_model: Arc<SessionPool>,
         encoder_outputs: EncoderOutputs,
         chunk_size: usize,
+        steady_chunk_size: Option<usize>,
         chunk_padding: usize,
     ) -> Self {
         let num_frames = encoder_outputs.z.shape()[2];
         let mel_chunker = AdaptiveMelChunker::new(
             num_frames as isize,
             chunk_size as isize,
+            steady_chunk_size,
             chunk_padding as isize,
         );
         let one_shot = num_frames <= (chunk_size * 2 + (chunk_padding * 2));
@@ -760,7 +1576,7 @@ impl SpeechStreamer {
         // println!("Mel index: {:?}\nAudio Index: {:?}", mel_index, audio_index);
         let audio = {
             let decoder_model = Arc::clone(&self.decoder_model);
-            let mut session = decoder_model.lock().unwrap();
+            let mut session = decoder_model.get();
             let z_view = self.encoder_outputs.z.view();
             let y_mask_view = self.encoder_outputs.y_mask.view();
             let z_chunk = z_view.slice_axis(Axis(2), mel_index);
@@ -815,7 +1631,7 @@ impl Iterator for SpeechStreamer {
             self.mel_chunker.consume();
             Some(
                 self.encoder_outputs
-                    .infer_decoder(&mut *self.decoder_model.lock().unwrap()),
+                    .infer_decoder(&mut self.decoder_model.get()),
             )
         } else {
             Some(self.synthesize_chunk(mel_index, audio_index))
@@ -826,16 +1642,24 @@ impl Iterator for SpeechStreamer {
 struct AdaptiveMelChunker {
     num_frames: isize,
     chunk_size: usize,
+    /// The size of every chunk after the first, instead of growing them
+    steady_chunk_size: Option<usize>,
     chunk_padding: isize,
     last_end_index: Option<isize>,
     step: usize,
 }
 
 impl AdaptiveMelChunker {
-    fn new(num_frames: isize, chunk_size: isize, chunk_padding: isize) -> Self {
+    fn new(
+        num_frames: isize,
+        chunk_size: isize,
+        steady_chunk_size: Option<usize>,
+        chunk_padding: isize,
+    ) -> Self {
         Self {
             num_frames,
             chunk_size: chunk_size as usize,
+            steady_chunk_size,
             chunk_padding,
             last_end_index: Some(0),
             step: 1,
@@ -851,7 +1675,11 @@ impl Iterator for AdaptiveMelChunker {
 
     fn next(&mut self) -> Option<Self::Item> {
         let last_index = self.last_end_index?;
-        let chunk_size = (self.chunk_size * self.step).min(MAX_CHUNK_SIZE);
+        let chunk_size = match self.steady_chunk_size {
+            Some(steady_chunk_size) if self.step > 1 => steady_chunk_size,
+            _ => self.chunk_size * self.step,
+        }
+        .min(MAX_CHUNK_SIZE);
         let (start_index, end_index): (isize, Option<isize>);
         let (start_padding, end_padding): (isize, Option<isize>);
         if last_index == 0 {
@@ -873,7 +1701,11 @@ impl Iterator for AdaptiveMelChunker {
         self.step += 1;
         self.last_end_index = end_index;
         let chunk_index = ndarray::Slice::new(start_index, end_index, 1);
-        let audio_index = ndarray::Slice::new(start_padding * 256, end_padding.map(|i| i * 256), 1);
+        let audio_index = ndarray::Slice::new(
+            start_padding * SAMPLES_PER_FRAME as isize,
+            end_padding.map(|i| i * SAMPLES_PER_FRAME as isize),
+            1,
+        );
         Some((chunk_index, audio_index))
     }
 }
@@ -0,0 +1,404 @@
+//! A fake `PiperModel` for testing code built on `PiperSpeechSynthesizer`
+//! without loading an ONNX model.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::core::{
+    Audio, AudioInfo, AudioSamples, AudioStreamIterator, ChunkSchedule, PhonemeChunkIterator,
+    PhonemeSpans, PhonemeTiming, Phonemes, PiperAudioResult, PiperError, PiperModel, PiperResult,
+};
+use crate::{text, PiperSynthesisConfig, SAMPLES_PER_FRAME};
+
+/// Speaks every sentence as a sine tone, `samples_per_phoneme` samples per
+/// character. The text itself is used as the phonemes.
+///
+/// Each speaker gets its own pitch, and `PiperSynthesisConfig::length_scale`
+/// stretches the tone, so the output is deterministic but still reflects the
+/// synthesis settings.
+pub struct MockPiperModel {
+    sample_rate: usize,
+    samples_per_phoneme: usize,
+    max_phoneme_length: Option<usize>,
+    language: Option<String>,
+    speakers: HashMap<i64, String>,
+    synth_config: RwLock<PiperSynthesisConfig>,
+    fail_phonemization: AtomicBool,
+    fail_synthesis: AtomicBool,
+    unpronounceable_words: Vec<String>,
+    num_synthesized: AtomicUsize,
+    synthesis_delay: Duration,
+    panic_synthesis: AtomicBool,
+}
+
+impl Default for MockPiperModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockPiperModel {
+    /// A single speaker, 22050 Hz model that speaks 10ms per phoneme
+    pub fn new() -> Self {
+        Self {
+            sample_rate: 22050,
+            samples_per_phoneme: 220,
+            max_phoneme_length: None,
+            language: None,
+            speakers: HashMap::new(),
+            synth_config: RwLock::new(PiperSynthesisConfig {
+                speaker: None,
+                noise_scale: 0.667,
+                length_scale: 1.0,
+                noise_w: 0.8,
+            }),
+            fail_phonemization: AtomicBool::new(false),
+            fail_synthesis: AtomicBool::new(false),
+            unpronounceable_words: Vec::new(),
+            num_synthesized: AtomicUsize::new(0),
+            synthesis_delay: Duration::ZERO,
+            panic_synthesis: AtomicBool::new(false),
+        }
+    }
+    pub fn with_sample_rate(mut self, sample_rate: usize) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+    pub fn with_samples_per_phoneme(mut self, samples_per_phoneme: usize) -> Self {
+        self.samples_per_phoneme = samples_per_phoneme;
+        self
+    }
+    pub fn with_max_phoneme_length(mut self, max_phoneme_length: usize) -> Self {
+        self.max_phoneme_length = Some(max_phoneme_length);
+        self
+    }
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+    pub fn with_speakers(mut self, speakers: HashMap<i64, String>) -> Self {
+        self.speakers = speakers;
+        self
+    }
+    /// Make phonemization fail for all text containing one of `words`
+    pub fn with_unpronounceable_words(mut self, words: Vec<String>) -> Self {
+        self.unpronounceable_words = words;
+        self
+    }
+    /// Take `delay` to synthesize each sentence
+    pub fn with_synthesis_delay(mut self, delay: Duration) -> Self {
+        self.synthesis_delay = delay;
+        self
+    }
+    /// Make phonemization fail until this is set back to `false`
+    pub fn set_fail_phonemization(&self, fail: bool) {
+        self.fail_phonemization.store(fail, Ordering::Relaxed);
+    }
+    /// Make synthesis fail until this is set back to `false`
+    pub fn set_fail_synthesis(&self, fail: bool) {
+        self.fail_synthesis.store(fail, Ordering::Relaxed);
+    }
+    /// Make synthesis panic until this is set back to `false`
+    pub fn set_panic_synthesis(&self, panic: bool) {
+        self.panic_synthesis.store(panic, Ordering::Relaxed);
+    }
+    /// How many sentences synthesis was started for
+    pub fn num_synthesized(&self) -> usize {
+        self.num_synthesized.load(Ordering::Relaxed)
+    }
+    /// The number of samples spoken for `num_phonemes` phonemes with the default config
+    pub fn num_samples_for(&self, num_phonemes: usize) -> usize {
+        num_phonemes * self.samples_per_phoneme
+    }
+
+    fn tone(&self, num_phonemes: f32, config: &PiperSynthesisConfig) -> PiperResult<Vec<f32>> {
+        self.num_synthesized.fetch_add(1, Ordering::Relaxed);
+        std::thread::sleep(self.synthesis_delay);
+        if self.panic_synthesis.load(Ordering::Relaxed) {
+            panic!("Mock synthesis panic");
+        }
+        if self.fail_synthesis.load(Ordering::Relaxed) {
+            return Err(PiperError::OperationError(
+                "Mock synthesis failure".to_string(),
+            ));
+        }
+        let num_samples =
+            (num_phonemes * self.samples_per_phoneme as f32 * config.length_scale).round() as usize;
+        let frequency = 220.0 * (1.0 + config.speaker.unwrap_or(0) as f32 * 0.25);
+        let step = TAU * frequency / self.sample_rate as f32;
+        Ok(Vec::from_iter(
+            (0..num_samples).map(|i| (i as f32 * step).sin() * 0.5),
+        ))
+    }
+    fn speak(&self, num_phonemes: usize, config: &PiperSynthesisConfig) -> PiperAudioResult {
+        Ok(Audio::new(
+            self.tone(num_phonemes as f32, config)?.into(),
+            self.sample_rate,
+            Some(0.0),
+        ))
+    }
+}
+
+impl PiperModel for MockPiperModel {
+    fn audio_output_info(&self) -> AudioInfo {
+        AudioInfo {
+            sample_rate: self.sample_rate,
+            num_channels: 1,
+            sample_width: 2,
+            native_sample_rate: self.sample_rate,
+        }
+    }
+    fn max_phoneme_length(&self) -> Option<usize> {
+        self.max_phoneme_length
+    }
+    fn phonemize_text(&self, text: &str) -> PiperResult<Phonemes> {
+        if self.fail_phonemization.load(Ordering::Relaxed) {
+            return Err(PiperError::PhonemizationError(
+                "Mock phonemization failure".to_string(),
+            ));
+        }
+        let is_unpronounceable = text.split_whitespace().any(|word| {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+            self.unpronounceable_words.iter().any(|w| w == word)
+        });
+        if is_unpronounceable {
+            return Err(PiperError::PhonemizationError(format!(
+                "Mock phonemization failure for `{}`",
+                text
+            )));
+        }
+        Ok(text::split_sentences(text).into())
+    }
+    fn speak_batch(&self, phoneme_batches: Vec<String>) -> PiperResult<Vec<Audio>> {
+        phoneme_batches
+            .into_iter()
+            .map(|phonemes| self.speak_one_sentence(phonemes))
+            .collect()
+    }
+    fn speak_one_sentence(&self, phonemes: String) -> PiperAudioResult {
+        self.speak(phonemes.chars().count(), &self.synth_config.read().unwrap())
+    }
+    fn speak_one_sentence_with(
+        &self,
+        phonemes: String,
+        synth_config: &dyn Any,
+    ) -> PiperAudioResult {
+        let Some(config) = synth_config.downcast_ref::<PiperSynthesisConfig>() else {
+            return Err(PiperError::OperationError(
+                "Invalid configuration for Mock Model".to_string(),
+            ));
+        };
+        self.speak(phonemes.chars().count(), config)
+    }
+    fn speak_one_sentence_as(&self, phonemes: String, speaker: i64) -> PiperAudioResult {
+        if !self.speakers.contains_key(&speaker) {
+            return Err(PiperError::OperationError(format!(
+                "Invalid speaker id `{}`",
+                speaker
+            )));
+        }
+        let mut config = self.synth_config.read().unwrap().clone();
+        config.speaker = Some(speaker);
+        self.speak(phonemes.chars().count(), &config)
+    }
+    /// A single value: the speaker id, which picks the pitch of the tone
+    fn speaker_embedding(&self, sid: i64) -> PiperResult<Vec<f32>> {
+        if !self.speakers.contains_key(&sid) {
+            return Err(PiperError::OperationError(format!(
+                "Invalid speaker id `{}`",
+                sid
+            )));
+        }
+        Ok(vec![sid as f32])
+    }
+    fn speak_with_embedding(&self, phonemes: String, embedding: &[f32]) -> PiperAudioResult {
+        let &[speaker] = embedding else {
+            return Err(PiperError::OperationError(format!(
+                "Expected a speaker embedding of 1 value, got {}",
+                embedding.len()
+            )));
+        };
+        let mut config = self.synth_config.read().unwrap().clone();
+        config.speaker = Some(speaker.round() as i64);
+        self.speak(phonemes.chars().count(), &config)
+    }
+    fn speak_input_ids(&self, input_ids: Vec<i64>) -> PiperAudioResult {
+        // ids are laid out as `bos, (phoneme, pad)*, eos`
+        let num_phonemes = input_ids.len().saturating_sub(2) / 2;
+        self.speak(num_phonemes, &self.synth_config.read().unwrap())
+    }
+    fn speak_with_durations(
+        &self,
+        phonemes: String,
+        duration_scales: Vec<f32>,
+    ) -> PiperAudioResult {
+        if duration_scales.len() != phonemes.chars().count() {
+            return Err(PiperError::OperationError(
+                "Expected one duration scale per phoneme".to_string(),
+            ));
+        }
+        let num_phonemes = duration_scales.iter().map(|scale| scale.max(0.0)).sum();
+        Ok(Audio::new(
+            self.tone(num_phonemes, &self.synth_config.read().unwrap())?
+                .into(),
+            self.sample_rate,
+            Some(0.0),
+        ))
+    }
+    fn get_default_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
+        Ok(Box::new(PiperSynthesisConfig {
+            speaker: None,
+            noise_scale: 0.667,
+            length_scale: 1.0,
+            noise_w: 0.8,
+        }))
+    }
+    fn get_fallback_synthesis_config(&self) -> PiperResult<Box<dyn Any>> {
+        Ok(Box::new(self.synth_config.read().unwrap().clone()))
+    }
+    fn set_fallback_synthesis_config(&self, synthesis_config: &dyn Any) -> PiperResult<()> {
+        match synthesis_config.downcast_ref::<PiperSynthesisConfig>() {
+            Some(new_config) => {
+                *self.synth_config.write().unwrap() = new_config.clone();
+                Ok(())
+            }
+            None => Err(PiperError::OperationError(
+                "Invalid configuration for Mock Model".to_string(),
+            )),
+        }
+    }
+    fn get_language(&self) -> PiperResult<Option<String>> {
+        Ok(self.language.clone())
+    }
+    fn get_speakers(&self) -> PiperResult<Option<&HashMap<i64, String>>> {
+        Ok(Some(&self.speakers))
+    }
+    fn set_speaker(&self, sid: i64) -> Option<PiperError> {
+        if !self.speakers.contains_key(&sid) {
+            return Some(PiperError::OperationError(format!(
+                "Invalid speaker id `{}`",
+                sid
+            )));
+        }
+        self.synth_config.write().unwrap().speaker = Some(sid);
+        None
+    }
+    /// Every phoneme lasts as long as it's spoken, between zero length markers
+    fn speak_with_timings(&self, phonemes: String) -> PiperResult<(Audio, Vec<PhonemeTiming>)> {
+        let timings = self.phoneme_timings(phonemes.clone())?;
+        Ok((self.speak_one_sentence(phonemes)?, timings))
+    }
+    fn phoneme_timings(&self, phonemes: String) -> PiperResult<Vec<PhonemeTiming>> {
+        let length_scale = self.synth_config.read().unwrap().length_scale;
+        let duration_ms =
+            self.samples_per_phoneme as f32 * length_scale * 1000.0 / self.sample_rate as f32;
+        let mut timings = vec![PhonemeTiming {
+            phoneme: '^',
+            start_ms: 0.0,
+            duration_ms: 0.0,
+        }];
+        timings.extend(
+            phonemes
+                .chars()
+                .enumerate()
+                .map(|(index, phoneme)| PhonemeTiming {
+                    phoneme,
+                    start_ms: index as f32 * duration_ms,
+                    duration_ms,
+                }),
+        );
+        timings.push(PhonemeTiming {
+            phoneme: '$',
+            start_ms: phonemes.chars().count() as f32 * duration_ms,
+            duration_ms: 0.0,
+        });
+        Ok(timings)
+    }
+    fn supports_streaming_output(&self) -> bool {
+        true
+    }
+    fn stream_synthesis(
+        &self,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let schedule = ChunkSchedule {
+            first_chunk_size: chunk_size,
+            steady_chunk_size: chunk_size,
+        };
+        self.stream_synthesis_scheduled(String::new(), phonemes, schedule, chunk_padding)
+    }
+    fn stream_synthesis_with_phonemes(
+        &self,
+        phonemes: String,
+        chunk_size: usize,
+        chunk_padding: usize,
+    ) -> PiperResult<PhonemeChunkIterator<'_>> {
+        let length_scale = self.synth_config.read().unwrap().length_scale;
+        let num_samples = (self.samples_per_phoneme as f32 * length_scale).round() as usize;
+        let spans = PhonemeSpans::new(phonemes.chars().map(|c| (Some(c), num_samples)));
+        Ok(spans.label_chunks(self.stream_synthesis(phonemes, chunk_size, chunk_padding)?))
+    }
+    /// Chunks of `schedule`'s sizes, at `SAMPLES_PER_FRAME` samples per frame
+    fn stream_synthesis_scheduled(
+        &self,
+        #[allow(unused_variables)] context: String,
+        phonemes: String,
+        schedule: ChunkSchedule,
+        #[allow(unused_variables)] chunk_padding: usize,
+    ) -> PiperResult<AudioStreamIterator<'_>> {
+        let mut samples = self.tone(
+            phonemes.chars().count() as f32,
+            &self.synth_config.read().unwrap(),
+        )?;
+        let first_len = (schedule.first_chunk_size * SAMPLES_PER_FRAME).max(1);
+        let steady_len = (schedule.steady_chunk_size * SAMPLES_PER_FRAME).max(1);
+        let rest = samples.split_off(first_len.min(samples.len()));
+        let mut chunks =
+            Vec::from_iter((!samples.is_empty()).then(|| Ok(AudioSamples::from(samples))));
+        chunks.extend(
+            rest.chunks(steady_len)
+                .map(|chunk| Ok(AudioSamples::from(chunk.to_vec()))),
+        );
+        Ok(Box::new(chunks.into_iter()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synth::PiperSpeechSynthesizer;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_mock_synthesis() {
+        let model = Arc::new(MockPiperModel::new());
+        let synth = PiperSpeechSynthesizer::new(model.clone()).unwrap();
+        let audio = synth.synthesize("Hi. Hello.".to_string(), None).unwrap();
+        assert_eq!(audio.len(), model.num_samples_for("Hi.Hello.".len()));
+        let streamed = synth
+            .synthesize_streamed("Hi. Hello.".to_string(), None, 1, 0)
+            .unwrap()
+            .collect_all()
+            .unwrap();
+        assert_eq!(streamed.samples.as_slice(), audio.samples.as_slice());
+    }
+
+    #[test]
+    fn test_mock_failures() {
+        let model = Arc::new(MockPiperModel::new());
+        let synth = PiperSpeechSynthesizer::new(model.clone()).unwrap();
+        model.set_fail_phonemization(true);
+        assert!(synth.synthesize("Hi.".to_string(), None).is_err());
+        model.set_fail_phonemization(false);
+        model.set_fail_synthesis(true);
+        assert!(synth.synthesize("Hi.".to_string(), None).is_err());
+        model.set_fail_synthesis(false);
+        assert!(synth.synthesize("Hi.".to_string(), None).is_ok());
+    }
+}
@@ -0,0 +1,674 @@
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Sentence-final punctuation, including the Arabic, Urdu, Devanagari and CJK forms
+const SENTENCE_TERMINATORS: [char; 10] = ['.', '!', '?', '…', '؟', '۔', '।', '。', '！', '？'];
+
+#[inline(always)]
+fn is_terminator(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| SENTENCE_TERMINATORS.contains(&c))
+}
+
+#[inline(always)]
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+/// Opening and closing quotes and brackets. Single quotes are left out, since
+/// they double as apostrophes.
+const ENCLOSING_PAIRS: [(char, char); 8] = [
+    ('"', '"'),
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('“', '”'),
+    ('«', '»'),
+    ('「', '」'),
+    ('『', '』'),
+];
+
+#[inline(always)]
+fn is_closing(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .next()
+        .is_some_and(|c| ENCLOSING_PAIRS.iter().any(|&(_, close)| close == c))
+}
+
+/// For every grapheme, whether it sits inside a balanced pair of quotes or
+/// brackets. Openers that are never closed, and closers that were never opened,
+/// are ignored.
+fn enclosed_graphemes(graphemes: &[&str]) -> Vec<bool> {
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut depth_change = vec![0i32; graphemes.len() + 1];
+    for (index, grapheme) in graphemes.iter().enumerate() {
+        let Some(c) = grapheme.chars().next() else {
+            continue;
+        };
+        let Some(&(opener, closer)) = ENCLOSING_PAIRS
+            .iter()
+            .find(|&&(opener, closer)| opener == c || closer == c)
+        else {
+            continue;
+        };
+        let matching = open.iter().rposition(|&(o, _)| o == opener);
+        match matching {
+            Some(position) if c == closer => {
+                // openers left unclosed inside this pair are dropped
+                let (_, start) = open[position];
+                open.truncate(position);
+                depth_change[start + 1] += 1;
+                depth_change[index] -= 1;
+            }
+            _ if c == opener => open.push((opener, index)),
+            _ => {}
+        }
+    }
+    let mut depth = 0;
+    Vec::from_iter(depth_change[..graphemes.len()].iter().map(|change| {
+        depth += change;
+        depth > 0
+    }))
+}
+
+/// Split `text` into sentences.
+///
+/// Every line is split after a run of sentence terminators that is followed by
+/// whitespace or the end of the line. Terminators inside balanced quotes or
+/// brackets don't split, and closing quotes or brackets right after a terminator
+/// stay with its sentence, as does a lowercase word after them. The text is
+/// walked by grapheme cluster, so combining marks (e.g. Arabic harakat or Hebrew
+/// niqqud) always stay attached to their base character, and no codepoints other
+/// than the surrounding whitespace of each sentence are dropped.
+pub(crate) fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let graphemes = Vec::from_iter(line.graphemes(true));
+        let enclosed = enclosed_graphemes(&graphemes);
+        let mut current = String::new();
+        let mut index = 0;
+        while index < graphemes.len() {
+            let grapheme = graphemes[index];
+            current.push_str(grapheme);
+            index += 1;
+            if !is_terminator(grapheme) {
+                continue;
+            }
+            while index < graphemes.len()
+                && (is_terminator(graphemes[index]) || is_closing(graphemes[index]))
+            {
+                current.push_str(graphemes[index]);
+                index += 1;
+            }
+            let at_boundary = graphemes.get(index).is_none_or(|g| is_whitespace(g));
+            let is_enclosed = enclosed.get(index).is_some_and(|&enclosed| enclosed);
+            // a quote followed by a lowercase word, as in `"Why?" she asked.`
+            let continues = is_closing(graphemes[index - 1])
+                && graphemes[index..]
+                    .iter()
+                    .find(|g| !is_whitespace(g))
+                    .and_then(|g| g.chars().next())
+                    .is_some_and(char::is_lowercase);
+            if at_boundary && !is_enclosed && !continues {
+                push_sentence(&mut sentences, std::mem::take(&mut current));
+            }
+        }
+        push_sentence(&mut sentences, current);
+    }
+    sentences
+}
+
+#[inline(always)]
+fn push_sentence(sentences: &mut Vec<String>, sentence: String) {
+    let trimmed = sentence.trim();
+    if !trimmed.is_empty() {
+        sentences.push(trimmed.to_string());
+    }
+}
+
+/// Split `text` into sentences for a character-input model, with every run of
+/// whitespace collapsed into a single space
+pub(crate) fn split_character_sentences(text: &str) -> Vec<String> {
+    Vec::from_iter(
+        split_sentences(text)
+            .into_iter()
+            .map(|sentence| sentence.split_whitespace().collect::<Vec<_>>().join(" ")),
+    )
+}
+
+/// Phonemes that mark a good place to split an over-long phoneme sequence
+const PHONEME_BREAKS: [char; 6] = [',', ';', ':', '.', '!', '?'];
+
+/// Split `phonemes` into chunks of at most `max_len` phonemes, preferring to break
+/// after punctuation, then at word boundaries, and only then mid-word.
+pub(crate) fn split_phonemes(phonemes: &str, max_len: usize) -> Vec<String> {
+    let max_len = max_len.max(1);
+    let mut chunks = Vec::new();
+    let mut remaining: Vec<char> = phonemes.chars().collect();
+    while remaining.len() > max_len {
+        let window = &remaining[..max_len];
+        let split_at = window
+            .iter()
+            .rposition(|c| PHONEME_BREAKS.contains(c))
+            .or_else(|| window.iter().rposition(|c| c.is_whitespace()))
+            .map(|i| i + 1)
+            .unwrap_or(max_len);
+        let rest = remaining.split_off(split_at);
+        push_sentence(&mut chunks, String::from_iter(remaining));
+        remaining = rest;
+    }
+    push_sentence(&mut chunks, String::from_iter(remaining));
+    chunks
+}
+
+const SPEAKER_TAG_PREFIX: &str = "[spk:";
+
+/// The inline tag that selects speaker `sid`
+pub(crate) fn speaker_tag(sid: i64) -> String {
+    format!("{}{}]", SPEAKER_TAG_PREFIX, sid)
+}
+
+/// A run of text spoken by a single speaker
+pub(crate) struct SpeakerSegment {
+    /// The speaker id, and the byte offset of the tag that selected it
+    pub speaker: Option<(i64, usize)>,
+    pub text: String,
+}
+
+/// Split `text` at inline `[spk:N]` tags. Text before the first tag has no speaker.
+pub(crate) fn split_speaker_tags(text: &str) -> Result<Vec<SpeakerSegment>, String> {
+    let mut segments = Vec::new();
+    let mut speaker = None;
+    let mut rest = text;
+    let mut offset = 0;
+    let mut push_segment = |speaker, text: &str| {
+        if !text.trim().is_empty() {
+            segments.push(SpeakerSegment {
+                speaker,
+                text: text.to_string(),
+            });
+        }
+    };
+    while let Some(start) = rest.find(SPEAKER_TAG_PREFIX) {
+        push_segment(speaker, &rest[..start]);
+        let tag_offset = offset + start;
+        let tag_body = &rest[start + SPEAKER_TAG_PREFIX.len()..];
+        let Some(end) = tag_body.find(']') else {
+            return Err(format!("Unterminated speaker tag at byte {}", tag_offset));
+        };
+        let id = tag_body[..end].trim();
+        let Ok(sid) = id.parse::<i64>() else {
+            return Err(format!(
+                "Invalid speaker id `{}` in tag at byte {}",
+                id, tag_offset
+            ));
+        };
+        speaker = Some((sid, tag_offset));
+        let consumed = start + SPEAKER_TAG_PREFIX.len() + end + 1;
+        offset += consumed;
+        rest = &rest[consumed..];
+    }
+    push_segment(speaker, rest);
+    Ok(segments)
+}
+
+/// `word` with its letters and digits apart, so the phonemizer reads them out one
+/// by one, e.g. `X K C D` for `XKCD!`
+pub(crate) fn spell_out(word: &str) -> String {
+    let mut letters = String::with_capacity(word.len() * 2);
+    for c in word.chars().filter(|c| c.is_alphanumeric()) {
+        if !letters.is_empty() {
+            letters.push(' ');
+        }
+        letters.push(c);
+    }
+    letters
+}
+
+const EMPHASIS_OPEN_TAG: &str = "<emphasis";
+const EMPHASIS_CLOSE_TAG: &str = "</emphasis>";
+
+/// A run of text, inside an `<emphasis>` span or not
+#[derive(Debug, PartialEq)]
+pub(crate) struct EmphasisSpan {
+    /// The `level` attribute of the span's tag, empty without one, or `None` for
+    /// text outside of spans
+    pub level: Option<String>,
+    pub text: String,
+}
+
+/// Split `text` at `<emphasis level="...">...</emphasis>` spans. Spans don't nest,
+/// and an unterminated span runs to the end of the text.
+pub(crate) fn split_emphasis(text: &str) -> Vec<EmphasisSpan> {
+    let mut spans = Vec::new();
+    let mut push_span = |level, text: &str| {
+        if !text.trim().is_empty() {
+            spans.push(EmphasisSpan {
+                level,
+                text: text.to_string(),
+            });
+        }
+    };
+    let mut rest = text;
+    while let Some(start) = find_emphasis_tag(rest) {
+        let tag = &rest[start + EMPHASIS_OPEN_TAG.len()..];
+        let Some(tag_end) = tag.find('>') else {
+            break;
+        };
+        push_span(None, &rest[..start]);
+        let body = &tag[tag_end + 1..];
+        let end = body.find(EMPHASIS_CLOSE_TAG).unwrap_or(body.len());
+        push_span(Some(emphasis_level(&tag[..tag_end])), &body[..end]);
+        rest = &body[(end + EMPHASIS_CLOSE_TAG.len()).min(body.len())..];
+    }
+    push_span(None, rest);
+    spans
+}
+
+fn find_emphasis_tag(text: &str) -> Option<usize> {
+    text.match_indices(EMPHASIS_OPEN_TAG)
+        .map(|(start, _)| start)
+        .find(|start| {
+            text[start + EMPHASIS_OPEN_TAG.len()..]
+                .starts_with(|c: char| c == '>' || c.is_whitespace())
+        })
+}
+
+/// The value of the `level` attribute among `attributes`, or an empty string
+fn emphasis_level(attributes: &str) -> String {
+    let level = attributes.split_once("level").and_then(|(_, value)| {
+        let value = value.trim_start().strip_prefix('=')?.trim_start();
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        value[1..].split(quote).next()
+    });
+    level.unwrap_or_default().to_string()
+}
+
+const DIGITS_OPEN_TAG: &str = "<digits>";
+const DIGITS_CLOSE_TAG: &str = "</digits>";
+
+/// Have the digits of `<digits>...</digits>` spans of `text` read one by one, or
+/// of all of it if `all_digits`, and remove the span tags. An unterminated span
+/// runs to the end of the text.
+pub(crate) fn normalize_digits(text: &str, all_digits: bool) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(DIGITS_OPEN_TAG) {
+        push_digits(&mut normalized, &rest[..start], all_digits);
+        let span = &rest[start + DIGITS_OPEN_TAG.len()..];
+        let end = span.find(DIGITS_CLOSE_TAG).unwrap_or(span.len());
+        push_digits(&mut normalized, &span[..end], true);
+        rest = &span[(end + DIGITS_CLOSE_TAG.len()).min(span.len())..];
+    }
+    push_digits(&mut normalized, rest, all_digits);
+    normalized
+}
+
+/// Append `text` to `out`, with spaces between consecutive digits if `spell_out`,
+/// which phonemizers read as separate numbers
+fn push_digits(out: &mut String, text: &str, spell_out: bool) {
+    if !spell_out {
+        out.push_str(text);
+        return;
+    }
+    let mut after_digit = false;
+    for c in text.chars() {
+        if after_digit && c.is_ascii_digit() {
+            out.push(' ');
+        }
+        out.push(c);
+        after_digit = c.is_ascii_digit();
+    }
+}
+
+/// How long a `...` pause marker lasts
+pub(crate) const ELLIPSIS_PAUSE_MS: u32 = 500;
+
+/// A run of text, and the pause that follows it
+#[derive(Debug, PartialEq)]
+pub(crate) struct PausedText {
+    pub text: String,
+    pub pause_ms: u32,
+}
+
+/// The index of the `>` closing the `<...>` marker at the start of `text`, if it
+/// is one. Markers don't contain whitespace.
+fn markup_end(text: &str) -> Option<usize> {
+    let body = text.strip_prefix('<')?;
+    let end = body.find(|c: char| c == '<' || c == '>' || c.is_whitespace())?;
+    body[end..].starts_with('>').then_some(end + 1)
+}
+
+/// Split `text` at inline pause markers: `...` or `…` for `ELLIPSIS_PAUSE_MS`, and
+/// `<pN>` for N milliseconds. Other `<...>` markers are kept as text, or removed
+/// if `strip_unknown`.
+pub(crate) fn split_pause_markers(text: &str, strip_unknown: bool) -> Vec<PausedText> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['.', '…', '<']) {
+        current.push_str(&rest[..start]);
+        let marker = &rest[start..];
+        let num_dots = marker.len() - marker.trim_start_matches('.').len();
+        let (pause_ms, len) = if num_dots >= 3 {
+            (Some(ELLIPSIS_PAUSE_MS), num_dots)
+        } else if marker.starts_with('…') {
+            (Some(ELLIPSIS_PAUSE_MS), '…'.len_utf8())
+        } else if let Some(end) = markup_end(marker) {
+            let pause_ms = marker[1..end]
+                .strip_prefix('p')
+                .and_then(|ms| ms.parse().ok());
+            if pause_ms.is_none() && !strip_unknown {
+                current.push_str(&marker[..=end]);
+            }
+            (pause_ms, end + 1)
+        } else {
+            // a lone `<`, or fewer than three dots
+            let len = num_dots.max(1);
+            current.push_str(&marker[..len]);
+            (None, len)
+        };
+        if let Some(pause_ms) = pause_ms {
+            parts.push(PausedText {
+                text: std::mem::take(&mut current),
+                pause_ms,
+            });
+        }
+        rest = &marker[len..];
+    }
+    current.push_str(rest);
+    if !current.is_empty() || parts.is_empty() {
+        parts.push(PausedText {
+            text: current,
+            pause_ms: 0,
+        });
+    }
+    parts
+}
+
+/// The writing system of a character, as far as the phonemizer cares
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Script {
+    /// Digits, punctuation, whitespace and combining marks, fine in any language
+    Common,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    Kana,
+    Han,
+    /// Emoji, pictographs, and every script not listed
+    Other,
+}
+
+fn script_of(c: char) -> Script {
+    match c {
+        'a'..='z' | 'A'..='Z' => Script::Latin,
+        // emoji joiners, variation selectors and skin tones belong to their emoji
+        '\u{200D}' | '\u{FE00}'..='\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}' => Script::Other,
+        _ if c.is_ascii() || c.is_whitespace() || c.is_numeric() => Script::Common,
+        '\u{00A0}'..='\u{00BF}'
+        | '\u{0300}'..='\u{036F}'
+        | '\u{2000}'..='\u{206F}'
+        | '\u{3000}'..='\u{303F}'
+        | '\u{FF00}'..='\u{FF20}' => Script::Common,
+        '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' => Script::Latin,
+        '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Script::Greek,
+        '\u{0400}'..='\u{052F}' => Script::Cyrillic,
+        '\u{0530}'..='\u{058F}' => Script::Armenian,
+        '\u{0590}'..='\u{05FF}' => Script::Hebrew,
+        '\u{0600}'..='\u{06FF}' | '\u{0750}'..='\u{077F}' => Script::Arabic,
+        '\u{0900}'..='\u{097F}' => Script::Devanagari,
+        '\u{0980}'..='\u{09FF}' => Script::Bengali,
+        '\u{0E00}'..='\u{0E7F}' => Script::Thai,
+        '\u{10A0}'..='\u{10FF}' => Script::Georgian,
+        '\u{1100}'..='\u{11FF}' | '\u{AC00}'..='\u{D7AF}' => Script::Hangul,
+        '\u{3040}'..='\u{30FF}' => Script::Kana,
+        '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' => Script::Han,
+        _ => Script::Other,
+    }
+}
+
+/// The scripts written in `language` (e.g. `ru` or `en-us`), besides Latin
+fn language_scripts(language: &str) -> &'static [Script] {
+    let code = language.split(['-', '_']).next().unwrap_or_default();
+    match code.to_ascii_lowercase().as_str() {
+        "el" | "grc" => &[Script::Greek],
+        "ru" | "uk" | "be" | "bg" | "mk" | "sr" | "kk" | "ky" | "tt" | "ba" | "mn" => {
+            &[Script::Cyrillic]
+        }
+        "hy" => &[Script::Armenian],
+        "he" => &[Script::Hebrew],
+        "ar" | "fa" | "ur" | "ps" | "ug" => &[Script::Arabic],
+        "hi" | "mr" | "ne" | "sa" => &[Script::Devanagari],
+        "bn" | "as" => &[Script::Bengali],
+        "th" => &[Script::Thai],
+        "ka" => &[Script::Georgian],
+        "ko" => &[Script::Hangul],
+        "ja" => &[Script::Kana, Script::Han],
+        "zh" | "cmn" | "yue" => &[Script::Han],
+        _ => &[],
+    }
+}
+
+/// The byte ranges of the runs of `text` in scripts the phonemizer can't read for
+/// `language`. Latin is assumed readable in every language. Without a language,
+/// only emoji and the scripts not told apart are reported.
+pub(crate) fn unsupported_script_runs(text: &str, language: Option<&str>) -> Vec<Range<usize>> {
+    let is_supported = |script| match language {
+        _ if script == Script::Common || script == Script::Latin => true,
+        Some(language) => language_scripts(language).contains(&script),
+        None => script != Script::Other,
+    };
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (start, c) in text.char_indices() {
+        if is_supported(script_of(c)) {
+            continue;
+        }
+        let end = start + c.len_utf8();
+        match runs.last_mut() {
+            Some(run) if run.end == start => run.end = end,
+            _ => runs.push(start..end),
+        }
+    }
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn non_whitespace(text: &str) -> String {
+        String::from_iter(text.chars().filter(|c| !c.is_whitespace()))
+    }
+
+    #[test]
+    fn test_split_latin() {
+        let sentences = split_sentences("Hello there. How are you? Fine!! Pi is 3.14");
+        assert_eq!(
+            sentences,
+            vec!["Hello there.", "How are you?", "Fine!!", "Pi is 3.14"]
+        );
+    }
+
+    #[test]
+    fn test_split_lines() {
+        let sentences = split_sentences("Hello\nThere\n\nAnd welcome");
+        assert_eq!(sentences.len(), 3);
+    }
+
+    #[test]
+    fn test_split_arabic_with_harakat() {
+        let text = "مَرْحَبَاً بِكَ. كَيْفَ حَالُكَ؟ أَنَا بِخَيْرٍ";
+        let sentences = split_sentences(text);
+        assert_eq!(sentences.len(), 3);
+        assert_eq!(sentences[1], "كَيْفَ حَالُكَ؟");
+        assert_eq!(non_whitespace(&sentences.concat()), non_whitespace(text));
+    }
+
+    #[test]
+    fn test_split_hebrew_with_niqqud() {
+        let text = "שָׁלוֹם. מַה שְּׁלוֹמְךָ?";
+        let sentences = split_sentences(text);
+        assert_eq!(sentences, vec!["שָׁלוֹם.", "מַה שְּׁלוֹמְךָ?"]);
+        assert_eq!(non_whitespace(&sentences.concat()), non_whitespace(text));
+    }
+
+    #[test]
+    fn test_combining_mark_after_terminator_is_kept() {
+        // U+0301 (combining acute accent) forms one grapheme with the full stop
+        let text = "First.\u{0301} Second.";
+        let sentences = split_sentences(text);
+        assert_eq!(sentences, vec!["First.\u{0301}", "Second."]);
+    }
+
+    #[test]
+    fn test_split_respects_quotes_and_brackets() {
+        let sentences = split_sentences(r#"He said "Stop. Now!" Then he left (see p. 3). Done."#);
+        assert_eq!(
+            sentences,
+            vec![
+                r#"He said "Stop. Now!""#,
+                "Then he left (see p. 3).",
+                "Done."
+            ]
+        );
+        let sentences = split_sentences(r#""Wait. Really?" she asked. «Oui. Non.» Fin."#);
+        assert_eq!(
+            sentences,
+            vec![r#""Wait. Really?" she asked."#, "«Oui. Non.»", "Fin."]
+        );
+        // closing quotes right after a terminator stay with the sentence
+        let sentences = split_sentences(r#"She wrote: "I came. (I saw.)" Next."#);
+        assert_eq!(sentences, vec![r#"She wrote: "I came. (I saw.)""#, "Next."]);
+    }
+
+    #[test]
+    fn test_split_unbalanced_quotes_and_brackets() {
+        let sentences = split_sentences(r#"He said "stop. Go now. (Really."#);
+        assert_eq!(sentences, vec![r#"He said "stop."#, "Go now.", "(Really."]);
+        let sentences = split_sentences("Odd) close. Next ( one. [a (b] c. d.");
+        assert_eq!(
+            sentences,
+            vec!["Odd) close.", "Next ( one.", "[a (b] c.", "d."]
+        );
+    }
+
+    #[test]
+    fn test_split_character_sentences() {
+        let sentences = split_character_sentences("Hello \t there.  How   are you?");
+        assert_eq!(sentences, vec!["Hello there.", "How are you?"]);
+    }
+
+    #[test]
+    fn test_split_phonemes() {
+        let chunks = split_phonemes("aa bb, cc dd ee", 9);
+        assert_eq!(chunks, vec!["aa bb,", "cc dd ee"]);
+        let chunks = split_phonemes("aa bb cc dd", 7);
+        assert_eq!(chunks, vec!["aa bb", "cc dd"]);
+        let chunks = split_phonemes("abcdefgh", 3);
+        assert_eq!(chunks, vec!["abc", "def", "gh"]);
+    }
+
+    #[test]
+    fn test_unsupported_script_runs() {
+        let text = "Hi 👋🏽 你好, мир!";
+        let runs = |language| {
+            Vec::from_iter(
+                unsupported_script_runs(text, language)
+                    .into_iter()
+                    .map(|run| &text[run]),
+            )
+        };
+        assert_eq!(runs(Some("en-us")), vec!["👋🏽", "你好", "мир"]);
+        assert_eq!(runs(Some("ru")), vec!["👋🏽", "你好"]);
+        assert_eq!(runs(Some("cmn")), vec!["👋🏽", "мир"]);
+        assert_eq!(runs(None), vec!["👋🏽"]);
+    }
+
+    #[test]
+    fn test_spell_out() {
+        assert_eq!(spell_out("XKCD!"), "X K C D");
+        assert_eq!(spell_out("R2-D2"), "R 2 D 2");
+        assert_eq!(spell_out("..."), "");
+    }
+
+    #[test]
+    fn test_split_emphasis() {
+        let span = |level: Option<&str>, text: &str| EmphasisSpan {
+            level: level.map(str::to_string),
+            text: text.to_string(),
+        };
+        assert_eq!(
+            split_emphasis("I <emphasis level=\"strong\">really</emphasis> mean it. <emphasis>Now"),
+            vec![
+                span(None, "I "),
+                span(Some("strong"), "really"),
+                span(None, " mean it. "),
+                span(Some(""), "Now"),
+            ]
+        );
+        assert_eq!(
+            split_emphasis("<emphasisx>a</emphasis> <emphasis level='reduced'>b"),
+            vec![
+                span(None, "<emphasisx>a</emphasis> "),
+                span(Some("reduced"), "b")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_digits() {
+        let text = "Room 42, code <digits>555-0100</digits>.";
+        assert_eq!(
+            normalize_digits(text, false),
+            "Room 42, code 5 5 5-0 1 0 0."
+        );
+        assert_eq!(
+            normalize_digits(text, true),
+            "Room 4 2, code 5 5 5-0 1 0 0."
+        );
+        assert_eq!(normalize_digits("<digits>12", false), "1 2");
+    }
+
+    #[test]
+    fn test_split_pause_markers() {
+        let part = |text: &str, pause_ms| PausedText {
+            text: text.to_string(),
+            pause_ms,
+        };
+        assert_eq!(
+            split_pause_markers("Wait... so <p250>it's <b>you. Ok.", false),
+            vec![
+                part("Wait", ELLIPSIS_PAUSE_MS),
+                part(" so ", 250),
+                part("it's <b>you. Ok.", 0),
+            ]
+        );
+        assert_eq!(
+            split_pause_markers("a < b…<pause><p10>", true),
+            vec![part("a < b", ELLIPSIS_PAUSE_MS), part("", 10)]
+        );
+    }
+
+    #[test]
+    fn test_split_speaker_tags() {
+        let segments = split_speaker_tags("Intro. [spk:3] Hello. [spk: 7 ]Goodbye.").unwrap();
+        let speakers = Vec::from_iter(segments.iter().map(|s| s.speaker));
+        assert_eq!(speakers, vec![None, Some((3, 7)), Some((7, 22))]);
+        assert_eq!(segments[2].text, "Goodbye.");
+        assert!(split_speaker_tags("[spk:x] Hi").is_err());
+        assert!(split_speaker_tags("[spk:1 Hi").is_err());
+    }
+}